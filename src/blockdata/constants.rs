@@ -26,6 +26,7 @@ use hashes::sha256d;
 use blockdata::opcodes;
 use blockdata::script;
 use blockdata::transaction::{OutPoint, Transaction, TxOut, TxIn};
+use blockdata::witness::Witness;
 use blockdata::block::{Block, BlockHeader};
 use network::constants::Network;
 use util::uint::Uint256;
@@ -46,6 +47,8 @@ pub const MAX_BLOCK_WEIGHT: u32 = 4_000_000;
 pub const MIN_TRANSACTION_WEIGHT: u32 = 4 * 60;
 /// The factor that non-witness serialization data is multiplied by during weight calculation
 pub const WITNESS_SCALE_FACTOR: usize = 4;
+/// The maximum allowed signature operations cost for a block, see BIP 141 (network rule)
+pub const MAX_BLOCK_SIGOPS_COST: u32 = 80_000;
 
 
 /// In Bitcoind this is insanely described as ~((u256)0 >> 32)
@@ -79,7 +82,7 @@ fn bitcoin_genesis_tx() -> Transaction {
         previous_output: OutPoint::null(),
         script_sig: in_script,
         sequence: MAX_SEQUENCE,
-        witness: vec![],
+        witness: Witness::default(),
     });
 
     // Outputs
@@ -0,0 +1,232 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Fee rates
+//!
+//! Fee rates are kept in satoshis per 1000 weight units, which is exact for
+//! rates given in satoshis per virtual byte. Fees computed from rates are
+//! rounded up, so that they pay at least the rate, and rates computed from
+//! fees are rounded down, so that the fee pays at least the resulting rate.
+//!
+
+use std::{fmt, ops};
+
+use blockdata::weight::Weight;
+use util::amount::Amount;
+
+#[cfg(feature = "serde")] use serde;
+
+/// A fee rate, in satoshis per 1000 weight units
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// Zero fee rate
+    pub const ZERO: FeeRate = FeeRate(0);
+
+    /// The default minimum fee rate of the transactions Bitcoin Core relays,
+    /// one satoshi per virtual byte
+    pub const MIN_RELAY: FeeRate = FeeRate(250);
+
+    /// Creates a fee rate of `sat_kwu` satoshis per 1000 weight units
+    pub fn from_sat_per_kwu(sat_kwu: u64) -> FeeRate {
+        FeeRate(sat_kwu)
+    }
+
+    /// Creates a fee rate of `sat_vb` satoshis per virtual byte, returning
+    /// `None` on overflow
+    pub fn from_sat_per_vb(sat_vb: u64) -> Option<FeeRate> {
+        sat_vb.checked_mul(250).map(FeeRate)
+    }
+
+    /// Creates a fee rate of `sat_kvb` satoshis per 1000 virtual bytes, the
+    /// unit of Bitcoin Core, rounded down to a whole satoshi per 1000 weight
+    /// units
+    pub fn from_sat_per_kvb(sat_kvb: u64) -> FeeRate {
+        FeeRate(sat_kvb / 4)
+    }
+
+    /// The fee rate in satoshis per 1000 weight units
+    pub fn to_sat_per_kwu(self) -> u64 {
+        self.0
+    }
+
+    /// The fee rate in satoshis per virtual byte, rounded down
+    pub fn to_sat_per_vb_floor(self) -> u64 {
+        self.0 / 250
+    }
+
+    /// The fee rate in satoshis per virtual byte, rounded up
+    pub fn to_sat_per_vb_ceil(self) -> u64 {
+        self.0 / 250 + if self.0 % 250 == 0 { 0 } else { 1 }
+    }
+
+    /// The fee of `weight` at this rate, rounded up to a whole satoshi.
+    /// Returns [None] if overflow occurred.
+    pub fn fee_wu(self, weight: Weight) -> Option<Amount> {
+        let fee = self.0.checked_mul(weight.to_wu())?;
+        Some(Amount::from_sat(fee / 1000 + if fee % 1000 == 0 { 0 } else { 1 }))
+    }
+
+    /// The fee of `vb` virtual bytes at this rate, rounded up to a whole
+    /// satoshi.
+    /// Returns [None] if overflow occurred.
+    pub fn fee_vb(self, vb: u64) -> Option<Amount> {
+        self.fee_wu(Weight::from_vb(vb)?)
+    }
+
+    /// Checked multiplication.
+    /// Returns [None] if overflow occurred.
+    pub fn checked_mul(self, rhs: u64) -> Option<FeeRate> {
+        self.0.checked_mul(rhs).map(FeeRate)
+    }
+
+    /// Checked integer division.
+    /// Returns [None] if `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<FeeRate> {
+        self.0.checked_div(rhs).map(FeeRate)
+    }
+}
+
+impl From<FeeRate> for u64 {
+    fn from(fee_rate: FeeRate) -> u64 {
+        fee_rate.0
+    }
+}
+
+impl fmt::Debug for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FeeRate({} sat/kwu)", self.0)
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/kwu", self.0)
+    }
+}
+
+impl ops::Mul<u64> for FeeRate {
+    type Output = FeeRate;
+
+    fn mul(self, rhs: u64) -> Self::Output {
+        self.checked_mul(rhs).expect("FeeRate multiplication error")
+    }
+}
+
+impl ops::Div<u64> for FeeRate {
+    type Output = FeeRate;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        self.checked_div(rhs).expect("FeeRate division error")
+    }
+}
+
+/// The fee of a weight at a fee rate, see [FeeRate::fee_wu]
+impl ops::Mul<Weight> for FeeRate {
+    type Output = Amount;
+
+    fn mul(self, rhs: Weight) -> Self::Output {
+        self.fee_wu(rhs).expect("FeeRate multiplication error")
+    }
+}
+
+/// The fee of a weight at a fee rate, see [FeeRate::fee_wu]
+impl ops::Mul<FeeRate> for Weight {
+    type Output = Amount;
+
+    fn mul(self, rhs: FeeRate) -> Self::Output {
+        rhs * self
+    }
+}
+
+/// The fee rate a fee pays for a weight, see [Amount::checked_div_by_weight]
+impl ops::Div<Weight> for Amount {
+    type Output = FeeRate;
+
+    fn div(self, rhs: Weight) -> Self::Output {
+        self.checked_div_by_weight(rhs).expect("Amount division error")
+    }
+}
+
+/// Serializes as a number of satoshis per 1000 weight units
+#[cfg(feature = "serde")]
+impl serde::Serialize for FeeRate {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FeeRate {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<FeeRate, D::Error> {
+        Ok(FeeRate(serde::Deserialize::deserialize(d)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_test() {
+        assert_eq!(FeeRate::from_sat_per_vb(1), Some(FeeRate::MIN_RELAY));
+        assert_eq!(FeeRate::from_sat_per_vb(u64::max_value() / 250 + 1), None);
+        assert_eq!(FeeRate::from_sat_per_kvb(1000), FeeRate::MIN_RELAY);
+        assert_eq!(FeeRate::from_sat_per_kvb(1003), FeeRate::from_sat_per_kwu(250));
+        assert_eq!(FeeRate::from_sat_per_kwu(499).to_sat_per_vb_floor(), 1);
+        assert_eq!(FeeRate::from_sat_per_kwu(499).to_sat_per_vb_ceil(), 2);
+        assert_eq!(FeeRate::from_sat_per_kwu(500).to_sat_per_vb_ceil(), 2);
+        assert_eq!(u64::from(FeeRate::MIN_RELAY), 250);
+        assert_eq!(FeeRate::MIN_RELAY.to_string(), "250 sat/kwu");
+    }
+
+    #[test]
+    fn fee_test() {
+        let rate = FeeRate::from_sat_per_vb(3).unwrap();
+        assert_eq!(rate.fee_vb(141), Some(Amount::from_sat(423)));
+        assert_eq!(rate.fee_wu(Weight::from_wu(561)), Some(Amount::from_sat(421)));
+        assert_eq!(rate * Weight::from_wu(561), Amount::from_sat(421));
+        assert_eq!(Weight::from_wu(561) * rate, Amount::from_sat(421));
+        assert_eq!(FeeRate::from_sat_per_kwu(1).fee_wu(Weight::from_wu(1)), Some(Amount::from_sat(1)));
+        assert_eq!(FeeRate::ZERO.fee_wu(Weight::MAX_BLOCK), Some(Amount::ZERO));
+        assert_eq!(rate.fee_wu(Weight::from_wu(u64::max_value())), None);
+        assert_eq!(rate.fee_vb(u64::max_value()), None);
+
+        // Rates computed from fees are rounded down, so the fee pays them
+        assert_eq!(Amount::from_sat(421) / Weight::from_wu(561), FeeRate::from_sat_per_kwu(750));
+        assert_eq!(Amount::from_sat(420).checked_div_by_weight(Weight::from_wu(561)), Some(FeeRate::from_sat_per_kwu(748)));
+        assert_eq!(Amount::from_sat(420).checked_div_by_weight(Weight::ZERO), None);
+        assert_eq!(Amount::max_value().checked_div_by_weight(Weight::from_wu(1)), None);
+        assert_eq!(Amount::from_sat(423).checked_div_by_vsize(141), Some(rate));
+        assert_eq!(Amount::from_sat(422).checked_div_by_vsize(141), Some(FeeRate::from_sat_per_kwu(748)));
+        assert_eq!(Amount::from_sat(423).checked_div_by_vsize(0), None);
+        assert_eq!(Amount::from_sat(423).checked_div_by_vsize(u64::max_value()), None);
+
+        assert_eq!(rate * 2, FeeRate::from_sat_per_kwu(1500));
+        assert_eq!(rate / 2, FeeRate::from_sat_per_kwu(375));
+        assert_eq!(rate.checked_mul(u64::max_value()), None);
+        assert_eq!(rate.checked_div(0), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        use serde_json;
+
+        let rate = FeeRate::from_sat_per_kwu(253);
+        assert_eq!(serde_json::to_string(&rate).unwrap(), "253");
+        assert_eq!(serde_json::from_str::<FeeRate>("253").unwrap(), rate);
+    }
+}
@@ -0,0 +1,598 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Lock times
+//!
+//! Typed views of the absolute lock time of a transaction, and of the input
+//! sequence numbers which carry relative lock times as of BIP68. Lock times
+//! count either blocks or time, and lock times of different units can't be
+//! compared. Transactions keep the raw consensus values, from which these
+//! types are converted with `from_consensus`.
+//!
+
+use std::{cmp, error, fmt, num, str};
+
+#[cfg(feature = "serde")] use serde;
+
+/// Absolute lock times below this value are block heights, and the others
+/// UNIX timestamps
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+/// Disables the relative lock time of a sequence number
+pub const SEQUENCE_LOCK_TIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// Makes the relative lock time of a sequence number count units of 512
+/// seconds instead of blocks
+pub const SEQUENCE_LOCK_TIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// Bits of a sequence number holding its relative lock time
+pub const SEQUENCE_LOCK_TIME_MASK: u32 = 0x0000ffff;
+
+/// Number of bits the 512 second units of relative lock times are shifted by
+/// to give seconds
+const SEQUENCE_LOCK_TIME_GRANULARITY: u32 = 9;
+
+/// A lock time error
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The block height is not below [LOCK_TIME_THRESHOLD]
+    InvalidHeight(u32),
+    /// The timestamp is below [LOCK_TIME_THRESHOLD]
+    InvalidTime(u32),
+    /// This number of seconds is more than a relative lock time can hold
+    IntervalOverflow(u32),
+    /// The string is not a 32-bit decimal number
+    Parse(num::ParseIntError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidHeight(height) => write!(f, "invalid lock time block height {}", height),
+            Error::InvalidTime(time) => write!(f, "invalid lock time timestamp {}", time),
+            Error::IntervalOverflow(seconds) => write!(f, "relative lock time of {} seconds overflows", seconds),
+            Error::Parse(ref e) => write!(f, "error parsing lock time: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Parse(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A block height of an absolute lock time, below [LOCK_TIME_THRESHOLD]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Height(u32);
+
+impl Height {
+    /// Creates a height from a consensus lock time value, which must be
+    /// below [LOCK_TIME_THRESHOLD]
+    pub fn from_consensus(height: u32) -> Result<Height, Error> {
+        if height < LOCK_TIME_THRESHOLD {
+            Ok(Height(height))
+        } else {
+            Err(Error::InvalidHeight(height))
+        }
+    }
+
+    /// The consensus value of the height
+    pub fn to_consensus_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// A UNIX timestamp of an absolute lock time, from [LOCK_TIME_THRESHOLD]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Time(u32);
+
+impl Time {
+    /// Creates a timestamp from a consensus lock time value, which must not
+    /// be below [LOCK_TIME_THRESHOLD]
+    pub fn from_consensus(time: u32) -> Result<Time, Error> {
+        if time >= LOCK_TIME_THRESHOLD {
+            Ok(Time(time))
+        } else {
+            Err(Error::InvalidTime(time))
+        }
+    }
+
+    /// The consensus value of the timestamp
+    pub fn to_consensus_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// The absolute lock time of a transaction, or the one required by
+/// OP_CHECKLOCKTIMEVERIFY
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum LockTime {
+    /// A block height
+    Blocks(Height),
+    /// A UNIX timestamp
+    Seconds(Time),
+}
+
+impl LockTime {
+    /// The lock time of transactions which are not locked
+    pub const ZERO: LockTime = LockTime::Blocks(Height(0));
+
+    /// Interprets a consensus lock time value
+    pub fn from_consensus(lock_time: u32) -> LockTime {
+        if lock_time < LOCK_TIME_THRESHOLD {
+            LockTime::Blocks(Height(lock_time))
+        } else {
+            LockTime::Seconds(Time(lock_time))
+        }
+    }
+
+    /// Creates a lock time at a block height, which must be below
+    /// [LOCK_TIME_THRESHOLD]
+    pub fn from_height(height: u32) -> Result<LockTime, Error> {
+        Height::from_consensus(height).map(LockTime::Blocks)
+    }
+
+    /// Creates a lock time at a UNIX timestamp, which must not be below
+    /// [LOCK_TIME_THRESHOLD]
+    pub fn from_time(time: u32) -> Result<LockTime, Error> {
+        Time::from_consensus(time).map(LockTime::Seconds)
+    }
+
+    /// The consensus value of the lock time
+    pub fn to_consensus_u32(self) -> u32 {
+        match self {
+            LockTime::Blocks(Height(n)) | LockTime::Seconds(Time(n)) => n,
+        }
+    }
+
+    /// Whether the lock time is a block height
+    pub fn is_block_height(self) -> bool {
+        match self {
+            LockTime::Blocks(_) => true,
+            LockTime::Seconds(_) => false,
+        }
+    }
+
+    /// Whether the lock time is a UNIX timestamp
+    pub fn is_block_time(self) -> bool {
+        !self.is_block_height()
+    }
+
+    /// Whether both lock times are block heights, or both timestamps
+    pub fn is_same_unit(self, other: LockTime) -> bool {
+        self.is_block_height() == other.is_block_height()
+    }
+
+    /// Whether a transaction with this lock time can be included in the
+    /// block at `height`, whose previous block has the median time past
+    /// `time`, like Bitcoin Core's `IsFinalTx` ignoring sequence numbers
+    pub fn is_satisfied_by(self, height: u32, time: u32) -> bool {
+        match self {
+            LockTime::Blocks(Height(n)) => n < height,
+            LockTime::Seconds(Time(n)) => n < time,
+        }
+    }
+
+    /// Whether a transaction lock time of `other` satisfies this lock time
+    /// as required by OP_CHECKLOCKTIMEVERIFY, being in the same unit and at
+    /// least as late
+    pub fn is_implied_by(self, other: LockTime) -> bool {
+        match self.partial_cmp(&other) {
+            Some(cmp::Ordering::Less) | Some(cmp::Ordering::Equal) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Lock times are only ordered when they have the same unit
+impl PartialOrd for LockTime {
+    fn partial_cmp(&self, other: &LockTime) -> Option<cmp::Ordering> {
+        match (*self, *other) {
+            (LockTime::Blocks(a), LockTime::Blocks(b)) => a.partial_cmp(&b),
+            (LockTime::Seconds(a), LockTime::Seconds(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+}
+
+impl From<u32> for LockTime {
+    fn from(lock_time: u32) -> LockTime {
+        LockTime::from_consensus(lock_time)
+    }
+}
+
+impl From<LockTime> for u32 {
+    fn from(lock_time: LockTime) -> u32 {
+        lock_time.to_consensus_u32()
+    }
+}
+
+/// Displays the consensus value
+impl fmt::Display for LockTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.to_consensus_u32(), f)
+    }
+}
+
+/// Parses a decimal consensus value
+impl str::FromStr for LockTime {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LockTime, Error> {
+        s.parse().map(LockTime::from_consensus).map_err(Error::Parse)
+    }
+}
+
+/// A relative lock time, as carried by sequence numbers or required by
+/// OP_CHECKSEQUENCEVERIFY
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum RelativeLockTime {
+    /// A number of blocks
+    Blocks(u16),
+    /// A number of intervals of 512 seconds
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    /// Creates a relative lock time of at least `seconds`, rounded up to
+    /// intervals of 512 seconds
+    pub fn from_seconds_ceil(seconds: u32) -> Result<RelativeLockTime, Error> {
+        let intervals = (seconds >> SEQUENCE_LOCK_TIME_GRANULARITY) +
+            if seconds % 512 == 0 { 0 } else { 1 };
+        if intervals > SEQUENCE_LOCK_TIME_MASK {
+            return Err(Error::IntervalOverflow(seconds));
+        }
+        Ok(RelativeLockTime::Time(intervals as u16))
+    }
+
+    /// Creates a relative lock time of at most `seconds`, rounded down to
+    /// intervals of 512 seconds
+    pub fn from_seconds_floor(seconds: u32) -> Result<RelativeLockTime, Error> {
+        let intervals = seconds >> SEQUENCE_LOCK_TIME_GRANULARITY;
+        if intervals > SEQUENCE_LOCK_TIME_MASK {
+            return Err(Error::IntervalOverflow(seconds));
+        }
+        Ok(RelativeLockTime::Time(intervals as u16))
+    }
+
+    /// Whether both lock times count blocks, or both time
+    pub fn is_same_unit(self, other: RelativeLockTime) -> bool {
+        match (self, other) {
+            (RelativeLockTime::Blocks(_), RelativeLockTime::Blocks(_)) |
+            (RelativeLockTime::Time(_), RelativeLockTime::Time(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether an input relative lock time of `other` satisfies this lock
+    /// time as required by OP_CHECKSEQUENCEVERIFY, being in the same unit
+    /// and at least as long
+    pub fn is_implied_by(self, other: RelativeLockTime) -> bool {
+        match self.partial_cmp(&other) {
+            Some(cmp::Ordering::Less) | Some(cmp::Ordering::Equal) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Lock times are only ordered when they have the same unit
+impl PartialOrd for RelativeLockTime {
+    fn partial_cmp(&self, other: &RelativeLockTime) -> Option<cmp::Ordering> {
+        match (*self, *other) {
+            (RelativeLockTime::Blocks(a), RelativeLockTime::Blocks(b)) |
+            (RelativeLockTime::Time(a), RelativeLockTime::Time(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+}
+
+/// The sequence number of a transaction input, which can enable the lock time
+/// of the transaction, signal replaceability (BIP125) or carry a relative
+/// lock time (BIP68)
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Sequence(pub u32);
+
+impl Sequence {
+    /// The final sequence number, which disables the lock time of the
+    /// transaction if all its inputs have it
+    pub const MAX: Sequence = Sequence(0xffffffff);
+
+    /// The largest sequence number which enables the lock time of the
+    /// transaction without signaling replaceability
+    pub const ENABLE_LOCKTIME_NO_RBF: Sequence = Sequence(0xfffffffe);
+
+    /// The largest sequence number signaling replaceability, without
+    /// relative lock time
+    pub const ENABLE_RBF_NO_LOCKTIME: Sequence = Sequence(0xfffffffd);
+
+    /// Interprets a consensus sequence number
+    pub fn from_consensus(sequence: u32) -> Sequence {
+        Sequence(sequence)
+    }
+
+    /// The consensus value of the sequence number
+    pub fn to_consensus_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Whether the sequence number is final, not enabling the lock time of
+    /// the transaction
+    pub fn is_final(self) -> bool {
+        self == Sequence::MAX
+    }
+
+    /// Whether the sequence number enables the lock time of the transaction
+    pub fn enables_absolute_lock_time(self) -> bool {
+        !self.is_final()
+    }
+
+    /// Whether the sequence number signals replaceability as of BIP125
+    pub fn is_rbf(self) -> bool {
+        self < Sequence::ENABLE_LOCKTIME_NO_RBF
+    }
+
+    /// Whether the sequence number carries a relative lock time, which is
+    /// only enforced for transactions of version 2 or more
+    pub fn is_relative_lock_time(self) -> bool {
+        self.0 & SEQUENCE_LOCK_TIME_DISABLE_FLAG == 0
+    }
+
+    /// The relative lock time of the sequence number, if it has one
+    pub fn to_relative_lock_time(self) -> Option<RelativeLockTime> {
+        if !self.is_relative_lock_time() {
+            return None;
+        }
+        let value = (self.0 & SEQUENCE_LOCK_TIME_MASK) as u16;
+        if self.0 & SEQUENCE_LOCK_TIME_TYPE_FLAG == 0 {
+            Some(RelativeLockTime::Blocks(value))
+        } else {
+            Some(RelativeLockTime::Time(value))
+        }
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::MAX
+    }
+}
+
+impl From<RelativeLockTime> for Sequence {
+    fn from(lock_time: RelativeLockTime) -> Sequence {
+        match lock_time {
+            RelativeLockTime::Blocks(n) => Sequence(n as u32),
+            RelativeLockTime::Time(n) => Sequence(SEQUENCE_LOCK_TIME_TYPE_FLAG | n as u32),
+        }
+    }
+}
+
+impl From<u32> for Sequence {
+    fn from(sequence: u32) -> Sequence {
+        Sequence(sequence)
+    }
+}
+
+impl From<Sequence> for u32 {
+    fn from(sequence: Sequence) -> u32 {
+        sequence.0
+    }
+}
+
+/// Displays the consensus value
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::LowerHex for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+/// Parses a decimal consensus value
+impl str::FromStr for Sequence {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Sequence, Error> {
+        s.parse().map(Sequence).map_err(Error::Parse)
+    }
+}
+
+/// Where an output spent by a transaction was confirmed, from which the
+/// relative lock time of the input spending it counts
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Confirmation {
+    /// Height of the block which confirmed the output, or of the next block
+    /// if it is unconfirmed
+    pub height: u32,
+    /// Median time past of the block before the one at `height`
+    pub time: u32,
+}
+
+/// The relative lock times of the inputs of a transaction combined, see
+/// `Transaction::sequence_locks`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct SequenceLocks {
+    /// The last block height at which the transaction is still locked
+    pub min_height: Option<u32>,
+    /// The last median time past at which the transaction is still locked
+    pub min_time: Option<u32>,
+}
+
+impl SequenceLocks {
+    /// Whether the transaction can be included in the block at `height`,
+    /// whose previous block has the median time past `time`, like Bitcoin
+    /// Core's `EvaluateSequenceLocks`
+    pub fn is_satisfied_by(&self, height: u32, time: u32) -> bool {
+        let height_ok = match self.min_height {
+            Some(min_height) => height > min_height,
+            None => true,
+        };
+        let time_ok = match self.min_time {
+            Some(min_time) => time > min_time,
+            None => true,
+        };
+        height_ok && time_ok
+    }
+}
+
+/// Serializes as the consensus value
+#[cfg(feature = "serde")]
+impl serde::Serialize for LockTime {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u32(self.to_consensus_u32())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LockTime {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<LockTime, D::Error> {
+        Ok(LockTime::from_consensus(serde::Deserialize::deserialize(d)?))
+    }
+}
+
+/// Serializes as the consensus value
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sequence {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u32(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sequence {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Sequence, D::Error> {
+        Ok(Sequence(serde::Deserialize::deserialize(d)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_time_test() {
+        assert_eq!(LockTime::from_consensus(499_999_999), LockTime::Blocks(Height::from_consensus(499_999_999).unwrap()));
+        assert_eq!(LockTime::from_consensus(500_000_000), LockTime::Seconds(Time::from_consensus(500_000_000).unwrap()));
+        assert_eq!(Height::from_consensus(500_000_000), Err(Error::InvalidHeight(500_000_000)));
+        assert_eq!(Time::from_consensus(499_999_999), Err(Error::InvalidTime(499_999_999)));
+        assert_eq!(LockTime::from_height(500_000_000), Err(Error::InvalidHeight(500_000_000)));
+        assert_eq!(LockTime::from_time(499_999_999), Err(Error::InvalidTime(499_999_999)));
+        assert_eq!(LockTime::from_time(1_600_000_000).unwrap().to_consensus_u32(), 1_600_000_000);
+        assert!(LockTime::ZERO.is_block_height());
+        assert!(LockTime::from(1_600_000_000).is_block_time());
+
+        assert_eq!("700000".parse::<LockTime>(), Ok(LockTime::from_height(700_000).unwrap()));
+        assert_eq!(LockTime::from_time(1_600_000_000).unwrap().to_string(), "1600000000");
+        assert!(match "-1".parse::<LockTime>() {
+            Err(Error::Parse(_)) => true,
+            _ => false,
+        });
+
+        // Heights and times don't compare
+        let height = LockTime::from_height(700_000).unwrap();
+        let time = LockTime::from_time(1_600_000_000).unwrap();
+        assert!(height < LockTime::from_height(700_001).unwrap());
+        assert_eq!(height.partial_cmp(&time), None);
+        assert!(height.is_implied_by(height));
+        assert!(height.is_implied_by(LockTime::from_height(700_001).unwrap()));
+        assert!(!height.is_implied_by(LockTime::from_height(699_999).unwrap()));
+        assert!(!height.is_implied_by(time));
+        assert!(!height.is_same_unit(time));
+
+        assert!(height.is_satisfied_by(700_001, 0));
+        assert!(!height.is_satisfied_by(700_000, u32::max_value()));
+        assert!(time.is_satisfied_by(0, 1_600_000_001));
+        assert!(!time.is_satisfied_by(u32::max_value(), 1_600_000_000));
+    }
+
+    #[test]
+    fn sequence_test() {
+        assert!(Sequence::MAX.is_final());
+        assert!(!Sequence::MAX.is_rbf());
+        assert!(!Sequence::ENABLE_LOCKTIME_NO_RBF.is_rbf());
+        assert!(Sequence::ENABLE_LOCKTIME_NO_RBF.enables_absolute_lock_time());
+        assert!(Sequence::ENABLE_RBF_NO_LOCKTIME.is_rbf());
+        assert_eq!(Sequence::ENABLE_RBF_NO_LOCKTIME.to_relative_lock_time(), None);
+        assert_eq!(Sequence::default(), Sequence::MAX);
+
+        assert_eq!(Sequence(144).to_relative_lock_time(), Some(RelativeLockTime::Blocks(144)));
+        assert_eq!(Sequence(0x0040_0010).to_relative_lock_time(), Some(RelativeLockTime::Time(16)));
+        // Bits outside of the type flag and value are ignored
+        assert_eq!(Sequence(0x7fbf_0090).to_relative_lock_time(), Some(RelativeLockTime::Blocks(144)));
+        assert_eq!(Sequence::from(RelativeLockTime::Time(16)), Sequence(0x0040_0010));
+        assert_eq!(Sequence::from(RelativeLockTime::Blocks(144)), Sequence(144));
+
+        assert_eq!("4294967294".parse::<Sequence>(), Ok(Sequence::ENABLE_LOCKTIME_NO_RBF));
+        assert_eq!(Sequence::MAX.to_string(), "4294967295");
+        assert_eq!(format!("{:x}", Sequence::ENABLE_RBF_NO_LOCKTIME), "fffffffd");
+    }
+
+    #[test]
+    fn relative_lock_time_test() {
+        assert_eq!(RelativeLockTime::from_seconds_floor(1023), Ok(RelativeLockTime::Time(1)));
+        assert_eq!(RelativeLockTime::from_seconds_ceil(1023), Ok(RelativeLockTime::Time(2)));
+        assert_eq!(RelativeLockTime::from_seconds_ceil(1024), Ok(RelativeLockTime::Time(2)));
+        assert_eq!(RelativeLockTime::from_seconds_floor(512 * 65536 - 1), Ok(RelativeLockTime::Time(65535)));
+        assert_eq!(RelativeLockTime::from_seconds_ceil(512 * 65535 + 1), Err(Error::IntervalOverflow(512 * 65535 + 1)));
+        assert_eq!(RelativeLockTime::from_seconds_floor(512 * 65536), Err(Error::IntervalOverflow(512 * 65536)));
+
+        let blocks = RelativeLockTime::Blocks(10);
+        let time = RelativeLockTime::Time(10);
+        assert_eq!(blocks.partial_cmp(&time), None);
+        assert!(blocks < RelativeLockTime::Blocks(11));
+        assert!(blocks.is_implied_by(RelativeLockTime::Blocks(11)));
+        assert!(!blocks.is_implied_by(RelativeLockTime::Blocks(9)));
+        assert!(!blocks.is_implied_by(time));
+        assert!(!blocks.is_same_unit(time));
+    }
+
+    #[test]
+    fn sequence_locks_test() {
+        assert!(SequenceLocks::default().is_satisfied_by(0, 0));
+        let locks = SequenceLocks { min_height: Some(100), min_time: Some(1_600_000_000) };
+        assert!(locks.is_satisfied_by(101, 1_600_000_001));
+        assert!(!locks.is_satisfied_by(100, 1_600_000_001));
+        assert!(!locks.is_satisfied_by(101, 1_600_000_000));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        use serde_json;
+
+        assert_eq!(serde_json::to_string(&LockTime::from_height(700_000).unwrap()).unwrap(), "700000");
+        assert_eq!(serde_json::from_str::<LockTime>("1600000000").unwrap(), LockTime::from_time(1_600_000_000).unwrap());
+        assert_eq!(serde_json::to_string(&Sequence::MAX).unwrap(), "4294967295");
+        assert_eq!(serde_json::from_str::<Sequence>("144").unwrap(), Sequence(144));
+    }
+}
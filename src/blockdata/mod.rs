@@ -22,5 +22,9 @@ pub mod constants;
 pub mod opcodes;
 pub mod script;
 pub mod transaction;
+pub mod witness;
+pub mod weight;
+pub mod fee_rate;
+pub mod locktime;
 pub mod block;
 
@@ -414,10 +414,14 @@ pub mod all {
     pub const OP_NOP9: All = All {code: 0xb8};
     /// Does nothing
     pub const OP_NOP10: All = All {code: 0xb9};
+    /// OP_CHECKSIG with a counter, only available in tapscript; acts as
+    /// OP_RETURN elsewhere
+    pub const OP_CHECKSIGADD: All = All {code: 0xba};
+    /// Former name of OP_CHECKSIGADD
+    #[deprecated(since = "0.26.1", note = "please use `OP_CHECKSIGADD` instead")]
+    pub const OP_RETURN_186: All = OP_CHECKSIGADD;
     // Every other opcode acts as OP_RETURN
     /// Synonym for OP_RETURN
-    pub const OP_RETURN_186: All = All {code: 0xba};
-    /// Synonym for OP_RETURN
     pub const OP_RETURN_187: All = All {code: 0xbb};
     /// Synonym for OP_RETURN
     pub const OP_RETURN_188: All = All {code: 0xbc};
@@ -649,6 +653,7 @@ impl fmt::Debug for All {
             all::OP_CHECKMULTISIGVERIFY => write!(f, "CHECKMULTISIGVERIFY"),
             all::OP_CLTV => write!(f, "CLTV"),
             all::OP_CSV => write!(f, "CSV"),
+            all::OP_CHECKSIGADD => write!(f, "CHECKSIGADD"),
             All {code: x} if x >= all::OP_NOP1.code && x <= all::OP_NOP10.code => write!(f, "NOP{}", x - all::OP_NOP1.code + 1),
             All {code: x} => write!(f, "RETURN_{}", x),
         }
@@ -677,7 +682,7 @@ impl All {
         // 75 opcodes
         } else if self == all::OP_RESERVED || self == all::OP_VER || self == all::OP_RETURN ||
                   self == all::OP_RESERVED1 || self == all::OP_RESERVED2 ||
-                  self.code >= all::OP_RETURN_186.code {
+                  self.code >= all::OP_CHECKSIGADD.code {
             Class::ReturnOp
         // 1 opcode
         } else if self == all::OP_PUSHNUM_NEG1 {
@@ -695,6 +700,37 @@ impl All {
         }
     }
 
+    /// Classifies an Opcode into a broad class, according to the rules of
+    /// the given script context
+    #[inline]
+    pub fn classify_ctx(self, ctx: ClassifyContext) -> Class {
+        match ctx {
+            ClassifyContext::Legacy => self.classify(),
+            ClassifyContext::TapScript => {
+                if self.is_tapscript_success() {
+                    Class::SuccessOp
+                } else if self == all::OP_CHECKMULTISIG || self == all::OP_CHECKMULTISIGVERIFY {
+                    Class::ReturnOp
+                } else if self == all::OP_CHECKSIGADD {
+                    Class::Ordinary(Ordinary::OP_CHECKSIGADD)
+                } else {
+                    self.classify()
+                }
+            }
+        }
+    }
+
+    /// Whether this is one of the OP_SUCCESSx opcodes of BIP342, which make
+    /// a tapscript succeed unconditionally when present anywhere in it
+    #[inline]
+    pub fn is_tapscript_success(self) -> bool {
+        match self.code {
+            80 | 98 | 126..=129 | 131..=134 | 137..=138 |
+            141..=142 | 149..=153 | 187..=254 => true,
+            _ => false,
+        }
+    }
+
     /// Encode as a byte
     #[inline]
     pub fn into_u8(self) -> u8 {
@@ -731,6 +767,27 @@ pub static OP_NOP2: All = all::OP_CLTV;
 /// previously called OP_NOP3
 pub static OP_NOP3: All = all::OP_CSV;
 
+/// The kind of script an opcode is found in, which matters for its
+/// classification since tapscript (BIP342) changed the meaning of some opcodes
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ClassifyContext {
+    /// Legacy, P2SH and segwit v0 scripts
+    Legacy,
+    /// Tapscript, i.e. the leaf scripts of taproot script path spends
+    TapScript,
+}
+
+impl ClassifyContext {
+    /// Whether the argument of OP_IF and OP_NOTIF must be either empty or
+    /// exactly `[0x01]` as a consensus rule. This MINIMALIF rule is only
+    /// a standardness rule for segwit v0 scripts and doesn't apply to legacy
+    /// scripts.
+    #[inline]
+    pub fn requires_minimal_if(self) -> bool {
+        self == ClassifyContext::TapScript
+    }
+}
+
 /// Broad categories of opcodes with similar behavior
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Class {
@@ -740,6 +797,8 @@ pub enum Class {
     PushBytes(u32),
     /// Fails the script if executed
     ReturnOp,
+    /// Makes the script succeed even if not executed (tapscript only)
+    SuccessOp,
     /// Fails the script even if not executed
     IllegalOp,
     /// Does nothing
@@ -781,7 +840,7 @@ macro_rules! ordinary_opcode {
     );
 }
 
-// "Ordinary" opcodes -- should be 60 of these
+// "Ordinary" opcodes -- should be 61 of these
 ordinary_opcode! {
     // pushdata
     OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4,
@@ -803,7 +862,9 @@ ordinary_opcode! {
     // crypto
     OP_RIPEMD160, OP_SHA1, OP_SHA256, OP_HASH160, OP_HASH256,
     OP_CODESEPARATOR, OP_CHECKSIG, OP_CHECKSIGVERIFY,
-    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY,
+    // tapscript only
+    OP_CHECKSIGADD
 }
 
 impl Ordinary {
@@ -1021,7 +1082,7 @@ mod tests {
         roundtrip!(unique, OP_NOP8);
         roundtrip!(unique, OP_NOP9);
         roundtrip!(unique, OP_NOP10);
-        roundtrip!(unique, OP_RETURN_186);
+        roundtrip!(unique, OP_CHECKSIGADD);
         roundtrip!(unique, OP_RETURN_187);
         roundtrip!(unique, OP_RETURN_188);
         roundtrip!(unique, OP_RETURN_189);
@@ -1093,5 +1154,37 @@ mod tests {
         roundtrip!(unique, OP_RETURN_255);
         assert_eq!(unique.len(), 256);
     }
+
+    #[test]
+    fn classify_tapscript() {
+        let legacy = ClassifyContext::Legacy;
+        let tapscript = ClassifyContext::TapScript;
+
+        assert_eq!(all::OP_CHECKSIGADD.classify_ctx(legacy), Class::ReturnOp);
+        assert_eq!(all::OP_CHECKSIGADD.classify_ctx(tapscript), Class::Ordinary(Ordinary::OP_CHECKSIGADD));
+        assert_eq!(all::OP_CHECKMULTISIG.classify_ctx(legacy), Class::Ordinary(Ordinary::OP_CHECKMULTISIG));
+        assert_eq!(all::OP_CHECKMULTISIG.classify_ctx(tapscript), Class::ReturnOp);
+        assert_eq!(all::OP_CHECKMULTISIGVERIFY.classify_ctx(tapscript), Class::ReturnOp);
+        assert_eq!(all::OP_CAT.classify_ctx(legacy), Class::IllegalOp);
+        assert_eq!(all::OP_CAT.classify_ctx(tapscript), Class::SuccessOp);
+        assert_eq!(all::OP_VERIF.classify_ctx(tapscript), Class::IllegalOp);
+        assert_eq!(all::OP_RESERVED.classify_ctx(tapscript), Class::SuccessOp);
+        assert_eq!(all::OP_RETURN.classify_ctx(tapscript), Class::ReturnOp);
+        assert_eq!(all::OP_RETURN_255.classify_ctx(tapscript), Class::ReturnOp);
+        assert_eq!(all::OP_NOP4.classify_ctx(tapscript), Class::NoOp);
+        assert_eq!(all::OP_CHECKSIG.classify_ctx(tapscript), Class::Ordinary(Ordinary::OP_CHECKSIG));
+
+        // 0x50, 0x62, 0x7e-0x81, 0x83-0x86, 0x89-0x8a, 0x8d-0x8e, 0x95-0x99, 0xbb-0xfe
+        let success = (0..=255u8).filter(|&b| All::from(b).is_tapscript_success()).count();
+        assert_eq!(success, 1 + 1 + 4 + 4 + 2 + 2 + 5 + 68);
+        for b in 0..=255u8 {
+            let op = All::from(b);
+            assert_eq!(op.is_tapscript_success(), op.classify_ctx(tapscript) == Class::SuccessOp);
+            assert_eq!(op.classify_ctx(legacy), op.classify());
+        }
+
+        assert!(tapscript.requires_minimal_if());
+        assert!(!legacy.requires_minimal_if());
+    }
 }
 
@@ -0,0 +1,2205 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//   The rust-bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Script interpreter
+//!
+//! A native implementation of the script verification rules of Bitcoin Core,
+//! which executes legacy, P2SH, segwit v0 and taproot spends (BIP16, BIP141,
+//! BIP341 and BIP342) under a configurable set of verification flags without
+//! the `bitcoinconsensus` library.
+//!
+
+use std::{fmt, ops};
+use std::borrow::Borrow;
+
+use hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash, HashEngine};
+use secp256k1::{self, schnorrsig, Message, Secp256k1, Signing, Verification};
+
+use blockdata::locktime::{LockTime, Sequence};
+use blockdata::opcodes;
+use blockdata::opcodes::all::*;
+use blockdata::script::{read_scriptbool, Builder, Error, Instruction, InstructionIndices, Script, ScriptNum};
+use blockdata::transaction::{SigHashType, Transaction, TxOut};
+use blockdata::witness::Witness;
+use consensus::Encodable;
+use util::misc::script_find_and_remove;
+use util::sighash::{self, Annex, Prevouts, SchnorrSigHashType, SigHashCache, TAPROOT_CODESEP_POS_NONE};
+use util::taproot::{TapBranchHash, TapLeafHash, TapTweakHash};
+use util::taproot::{TAPROOT_CONTROL_BASE_SIZE, TAPROOT_CONTROL_MAX_SIZE};
+use util::taproot::{TAPROOT_CONTROL_NODE_SIZE, TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT};
+
+/// Maximum size in bytes of an element pushed onto the stack
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Maximum number of non-push operations in a legacy or segwit v0 script
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Maximum number of public keys of an OP_CHECKMULTISIG
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+/// Maximum size in bytes of a legacy or segwit v0 script
+pub const MAX_SCRIPT_SIZE: usize = 10000;
+/// Maximum number of elements of the stack and alt stack together
+pub const MAX_STACK_SIZE: usize = 1000;
+/// Validation weight consumed by each signature check of a tapscript, see BIP342
+pub const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+/// Validation weight budget of a tapscript in addition to its witness size
+pub const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+
+/// Script verification flags, selecting the consensus and policy rules
+/// which are enforced when verifying a transaction input.
+///
+/// The bit values are the ones of Bitcoin Core's `SCRIPT_VERIFY_*` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    /// No rule beyond the ones of the original script system.
+    pub const NONE: VerifyFlags = VerifyFlags(0);
+
+    /// Evaluate P2SH subscripts (BIP16).
+    pub const P2SH: VerifyFlags = VerifyFlags(1 << 0);
+
+    /// Enforce strict public key and signature hash type encodings.
+    pub const STRICTENC: VerifyFlags = VerifyFlags(1 << 1);
+
+    /// Enforce strict DER signature encoding (BIP66).
+    pub const DERSIG: VerifyFlags = VerifyFlags(1 << 2);
+
+    /// Enforce low S values in signatures (BIP62 rule 5).
+    pub const LOW_S: VerifyFlags = VerifyFlags(1 << 3);
+
+    /// Require the dummy element of OP_CHECKMULTISIG to be empty (BIP147).
+    pub const NULLDUMMY: VerifyFlags = VerifyFlags(1 << 4);
+
+    /// Require the scriptSig to only contain push operations (BIP62 rule 2).
+    pub const SIGPUSHONLY: VerifyFlags = VerifyFlags(1 << 5);
+
+    /// Require minimal pushes and number encodings (BIP62 rules 3 and 4).
+    pub const MINIMALDATA: VerifyFlags = VerifyFlags(1 << 6);
+
+    /// Fail on upgradable OP_NOPs, reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_NOPS: VerifyFlags = VerifyFlags(1 << 7);
+
+    /// Require the stack to hold a single element after execution (BIP62 rule 6).
+    pub const CLEANSTACK: VerifyFlags = VerifyFlags(1 << 8);
+
+    /// Enable OP_CHECKLOCKTIMEVERIFY (BIP65).
+    pub const CHECKLOCKTIMEVERIFY: VerifyFlags = VerifyFlags(1 << 9);
+
+    /// Enable OP_CHECKSEQUENCEVERIFY (BIP112).
+    pub const CHECKSEQUENCEVERIFY: VerifyFlags = VerifyFlags(1 << 10);
+
+    /// Evaluate segwit programs (BIP141).
+    pub const WITNESS: VerifyFlags = VerifyFlags(1 << 11);
+
+    /// Fail on unknown witness versions, reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: VerifyFlags = VerifyFlags(1 << 12);
+
+    /// Require the argument of OP_IF and OP_NOTIF in segwit v0 scripts to be
+    /// empty or exactly `[0x01]`.
+    pub const MINIMALIF: VerifyFlags = VerifyFlags(1 << 13);
+
+    /// Require the signatures of failed signature checks to be empty (BIP146).
+    pub const NULLFAIL: VerifyFlags = VerifyFlags(1 << 14);
+
+    /// Require public keys in segwit v0 scripts to be compressed.
+    pub const WITNESS_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 15);
+
+    /// Fail on OP_CODESEPARATOR and on signatures found in the script code of
+    /// legacy scripts.
+    pub const CONST_SCRIPTCODE: VerifyFlags = VerifyFlags(1 << 16);
+
+    /// Evaluate taproot programs and tapscripts (BIP341 and BIP342).
+    pub const TAPROOT: VerifyFlags = VerifyFlags(1 << 17);
+
+    /// Fail on unknown taproot leaf versions, reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: VerifyFlags = VerifyFlags(1 << 18);
+
+    /// Fail on OP_SUCCESSx opcodes in tapscripts, reserved for soft forks.
+    pub const DISCOURAGE_OP_SUCCESS: VerifyFlags = VerifyFlags(1 << 19);
+
+    /// Fail on unknown public key types in tapscripts, reserved for soft forks.
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 20);
+
+    /// The rules enforced by consensus as of the taproot soft fork: P2SH,
+    /// DERSIG, NULLDUMMY, CHECKLOCKTIMEVERIFY, CHECKSEQUENCEVERIFY, WITNESS
+    /// and TAPROOT.
+    pub const CONSENSUS: VerifyFlags = VerifyFlags(
+        1 << 0 | 1 << 2 | 1 << 4 | 1 << 9 | 1 << 10 | 1 << 11 | 1 << 17
+    );
+
+    /// The rules enforced by Bitcoin Core when relaying transactions, i.e.
+    /// every flag except SIGPUSHONLY.
+    pub const STANDARD: VerifyFlags = VerifyFlags(((1 << 21) - 1) & !(1 << 5));
+
+    // NOTE: When adding new flags, remember to update the Display impl accordingly.
+
+    /// Add [VerifyFlags] together.
+    ///
+    /// Returns itself.
+    pub fn add(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 |= other.0;
+        *self
+    }
+
+    /// Remove [VerifyFlags] from this.
+    ///
+    /// Returns itself.
+    pub fn remove(&mut self, other: VerifyFlags) -> VerifyFlags {
+        self.0 &= !other.0;
+        *self
+    }
+
+    /// Check whether [VerifyFlags] are included in this one.
+    pub fn has(self, flags: VerifyFlags) -> bool {
+        (self.0 | flags.0) == self.0
+    }
+
+    /// Get the integer representation of this [VerifyFlags].
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::LowerHex for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut flags = *self;
+        if flags == VerifyFlags::NONE {
+            return write!(f, "VerifyFlags(NONE)");
+        }
+        let mut first = true;
+        macro_rules! write_flag {
+            ($f:ident) => {
+                if flags.has(VerifyFlags::$f) {
+                    if !first {
+                        write!(f, "|")?;
+                    }
+                    first = false;
+                    write!(f, stringify!($f))?;
+                    flags.remove(VerifyFlags::$f);
+                }
+            }
+        }
+        write!(f, "VerifyFlags(")?;
+        write_flag!(P2SH);
+        write_flag!(STRICTENC);
+        write_flag!(DERSIG);
+        write_flag!(LOW_S);
+        write_flag!(NULLDUMMY);
+        write_flag!(SIGPUSHONLY);
+        write_flag!(MINIMALDATA);
+        write_flag!(DISCOURAGE_UPGRADABLE_NOPS);
+        write_flag!(CLEANSTACK);
+        write_flag!(CHECKLOCKTIMEVERIFY);
+        write_flag!(CHECKSEQUENCEVERIFY);
+        write_flag!(WITNESS);
+        write_flag!(DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM);
+        write_flag!(MINIMALIF);
+        write_flag!(NULLFAIL);
+        write_flag!(WITNESS_PUBKEYTYPE);
+        write_flag!(CONST_SCRIPTCODE);
+        write_flag!(TAPROOT);
+        write_flag!(DISCOURAGE_UPGRADABLE_TAPROOT_VERSION);
+        write_flag!(DISCOURAGE_OP_SUCCESS);
+        write_flag!(DISCOURAGE_UPGRADABLE_PUBKEYTYPE);
+        // If there are unknown flags left, we append them in hex.
+        if flags != VerifyFlags::NONE {
+            if !first {
+                write!(f, "|")?;
+            }
+            write!(f, "0x{:x}", flags)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl From<u32> for VerifyFlags {
+    fn from(f: u32) -> Self {
+        VerifyFlags(f)
+    }
+}
+
+impl From<VerifyFlags> for u32 {
+    fn from(flags: VerifyFlags) -> u32 {
+        flags.0
+    }
+}
+
+impl ops::BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self {
+        self.add(rhs)
+    }
+}
+
+impl ops::BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.add(rhs);
+    }
+}
+
+/// The kind of script being executed, which determines the signature
+/// checking and resource limit rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SigVersion {
+    /// Legacy scripts: scriptSigs, scriptPubKeys and P2SH redeem scripts
+    Base,
+    /// Segwit v0 witness scripts (BIP143)
+    WitnessV0,
+    /// Taproot key path spends (BIP341), which don't execute any script
+    Taproot,
+    /// Leaf scripts of taproot script path spends (BIP342)
+    Tapscript,
+}
+
+/// The transaction input a script is executed against
+struct Checker<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> {
+    secp: &'a Secp256k1<C>,
+    tx: &'a Transaction,
+    index: usize,
+    amount: u64,
+    prevouts: &'a Prevouts<'a, T>,
+}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Clone for Checker<'a, C, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Copy for Checker<'a, C, T> {}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Checker<'a, C, T> {
+    /// Checks an ECDSA signature of a legacy or segwit v0 script, whose
+    /// encoding was already checked against the verification flags.
+    fn check_ecdsa_sig(
+        &self,
+        cache: &mut SigHashCache<&'a Transaction>,
+        sig: &[u8],
+        pubkey: &[u8],
+        script_code: &[u8],
+        sigversion: SigVersion,
+    ) -> Result<bool, Error> {
+        let pubkey = match secp256k1::PublicKey::from_slice(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return Ok(false),
+        };
+        if sig.is_empty() {
+            return Ok(false);
+        }
+        let hash_type = sig[sig.len() - 1] as u32;
+        let mut sig = match secp256k1::Signature::from_der_lax(&sig[..sig.len() - 1]) {
+            Ok(sig) => sig,
+            Err(_) => return Ok(false),
+        };
+        sig.normalize_s();
+
+        let hash = match sigversion {
+            SigVersion::Base => {
+                // Signing the out of range SIGHASH_SINGLE output signs the
+                // number one rather than any hash of the transaction
+                let (sighash, _) = SigHashType::from_u32_consensus(hash_type).split_anyonecanpay_flag();
+                if sighash == SigHashType::Single && self.index >= self.tx.output.len() {
+                    let mut one = [0u8; 32];
+                    one[0] = 1;
+                    one
+                } else {
+                    let script_code = Script::from(remove_codeseparators(script_code));
+                    self.tx.signature_hash(self.index, &script_code, hash_type).into_inner()
+                }
+            }
+            SigVersion::WitnessV0 => {
+                let script_code = Script::from(script_code.to_vec());
+                cache.segwit_signature_hash(self.index, &script_code, self.amount, hash_type)?.into_inner()
+            }
+            SigVersion::Taproot | SigVersion::Tapscript => unreachable!("no ECDSA signatures in taproot"),
+        };
+        let msg = Message::from_slice(&hash[..]).expect("32-byte hash");
+        Ok(self.secp.verify(&msg, &sig, &pubkey).is_ok())
+    }
+
+    /// Checks a schnorr signature of a taproot key path spend, or of a
+    /// tapscript if `leaf_hash_code_separator` is given.
+    fn check_schnorr_sig(
+        &self,
+        cache: &mut SigHashCache<&'a Transaction>,
+        sig: &[u8],
+        pubkey: &[u8],
+        annex: Option<&[u8]>,
+        leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
+    ) -> Result<(), Error> {
+        let (sig, hash_type) = match sig.len() {
+            64 => (sig, SchnorrSigHashType::Default),
+            65 if sig[64] == SchnorrSigHashType::Default.as_u8() => return Err(Error::SchnorrSigHashType),
+            65 => match SchnorrSigHashType::from_u8(sig[64]) {
+                Ok(hash_type) => (&sig[..64], hash_type),
+                Err(_) => return Err(Error::SchnorrSigHashType),
+            },
+            _ => return Err(Error::SchnorrSigSize),
+        };
+        let annex = annex.map(|annex| Annex::new(annex).expect("annex starts with the annex prefix"));
+        let hash = match cache.taproot_signature_hash(
+            self.index,
+            self.prevouts,
+            annex,
+            leaf_hash_code_separator,
+            hash_type,
+        ) {
+            Ok(hash) => hash,
+            Err(sighash::Error::SingleWithoutCorrespondingOutput { .. }) => return Err(Error::SchnorrSigHashType),
+            Err(e) => return Err(Error::SigHash(e)),
+        };
+        let msg = Message::from_slice(&hash[..]).expect("32-byte hash");
+        let sig = schnorrsig::Signature::from_slice(sig).map_err(|_| Error::SchnorrSig)?;
+        let pubkey = schnorrsig::PublicKey::from_slice(pubkey).map_err(|_| Error::SchnorrSig)?;
+        self.secp.schnorrsig_verify(&sig, &msg, &pubkey).map_err(|_| Error::SchnorrSig)
+    }
+
+    /// Checks the absolute lock time required by OP_CHECKLOCKTIMEVERIFY (BIP65)
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        // Lock times beyond the range of transaction lock times can't be met
+        if lock_time > u32::max_value() as i64 {
+            return false;
+        }
+        if !LockTime::from_consensus(lock_time as u32).is_implied_by(LockTime::from_consensus(self.tx.lock_time)) {
+            return false;
+        }
+        // A final input disables the lock time of the transaction
+        Sequence(self.tx.input[self.index].sequence).enables_absolute_lock_time()
+    }
+
+    /// Checks the relative lock time required by OP_CHECKSEQUENCEVERIFY
+    /// (BIP112), whose disable flag is unset
+    fn check_sequence(&self, sequence: i64) -> bool {
+        // Relative lock times only apply from version 2 transactions
+        if (self.tx.version as u32) < 2 {
+            return false;
+        }
+        // Only the type flag and value bits matter, which are kept by the
+        // truncation
+        let required = Sequence(sequence as u32).to_relative_lock_time();
+        let actual = Sequence(self.tx.input[self.index].sequence).to_relative_lock_time();
+        match (required, actual) {
+            (Some(required), Some(actual)) => required.is_implied_by(actual),
+            _ => false,
+        }
+    }
+}
+
+/// The execution state of a single script
+struct Exec<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> {
+    checker: Checker<'a, C, T>,
+    cache: SigHashCache<&'a Transaction>,
+    script: &'a Script,
+    flags: VerifyFlags,
+    sigversion: SigVersion,
+    /// Byte position of the next opcode
+    pos: usize,
+    stack: Vec<Vec<u8>>,
+    altstack: Vec<Vec<u8>>,
+    /// Whether each of the enclosing OP_IF branches is executed
+    cond_stack: Vec<bool>,
+    /// Number of non-push opcodes executed, counted for legacy and segwit v0 scripts
+    op_count: usize,
+    /// Number of opcodes executed so far, which is the position committed to
+    /// by tapscript signatures of the next OP_CODESEPARATOR
+    opcode_pos: u32,
+    /// Byte position the script code of signatures starts at
+    codesep_begin: usize,
+    /// Opcode position of the last executed OP_CODESEPARATOR
+    codesep_pos: u32,
+    /// Leaf hash of the executed tapscript
+    leaf_hash: Option<TapLeafHash>,
+    /// Annex of the taproot witness
+    annex: Option<&'a [u8]>,
+    /// Remaining tapscript signature validation budget
+    validation_weight_left: i64,
+}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Exec<'a, C, T> {
+    fn new(
+        checker: Checker<'a, C, T>,
+        script: &'a Script,
+        stack: Vec<Vec<u8>>,
+        flags: VerifyFlags,
+        sigversion: SigVersion,
+    ) -> Result<Exec<'a, C, T>, Error> {
+        if is_legacy_or_v0(sigversion) && script.len() > MAX_SCRIPT_SIZE {
+            return Err(Error::ScriptSize);
+        }
+        Ok(Exec {
+            checker: checker,
+            cache: SigHashCache::new(checker.tx),
+            script: script,
+            flags: flags,
+            sigversion: sigversion,
+            pos: 0,
+            stack: stack,
+            altstack: vec![],
+            cond_stack: vec![],
+            op_count: 0,
+            opcode_pos: 0,
+            codesep_begin: 0,
+            codesep_pos: TAPROOT_CODESEP_POS_NONE,
+            leaf_hash: None,
+            annex: None,
+            validation_weight_left: 0,
+        })
+    }
+
+    /// Whether the whole script was executed
+    fn is_done(&self) -> bool {
+        self.pos >= self.script.len()
+    }
+
+    /// Executes the whole script and returns the resulting stack
+    fn run(mut self) -> Result<Vec<Vec<u8>>, Error> {
+        while !self.is_done() {
+            self.step()?;
+        }
+        self.finish()
+    }
+
+    /// Checks that all the conditionals are closed at the end of the script
+    fn finish(self) -> Result<Vec<Vec<u8>>, Error> {
+        if !self.cond_stack.is_empty() {
+            return Err(Error::UnbalancedConditional);
+        }
+        Ok(self.stack)
+    }
+
+    /// Executes the next opcode of the script and returns it
+    fn step(&mut self) -> Result<Instruction<'a>, Error> {
+        let script: &'a Script = self.script;
+        let opcode = opcodes::All::from(script[self.pos]);
+        let mut instructions = InstructionIndices::from_slice(&script[..], self.pos, false);
+        let instruction = match instructions.next() {
+            Some(Ok((_, instruction))) => instruction,
+            _ => return Err(Error::BadOpcode),
+        };
+        self.pos = instructions.pos();
+        let push = match instruction {
+            Instruction::PushBytes(data) => Some(data),
+            Instruction::Op(_) => None,
+        };
+        let executing = self.cond_stack.iter().all(|branch| *branch);
+
+        if let Some(data) = push {
+            if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(Error::PushSize);
+            }
+        }
+
+        if is_legacy_or_v0(self.sigversion) && opcode.into_u8() > OP_PUSHNUM_16.into_u8() {
+            self.op_count += 1;
+            if self.op_count > MAX_OPS_PER_SCRIPT {
+                return Err(Error::OpCount);
+            }
+        }
+
+        // Disabled opcodes fail the script even in unexecuted branches
+        match opcode {
+            OP_CAT | OP_SUBSTR | OP_LEFT | OP_RIGHT | OP_INVERT | OP_AND | OP_OR | OP_XOR |
+            OP_2MUL | OP_2DIV | OP_MUL | OP_DIV | OP_MOD | OP_LSHIFT | OP_RSHIFT => {
+                return Err(Error::DisabledOpcode);
+            }
+            _ => {}
+        }
+
+        if opcode == OP_CODESEPARATOR && self.sigversion == SigVersion::Base
+            && self.flags.has(VerifyFlags::CONST_SCRIPTCODE) {
+            return Err(Error::OpCodeSeparator);
+        }
+
+        if let Some(data) = push {
+            if executing {
+                if self.flags.has(VerifyFlags::MINIMALDATA) && !is_minimal_push(data, opcode) {
+                    return Err(Error::NonMinimalPush);
+                }
+                self.stack.push(data.to_vec());
+            }
+        } else if executing || (OP_IF.into_u8() <= opcode.into_u8() && opcode.into_u8() <= OP_ENDIF.into_u8()) {
+            self.execute_opcode(opcode, executing)?;
+        }
+
+        self.opcode_pos += 1;
+
+        if self.stack.len() + self.altstack.len() > MAX_STACK_SIZE {
+            return Err(Error::StackSize);
+        }
+        Ok(instruction)
+    }
+
+    /// Executes a non-push opcode, which is either in an executed branch or a conditional
+    fn execute_opcode(&mut self, opcode: opcodes::All, executing: bool) -> Result<(), Error> {
+        if let opcodes::Class::PushNum(n) = opcode.classify() {
+            self.push_num(n as i64);
+            return Ok(());
+        }
+
+        match opcode {
+            OP_NOP => {}
+            OP_CLTV => {
+                if !self.flags.has(VerifyFlags::CHECKLOCKTIMEVERIFY) {
+                    // Treated as OP_NOP2 before BIP65
+                    return Ok(());
+                }
+                self.require(1)?;
+                // The lock time is read as a 5-byte number since 4-byte
+                // numbers would overflow in 2038
+                let lock_time = self.num(1, ScriptNum::MAX_LOCKTIME_SIZE)?;
+                if lock_time < 0 {
+                    return Err(Error::NegativeLockTime);
+                }
+                if !self.checker.check_lock_time(lock_time) {
+                    return Err(Error::UnsatisfiedLockTime);
+                }
+            }
+            OP_CSV => {
+                if !self.flags.has(VerifyFlags::CHECKSEQUENCEVERIFY) {
+                    // Treated as OP_NOP3 before BIP112
+                    return Ok(());
+                }
+                self.require(1)?;
+                let sequence = self.num(1, ScriptNum::MAX_LOCKTIME_SIZE)?;
+                if sequence < 0 {
+                    return Err(Error::NegativeLockTime);
+                }
+                // Sequences with the disable flag behave as OP_NOP3, for future soft forks
+                if Sequence(sequence as u32).is_relative_lock_time() && !self.checker.check_sequence(sequence) {
+                    return Err(Error::UnsatisfiedLockTime);
+                }
+            }
+            OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9 | OP_NOP10 => {
+                if self.flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                    return Err(Error::DiscourageUpgradableNops);
+                }
+            }
+            OP_IF | OP_NOTIF => {
+                let mut value = false;
+                if executing {
+                    let top = match self.stack.pop() {
+                        Some(top) => top,
+                        None => return Err(Error::UnbalancedConditional),
+                    };
+                    let minimal = top.is_empty() || top[..] == [1];
+                    if self.sigversion == SigVersion::Tapscript && !minimal {
+                        return Err(Error::TapscriptMinimalIf);
+                    }
+                    if self.sigversion == SigVersion::WitnessV0 && self.flags.has(VerifyFlags::MINIMALIF) && !minimal {
+                        return Err(Error::MinimalIf);
+                    }
+                    value = read_scriptbool(&top) != (opcode == OP_NOTIF);
+                }
+                self.cond_stack.push(value);
+            }
+            OP_ELSE => {
+                match self.cond_stack.last_mut() {
+                    Some(branch) => *branch = !*branch,
+                    None => return Err(Error::UnbalancedConditional),
+                }
+            }
+            OP_ENDIF => {
+                if self.cond_stack.pop().is_none() {
+                    return Err(Error::UnbalancedConditional);
+                }
+            }
+            OP_VERIFY => {
+                self.require(1)?;
+                if !self.pop_bool() {
+                    return Err(Error::Verify);
+                }
+            }
+            OP_RETURN => return Err(Error::OpReturn),
+
+            // Stack operations
+            OP_TOALTSTACK => {
+                self.require(1)?;
+                let top = self.stack.pop().unwrap();
+                self.altstack.push(top);
+            }
+            OP_FROMALTSTACK => {
+                let top = self.altstack.pop().ok_or(Error::InvalidAltstackOperation)?;
+                self.stack.push(top);
+            }
+            OP_2DROP => {
+                self.require(2)?;
+                self.stack.pop();
+                self.stack.pop();
+            }
+            OP_2DUP => {
+                self.require(2)?;
+                self.push_copies(2, 0);
+            }
+            OP_3DUP => {
+                self.require(3)?;
+                self.push_copies(3, 0);
+            }
+            OP_2OVER => {
+                self.require(4)?;
+                self.push_copies(4, 2);
+            }
+            OP_2ROT => {
+                self.require(6)?;
+                let len = self.stack.len();
+                let moved: Vec<_> = self.stack.drain(len - 6..len - 4).collect();
+                self.stack.extend(moved);
+            }
+            OP_2SWAP => {
+                self.require(4)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 4, len - 2);
+                self.stack.swap(len - 3, len - 1);
+            }
+            OP_IFDUP => {
+                self.require(1)?;
+                let top = self.top(1).clone();
+                if read_scriptbool(&top) {
+                    self.stack.push(top);
+                }
+            }
+            OP_DEPTH => {
+                let depth = self.stack.len() as i64;
+                self.push_num(depth);
+            }
+            OP_DROP => {
+                self.require(1)?;
+                self.stack.pop();
+            }
+            OP_DUP => {
+                self.require(1)?;
+                let top = self.top(1).clone();
+                self.stack.push(top);
+            }
+            OP_NIP => {
+                self.require(2)?;
+                let len = self.stack.len();
+                self.stack.remove(len - 2);
+            }
+            OP_OVER => {
+                self.require(2)?;
+                let second = self.top(2).clone();
+                self.stack.push(second);
+            }
+            OP_PICK | OP_ROLL => {
+                self.require(2)?;
+                let n = self.num(1, ScriptNum::MAX_SIZE)?;
+                self.stack.pop();
+                if n < 0 || n >= self.stack.len() as i64 {
+                    return Err(Error::InvalidStackOperation);
+                }
+                let index = self.stack.len() - 1 - n as usize;
+                let item = if opcode == OP_ROLL {
+                    self.stack.remove(index)
+                } else {
+                    self.stack[index].clone()
+                };
+                self.stack.push(item);
+            }
+            OP_ROT => {
+                self.require(3)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 3, len - 2);
+                self.stack.swap(len - 2, len - 1);
+            }
+            OP_SWAP => {
+                self.require(2)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 2, len - 1);
+            }
+            OP_TUCK => {
+                self.require(2)?;
+                let len = self.stack.len();
+                let top = self.top(1).clone();
+                self.stack.insert(len - 2, top);
+            }
+            OP_SIZE => {
+                self.require(1)?;
+                let size = self.top(1).len() as i64;
+                self.push_num(size);
+            }
+
+            // Bitwise logic
+            OP_EQUAL | OP_EQUALVERIFY => {
+                self.require(2)?;
+                let a = self.stack.pop().unwrap();
+                let b = self.stack.pop().unwrap();
+                let equal = a == b;
+                if opcode == OP_EQUALVERIFY {
+                    if !equal {
+                        return Err(Error::EqualVerify);
+                    }
+                } else {
+                    self.push_bool(equal);
+                }
+            }
+
+            // Numeric
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                self.require(1)?;
+                let n = self.num(1, ScriptNum::MAX_SIZE)?;
+                let res = match opcode {
+                    OP_1ADD => n + 1,
+                    OP_1SUB => n - 1,
+                    OP_NEGATE => -n,
+                    OP_ABS => n.abs(),
+                    OP_NOT => (n == 0) as i64,
+                    _ => (n != 0) as i64,
+                };
+                self.stack.pop();
+                self.push_num(res);
+            }
+            OP_ADD | OP_SUB | OP_BOOLAND | OP_BOOLOR | OP_NUMEQUAL | OP_NUMEQUALVERIFY |
+            OP_NUMNOTEQUAL | OP_LESSTHAN | OP_GREATERTHAN | OP_LESSTHANOREQUAL |
+            OP_GREATERTHANOREQUAL | OP_MIN | OP_MAX => {
+                self.require(2)?;
+                let a = self.num(2, ScriptNum::MAX_SIZE)?;
+                let b = self.num(1, ScriptNum::MAX_SIZE)?;
+                let res = match opcode {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_BOOLAND => (a != 0 && b != 0) as i64,
+                    OP_BOOLOR => (a != 0 || b != 0) as i64,
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => (a == b) as i64,
+                    OP_NUMNOTEQUAL => (a != b) as i64,
+                    OP_LESSTHAN => (a < b) as i64,
+                    OP_GREATERTHAN => (a > b) as i64,
+                    OP_LESSTHANOREQUAL => (a <= b) as i64,
+                    OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                    OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                self.stack.pop();
+                self.stack.pop();
+                if opcode == OP_NUMEQUALVERIFY {
+                    if res == 0 {
+                        return Err(Error::NumEqualVerify);
+                    }
+                } else {
+                    self.push_num(res);
+                }
+            }
+            OP_WITHIN => {
+                self.require(3)?;
+                let x = self.num(3, ScriptNum::MAX_SIZE)?;
+                let min = self.num(2, ScriptNum::MAX_SIZE)?;
+                let max = self.num(1, ScriptNum::MAX_SIZE)?;
+                self.stack.pop();
+                self.stack.pop();
+                self.stack.pop();
+                self.push_bool(min <= x && x < max);
+            }
+
+            // Crypto
+            OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                self.require(1)?;
+                let data = self.stack.pop().unwrap();
+                let hash = match opcode {
+                    OP_RIPEMD160 => ripemd160::Hash::hash(&data).to_vec(),
+                    OP_SHA1 => sha1::Hash::hash(&data).to_vec(),
+                    OP_SHA256 => sha256::Hash::hash(&data).to_vec(),
+                    OP_HASH160 => hash160::Hash::hash(&data).to_vec(),
+                    _ => sha256d::Hash::hash(&data).to_vec(),
+                };
+                self.stack.push(hash);
+            }
+            OP_CODESEPARATOR => {
+                self.codesep_begin = self.pos;
+                self.codesep_pos = self.opcode_pos;
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                self.require(2)?;
+                let pubkey = self.stack.pop().unwrap();
+                let sig = self.stack.pop().unwrap();
+                let success = self.eval_checksig(&sig, &pubkey)?;
+                if opcode == OP_CHECKSIGVERIFY {
+                    if !success {
+                        return Err(Error::CheckSigVerify);
+                    }
+                } else {
+                    self.push_bool(success);
+                }
+            }
+            OP_CHECKSIGADD => {
+                if is_legacy_or_v0(self.sigversion) {
+                    return Err(Error::BadOpcode);
+                }
+                self.require(3)?;
+                let n = self.num(2, ScriptNum::MAX_SIZE)?;
+                let pubkey = self.stack.pop().unwrap();
+                self.stack.pop();
+                let sig = self.stack.pop().unwrap();
+                let success = self.eval_checksig(&sig, &pubkey)?;
+                self.push_num(n + success as i64);
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                if self.sigversion == SigVersion::Tapscript {
+                    return Err(Error::TapscriptCheckMultiSig);
+                }
+                let success = self.eval_checkmultisig()?;
+                if opcode == OP_CHECKMULTISIGVERIFY {
+                    if !success {
+                        return Err(Error::CheckMultiSigVerify);
+                    }
+                } else {
+                    self.push_bool(success);
+                }
+            }
+            _ => return Err(Error::BadOpcode),
+        }
+        Ok(())
+    }
+
+    /// Checks a signature against a public key, returning whether the check
+    /// succeeded or an error if the script must fail
+    fn eval_checksig(&mut self, sig: &[u8], pubkey: &[u8]) -> Result<bool, Error> {
+        match self.sigversion {
+            SigVersion::Base | SigVersion::WitnessV0 => {
+                let mut script_code = self.script[self.codesep_begin..].to_vec();
+                if self.sigversion == SigVersion::Base {
+                    let found = script_find_and_remove(&mut script_code, &Builder::new().push_slice(sig).into_script()[..]);
+                    if found > 0 && self.flags.has(VerifyFlags::CONST_SCRIPTCODE) {
+                        return Err(Error::SigFindAndDelete);
+                    }
+                }
+                check_signature_encoding(sig, self.flags)?;
+                check_pubkey_encoding(pubkey, self.flags, self.sigversion)?;
+                let success = self.checker.check_ecdsa_sig(&mut self.cache, sig, pubkey, &script_code, self.sigversion)?;
+                if !success && self.flags.has(VerifyFlags::NULLFAIL) && !sig.is_empty() {
+                    return Err(Error::SigNullFail);
+                }
+                Ok(success)
+            }
+            SigVersion::Tapscript => {
+                let success = !sig.is_empty();
+                if success {
+                    self.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+                    if self.validation_weight_left < 0 {
+                        return Err(Error::TapscriptValidationWeight);
+                    }
+                }
+                if pubkey.is_empty() {
+                    return Err(Error::PubkeyType);
+                } else if pubkey.len() == 32 {
+                    if success {
+                        let leaf_hash = self.leaf_hash.expect("tapscripts have a leaf hash");
+                        self.checker.check_schnorr_sig(
+                            &mut self.cache,
+                            sig,
+                            pubkey,
+                            self.annex,
+                            Some((leaf_hash, self.codesep_pos)),
+                        )?;
+                    }
+                } else if self.flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE) {
+                    return Err(Error::DiscourageUpgradablePubkeyType);
+                }
+                Ok(success)
+            }
+            SigVersion::Taproot => unreachable!("key path spends don't execute scripts"),
+        }
+    }
+
+    /// Executes an OP_CHECKMULTISIG, removing its arguments from the stack
+    fn eval_checkmultisig(&mut self) -> Result<bool, Error> {
+        let mut i = 1;
+        self.require(i)?;
+
+        let mut keys_count = self.num(i, ScriptNum::MAX_SIZE)?;
+        if keys_count < 0 || keys_count > MAX_PUBKEYS_PER_MULTISIG {
+            return Err(Error::PubkeyCount);
+        }
+        self.op_count += keys_count as usize;
+        if self.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(Error::OpCount);
+        }
+        i += 1;
+        let mut ikey = i;
+        // The index of the last key to clear on failure under NULLFAIL
+        let mut ikey2 = keys_count as usize + 2;
+        i += keys_count as usize;
+        self.require(i)?;
+
+        let mut sigs_count = self.num(i, ScriptNum::MAX_SIZE)?;
+        if sigs_count < 0 || sigs_count > keys_count {
+            return Err(Error::SigCount);
+        }
+        i += 1;
+        let mut isig = i;
+        i += sigs_count as usize;
+        self.require(i)?;
+
+        let mut script_code = self.script[self.codesep_begin..].to_vec();
+        if self.sigversion == SigVersion::Base {
+            for k in 0..sigs_count as usize {
+                let sig = Builder::new().push_slice(self.top(isig + k)).into_script();
+                let found = script_find_and_remove(&mut script_code, &sig[..]);
+                if found > 0 && self.flags.has(VerifyFlags::CONST_SCRIPTCODE) {
+                    return Err(Error::SigFindAndDelete);
+                }
+            }
+        }
+
+        let mut success = true;
+        while success && sigs_count > 0 {
+            let sig = self.top(isig).clone();
+            let pubkey = self.top(ikey).clone();
+            check_signature_encoding(&sig, self.flags)?;
+            check_pubkey_encoding(&pubkey, self.flags, self.sigversion)?;
+            if self.checker.check_ecdsa_sig(&mut self.cache, &sig, &pubkey, &script_code, self.sigversion)? {
+                isig += 1;
+                sigs_count -= 1;
+            }
+            ikey += 1;
+            keys_count -= 1;
+            // Fail early if there are more signatures left than keys
+            if sigs_count > keys_count {
+                success = false;
+            }
+        }
+
+        // Clean up the stack of the arguments, and the extra dummy element
+        while i > 1 {
+            i -= 1;
+            if !success && self.flags.has(VerifyFlags::NULLFAIL) && ikey2 == 0 && !self.top(1).is_empty() {
+                return Err(Error::SigNullFail);
+            }
+            ikey2 = ikey2.saturating_sub(1);
+            self.stack.pop();
+        }
+        self.require(1)?;
+        if self.flags.has(VerifyFlags::NULLDUMMY) && !self.top(1).is_empty() {
+            return Err(Error::SigNullDummy);
+        }
+        self.stack.pop();
+        Ok(success)
+    }
+
+    /// Fails if the stack holds less than `n` elements
+    fn require(&self, n: usize) -> Result<(), Error> {
+        if self.stack.len() < n {
+            Err(Error::InvalidStackOperation)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The `n`th element from the top of the stack, starting at 1
+    fn top(&self, n: usize) -> &Vec<u8> {
+        &self.stack[self.stack.len() - n]
+    }
+
+    /// Reads the `n`th element from the top of the stack as a number
+    fn num(&self, n: usize, max_size: usize) -> Result<i64, Error> {
+        ScriptNum::from_slice(self.top(n), self.flags.has(VerifyFlags::MINIMALDATA), max_size).map(ScriptNum::to_i64)
+    }
+
+    /// Pops the top of the stack as a boolean
+    fn pop_bool(&mut self) -> bool {
+        let top = self.stack.pop().unwrap();
+        read_scriptbool(&top)
+    }
+
+    /// Pushes copies of the stack elements from the `from`th to the one
+    /// above the `to`th from the top of the stack
+    fn push_copies(&mut self, from: usize, to: usize) {
+        let len = self.stack.len();
+        for i in len - from..len - to {
+            let item = self.stack[i].clone();
+            self.stack.push(item);
+        }
+    }
+
+    fn push_num(&mut self, n: i64) {
+        self.stack.push(ScriptNum::from(n).to_bytes());
+    }
+
+    fn push_bool(&mut self, b: bool) {
+        self.stack.push(if b { vec![1] } else { vec![] });
+    }
+}
+
+fn is_legacy_or_v0(sigversion: SigVersion) -> bool {
+    sigversion == SigVersion::Base || sigversion == SigVersion::WitnessV0
+}
+
+/// Whether the data was pushed with the smallest possible push opcode
+fn is_minimal_push(data: &[u8], opcode: opcodes::All) -> bool {
+    let op = opcode.into_u8();
+    if data.is_empty() {
+        op == OP_PUSHBYTES_0.into_u8()
+    } else if data.len() == 1 && data[0] >= 1 && data[0] <= 16 {
+        // Could have used OP_1 .. OP_16
+        false
+    } else if data.len() == 1 && data[0] == 0x81 {
+        // Could have used OP_1NEGATE
+        false
+    } else if data.len() <= 75 {
+        op as usize == data.len()
+    } else if data.len() <= 0xff {
+        opcode == OP_PUSHDATA1
+    } else if data.len() <= 0xffff {
+        opcode == OP_PUSHDATA2
+    } else {
+        true
+    }
+}
+
+/// Whether the script only contains push opcodes, counting OP_RESERVED as one
+fn is_push_only(script: &Script) -> bool {
+    script.instructions().all(|instruction| match instruction {
+        Ok(Instruction::PushBytes(_)) => true,
+        Ok(Instruction::Op(op)) => op.into_u8() <= OP_PUSHNUM_16.into_u8(),
+        Err(_) => false,
+    })
+}
+
+/// Removes the OP_CODESEPARATORs of a legacy script code before it is signed
+fn remove_codeseparators(script: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(script.len());
+    let mut copied = 0;
+    let mut instructions = InstructionIndices::from_slice(script, 0, false);
+    while let Some(Ok((pos, instruction))) = instructions.next() {
+        if instruction == Instruction::Op(OP_CODESEPARATOR) {
+            result.extend_from_slice(&script[copied..pos]);
+            copied = instructions.pos();
+        }
+    }
+    result.extend_from_slice(&script[copied..]);
+    result
+}
+
+/// Whether the signature is strictly DER encoded with a trailing sighash
+/// type byte, as required by BIP66
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    // Format: 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash]
+    if sig.len() < 9 || sig.len() > 73 {
+        return false;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != sig.len() {
+        return false;
+    }
+    // R must be a positive integer without excess padding
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    // Same for S
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+/// Whether the S value of a DER signature is at most half the curve order
+fn is_low_der_signature(sig: &[u8]) -> bool {
+    let mut parsed = match secp256k1::Signature::from_der_lax(&sig[..sig.len() - 1]) {
+        Ok(parsed) => parsed,
+        Err(_) => return false,
+    };
+    let compact = parsed.serialize_compact();
+    parsed.normalize_s();
+    parsed.serialize_compact()[..] == compact[..]
+}
+
+/// Checks the encoding of an ECDSA signature against the verification flags
+fn check_signature_encoding(sig: &[u8], flags: VerifyFlags) -> Result<(), Error> {
+    // Empty signatures are allowed, to fail signature checks without failing the script
+    if sig.is_empty() {
+        return Ok(());
+    }
+    let strict = VerifyFlags::DERSIG | VerifyFlags::LOW_S | VerifyFlags::STRICTENC;
+    if flags.as_u32() & strict.as_u32() != 0 && !is_valid_signature_encoding(sig) {
+        return Err(Error::SigDer);
+    }
+    if flags.has(VerifyFlags::LOW_S) && !is_low_der_signature(sig) {
+        return Err(Error::SigHighS);
+    }
+    if flags.has(VerifyFlags::STRICTENC) {
+        let hash_type = sig[sig.len() - 1] & !0x80;
+        if hash_type < 0x01 || hash_type > 0x03 {
+            return Err(Error::SigHashType);
+        }
+    }
+    Ok(())
+}
+
+/// Checks the encoding of an ECDSA public key against the verification flags
+fn check_pubkey_encoding(pubkey: &[u8], flags: VerifyFlags, sigversion: SigVersion) -> Result<(), Error> {
+    let compressed = pubkey.len() == 33 && (pubkey[0] == 0x02 || pubkey[0] == 0x03);
+    let uncompressed = pubkey.len() == 65 && pubkey[0] == 0x04;
+    if flags.has(VerifyFlags::STRICTENC) && !compressed && !uncompressed {
+        return Err(Error::PubkeyType);
+    }
+    if flags.has(VerifyFlags::WITNESS_PUBKEYTYPE) && sigversion == SigVersion::WitnessV0 && !compressed {
+        return Err(Error::WitnessPubkeyType);
+    }
+    Ok(())
+}
+
+/// The witness version and program of a witness program script
+fn witness_program(script: &Script) -> Option<(u8, &[u8])> {
+    if !script.is_witness_program() {
+        return None;
+    }
+    let version = match script[0] {
+        0 => 0,
+        op => op - (OP_PUSHNUM_1.into_u8() - 1),
+    };
+    Some((version, &script[2..]))
+}
+
+/// Computes the leaf hash of a tapscript with any leaf version, even the
+/// ones [LeafVersion](::util::taproot::LeafVersion) rejects
+fn tap_leaf_hash(leaf_version: u8, script: &Script) -> TapLeafHash {
+    let mut eng = TapLeafHash::engine();
+    eng.input(&[leaf_version]);
+    script.consensus_encode(&mut eng).expect("engines don't error");
+    TapLeafHash::from_engine(eng)
+}
+
+/// Checks that the taproot output key commits to the leaf using the merkle
+/// path and internal key of the control block
+fn verify_taproot_commitment<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    control: &[u8],
+    program: &[u8],
+    leaf_hash: TapLeafHash,
+) -> bool {
+    let internal_key = match schnorrsig::PublicKey::from_slice(&control[1..TAPROOT_CONTROL_BASE_SIZE]) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let output_key = match schnorrsig::PublicKey::from_slice(program) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let mut hash = sha256::Hash::from_inner(leaf_hash.into_inner());
+    for node in control[TAPROOT_CONTROL_BASE_SIZE..].chunks(TAPROOT_CONTROL_NODE_SIZE) {
+        let node = sha256::Hash::from_slice(node).expect("32-byte node");
+        hash = sha256::Hash::from_inner(TapBranchHash::from_node_hashes(hash, node).into_inner());
+    }
+    let merkle_root = TapBranchHash::from_inner(hash.into_inner());
+    let tweak = TapTweakHash::from_key_and_tweak(&internal_key, Some(merkle_root));
+    internal_key.tweak_add_check(secp, &output_key, control[0] & 1 == 1, tweak.into_inner())
+}
+
+/// The state of a script execution after one of its steps, see [Trace]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step<'a> {
+    /// Byte position of the executed instruction in the script
+    pub position: usize,
+    /// The executed instruction, which may be in a skipped branch
+    pub instruction: Instruction<'a>,
+    /// The stack, with its top element last
+    pub stack: Vec<Vec<u8>>,
+    /// The alt stack, with its top element last
+    pub altstack: Vec<Vec<u8>>,
+    /// Whether each of the enclosing OP_IF branches is executed, the
+    /// innermost one last
+    pub cond_stack: Vec<bool>,
+}
+
+/// A step-by-step execution of a script against a transaction input, to
+/// debug spends.
+///
+/// The trace is an iterator yielding the state of the execution after each
+/// instruction of the script. It stops after yielding the error of a failed
+/// instruction, or the [Error::UnbalancedConditional] of a script ending in
+/// an unclosed OP_IF.
+///
+/// Only the script itself is executed: the rules applied around it by
+/// [verify_input], like the P2SH and witness program rules, the clean stack
+/// rule or the OP_SUCCESSx processing of tapscripts, are up to the caller.
+pub struct Trace<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> {
+    exec: Exec<'a, C, T>,
+    done: bool,
+}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Trace<'a, C, T> {
+    /// Starts the execution of `script` with an empty stack, as the script of
+    /// input `index` of `tx` of the given signature version. See
+    /// [verify_input] for `prevouts`.
+    ///
+    /// Tapscripts are executed as leaves of the tapscript leaf version, with
+    /// the annex and signature validation budget of the input witness.
+    ///
+    /// Fails with [Error::TaprootKeyPath] if `sigversion` is
+    /// [SigVersion::Taproot], as taproot key path spends execute no script.
+    pub fn new(
+        secp: &'a Secp256k1<C>,
+        script: &'a Script,
+        tx: &'a Transaction,
+        index: usize,
+        prevouts: &'a Prevouts<'a, T>,
+        sigversion: SigVersion,
+        flags: VerifyFlags,
+    ) -> Result<Trace<'a, C, T>, Error> {
+        if sigversion == SigVersion::Taproot {
+            return Err(Error::TaprootKeyPath);
+        }
+        let input = match tx.input.get(index) {
+            Some(input) => input,
+            None => return Err(Error::SigHash(sighash::Error::IndexOutOfInputsBounds {
+                index: index,
+                inputs_size: tx.input.len(),
+            })),
+        };
+        let prevout = prevouts.get(index).map_err(|_| Error::UnknownSpentOutput(input.previous_output))?;
+        let checker = Checker {
+            secp: secp,
+            tx: tx,
+            index: index,
+            amount: prevout.value,
+            prevouts: prevouts,
+        };
+        let mut exec = Exec::new(checker, script, vec![], flags, sigversion)?;
+        if sigversion == SigVersion::Tapscript {
+            exec.leaf_hash = Some(tap_leaf_hash(TAPROOT_LEAF_TAPSCRIPT, script));
+            exec.annex = input.witness.taproot_annex();
+            exec.validation_weight_left = input.witness.serialized_len() as i64 + VALIDATION_WEIGHT_OFFSET;
+        }
+        Ok(Trace {
+            exec: exec,
+            done: false,
+        })
+    }
+
+    /// Sets the stack the script starts executing with, e.g. the stack left
+    /// by the scriptSig for a scriptPubKey
+    pub fn with_stack(mut self, stack: Vec<Vec<u8>>) -> Trace<'a, C, T> {
+        self.exec.stack = stack;
+        self
+    }
+
+    /// Byte position of the next instruction, or of the failed one after an
+    /// error
+    pub fn position(&self) -> usize {
+        self.exec.pos
+    }
+
+    /// The current stack, with its top element last
+    pub fn stack(&self) -> &[Vec<u8>] {
+        &self.exec.stack
+    }
+}
+
+impl<'a, C: Signing + Verification + 'a, T: Borrow<TxOut> + 'a> Iterator for Trace<'a, C, T> {
+    type Item = Result<Step<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.exec.is_done() {
+            self.done = true;
+            if !self.exec.cond_stack.is_empty() {
+                return Some(Err(Error::UnbalancedConditional));
+            }
+            return None;
+        }
+        let position = self.exec.pos;
+        match self.exec.step() {
+            Ok(instruction) => Some(Ok(Step {
+                position: position,
+                instruction: instruction,
+                stack: self.exec.stack.clone(),
+                altstack: self.exec.altstack.clone(),
+                cond_stack: self.exec.cond_stack.clone(),
+            })),
+            Err(e) => {
+                self.done = true;
+                self.exec.pos = position;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn eval_script<'a, C: Signing + Verification, T: Borrow<TxOut>>(
+    checker: Checker<'a, C, T>,
+    script: &'a Script,
+    stack: Vec<Vec<u8>>,
+    flags: VerifyFlags,
+    sigversion: SigVersion,
+) -> Result<Vec<Vec<u8>>, Error> {
+    Exec::new(checker, script, stack, flags, sigversion)?.run()
+}
+
+/// Executes a witness script, either a segwit v0 one or a tapscript with its
+/// leaf hash, annex and signature validation budget
+fn execute_witness_script<'a, C: Signing + Verification, T: Borrow<TxOut>>(
+    checker: Checker<'a, C, T>,
+    stack: Vec<Vec<u8>>,
+    script: &'a Script,
+    flags: VerifyFlags,
+    sigversion: SigVersion,
+    tapscript: Option<(TapLeafHash, Option<&'a [u8]>, i64)>,
+) -> Result<(), Error> {
+    if sigversion == SigVersion::Tapscript {
+        // OP_SUCCESSx processing overrides everything, including stack element size limits
+        for instruction in script.instructions() {
+            match instruction {
+                Err(_) => return Err(Error::BadOpcode),
+                Ok(Instruction::Op(op)) if op.is_tapscript_success() => {
+                    if flags.has(VerifyFlags::DISCOURAGE_OP_SUCCESS) {
+                        return Err(Error::DiscourageOpSuccess);
+                    }
+                    return Ok(());
+                }
+                Ok(_) => {}
+            }
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(Error::StackSize);
+        }
+    }
+    if stack.iter().any(|elem| elem.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(Error::PushSize);
+    }
+
+    let mut exec = Exec::new(checker, script, stack, flags, sigversion)?;
+    if let Some((leaf_hash, annex, validation_weight)) = tapscript {
+        exec.leaf_hash = Some(leaf_hash);
+        exec.annex = annex;
+        exec.validation_weight_left = validation_weight;
+    }
+    let stack = exec.run()?;
+
+    // Witness scripts implicitly require a clean stack
+    if stack.len() != 1 {
+        return Err(Error::CleanStack);
+    }
+    if !read_scriptbool(&stack[0]) {
+        return Err(Error::EvalFalse);
+    }
+    Ok(())
+}
+
+fn verify_witness_program<'a, C: Signing + Verification, T: Borrow<TxOut>>(
+    checker: Checker<'a, C, T>,
+    witness: &'a Witness,
+    version: u8,
+    program: &[u8],
+    flags: VerifyFlags,
+    is_p2sh: bool,
+) -> Result<(), Error> {
+    if version == 0 {
+        if program.len() == 32 {
+            // P2WSH
+            let script = Script::from(witness.last().ok_or(Error::WitnessProgramWitnessEmpty)?.to_vec());
+            if sha256::Hash::hash(&script[..])[..] != program[..] {
+                return Err(Error::WitnessProgramMismatch);
+            }
+            let stack = witness_stack(witness, witness.len() - 1);
+            execute_witness_script(checker, stack, &script, flags, SigVersion::WitnessV0, None)
+        } else if program.len() == 20 {
+            // P2WPKH
+            if witness.len() != 2 {
+                return Err(Error::WitnessProgramMismatch);
+            }
+            let script = Builder::new()
+                .push_opcode(OP_DUP)
+                .push_opcode(OP_HASH160)
+                .push_slice(program)
+                .push_opcode(OP_EQUALVERIFY)
+                .push_opcode(OP_CHECKSIG)
+                .into_script();
+            execute_witness_script(checker, witness.to_vec(), &script, flags, SigVersion::WitnessV0, None)
+        } else {
+            Err(Error::WitnessProgramWrongLength)
+        }
+    } else if version == 1 && program.len() == 32 && !is_p2sh {
+        // Taproot
+        if !flags.has(VerifyFlags::TAPROOT) {
+            return Ok(());
+        }
+        if witness.is_empty() {
+            return Err(Error::WitnessProgramWitnessEmpty);
+        }
+        let annex = witness.taproot_annex();
+        let (control, script) = match (witness.taproot_control_block(), witness.tapscript()) {
+            (Some(control), Some(script)) => (control, script),
+            _ => {
+                // Key path spend
+                let mut cache = SigHashCache::new(checker.tx);
+                return checker.check_schnorr_sig(&mut cache, &witness[0], program, annex, None);
+            }
+        };
+
+        // Script path spend
+        if control.len() < TAPROOT_CONTROL_BASE_SIZE || control.len() > TAPROOT_CONTROL_MAX_SIZE
+            || (control.len() - TAPROOT_CONTROL_BASE_SIZE) % TAPROOT_CONTROL_NODE_SIZE != 0 {
+            return Err(Error::TaprootWrongControlSize);
+        }
+        let leaf_version = control[0] & TAPROOT_LEAF_MASK;
+        let script = Script::from(script.to_vec());
+        let leaf_hash = tap_leaf_hash(leaf_version, &script);
+        if !verify_taproot_commitment(checker.secp, control, program, leaf_hash) {
+            return Err(Error::WitnessProgramMismatch);
+        }
+        if leaf_version == TAPROOT_LEAF_TAPSCRIPT {
+            let validation_weight = witness.serialized_len() as i64 + VALIDATION_WEIGHT_OFFSET;
+            let stack_len = witness.len() - if annex.is_some() { 3 } else { 2 };
+            return execute_witness_script(
+                checker,
+                witness_stack(witness, stack_len),
+                &script,
+                flags,
+                SigVersion::Tapscript,
+                Some((leaf_hash, annex, validation_weight)),
+            );
+        }
+        if flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION) {
+            return Err(Error::DiscourageUpgradableTaprootVersion);
+        }
+        Ok(())
+    } else {
+        // Unknown witness versions, and P2SH-wrapped taproot, are anyone-can-spend
+        if flags.has(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM) {
+            return Err(Error::DiscourageUpgradableWitnessProgram);
+        }
+        Ok(())
+    }
+}
+
+/// The first `len` elements of the witness, as the initial stack of a
+/// witness script
+fn witness_stack(witness: &Witness, len: usize) -> Vec<Vec<u8>> {
+    witness.iter().take(len).map(|elem| elem.to_vec()).collect()
+}
+
+fn verify_script<'a, C: Signing + Verification, T: Borrow<TxOut>>(
+    checker: Checker<'a, C, T>,
+    script_sig: &'a Script,
+    script_pubkey: &'a Script,
+    witness: &'a Witness,
+    flags: VerifyFlags,
+) -> Result<(), Error> {
+    if flags.has(VerifyFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+        return Err(Error::SigPushOnly);
+    }
+
+    // The scriptSig and scriptPubKey are evaluated sequentially on the same
+    // stack rather than being concatenated
+    let stack = eval_script(checker, script_sig, vec![], flags, SigVersion::Base)?;
+    let stack_copy = if flags.has(VerifyFlags::P2SH) { stack.clone() } else { vec![] };
+    let mut stack = eval_script(checker, script_pubkey, stack, flags, SigVersion::Base)?;
+    if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+        return Err(Error::EvalFalse);
+    }
+
+    // Native witness programs
+    let mut had_witness = false;
+    if flags.has(VerifyFlags::WITNESS) {
+        if let Some((version, program)) = witness_program(script_pubkey) {
+            had_witness = true;
+            if !script_sig.is_empty() {
+                return Err(Error::WitnessMalleated);
+            }
+            verify_witness_program(checker, witness, version, program, flags, false)?;
+            // The actual stack is obviously not clean for witness programs
+            stack.truncate(1);
+        }
+    }
+
+    // P2SH redeem scripts, and P2SH-wrapped witness programs
+    if flags.has(VerifyFlags::P2SH) && script_pubkey.is_p2sh() {
+        if !is_push_only(script_sig) {
+            return Err(Error::SigPushOnly);
+        }
+        stack = stack_copy;
+        // The scriptPubKey succeeded, so it hashed the top element
+        let redeem_script = Script::from(stack.pop().expect("non-empty stack"));
+        stack = eval_script(checker, &redeem_script, stack, flags, SigVersion::Base)?;
+        if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+            return Err(Error::EvalFalse);
+        }
+
+        if flags.has(VerifyFlags::WITNESS) {
+            if let Some((version, program)) = witness_program(&redeem_script) {
+                had_witness = true;
+                let push = Builder::new().push_slice(&redeem_script[..]).into_script();
+                if *script_sig != push {
+                    return Err(Error::WitnessMalleatedP2sh);
+                }
+                verify_witness_program(checker, witness, version, program, flags, true)?;
+                stack.truncate(1);
+            }
+        }
+    }
+
+    // The clean stack check is only performed after P2SH and witness evaluation,
+    // which are expected to leave the stack unclean when evaluated as legacy scripts
+    if flags.has(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+        return Err(Error::CleanStack);
+    }
+
+    if flags.has(VerifyFlags::WITNESS) && !had_witness && !witness.is_empty() {
+        return Err(Error::WitnessUnexpected);
+    }
+    Ok(())
+}
+
+/// Verifies that the input at `index` of `tx` correctly spends its output
+/// under the given verification flags.
+///
+/// `prevouts` must hold the spent output of the input, or all the spent
+/// outputs of the transaction to verify taproot signatures which commit to
+/// them. The CLEANSTACK and WITNESS flags should only be used together with
+/// the P2SH flag, as Bitcoin Core requires.
+pub fn verify_input<C: Signing + Verification, T: Borrow<TxOut>>(
+    secp: &Secp256k1<C>,
+    tx: &Transaction,
+    index: usize,
+    prevouts: &Prevouts<T>,
+    flags: VerifyFlags,
+) -> Result<(), Error> {
+    let input = match tx.input.get(index) {
+        Some(input) => input,
+        None => return Err(Error::SigHash(sighash::Error::IndexOutOfInputsBounds {
+            index: index,
+            inputs_size: tx.input.len(),
+        })),
+    };
+    let prevout = prevouts.get(index).map_err(|_| Error::UnknownSpentOutput(input.previous_output))?;
+    let checker = Checker {
+        secp: secp,
+        tx: tx,
+        index: index,
+        amount: prevout.value,
+        prevouts: prevouts,
+    };
+    verify_script(checker, &input.script_sig, &prevout.script_pubkey, &input.witness, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    use std::collections::HashMap;
+    use std::slice;
+
+    use hashes::hex::FromHex;
+    use secp256k1::schnorrsig::KeyPair;
+
+    use blockdata::transaction::{OutPoint, TxIn};
+    #[cfg(feature = "serde")]
+    use consensus::encode::deserialize;
+    use util::schnorr::TweakedPublicKey;
+    use util::taproot::{TAPROOT_ANNEX_PREFIX, TAPROOT_LEAF_TAPSCRIPT};
+
+    /// Parses the script notation of Bitcoin Core's test vectors
+    #[cfg(feature = "serde")]
+    fn parse_core_asm(asm: &str) -> Script {
+        let mut names = HashMap::new();
+        for code in (0x61..0xba).chain(Some(0x50)) {
+            let name = format!("{:?}", opcodes::All::from(code as u8));
+            names.insert(name[3..].to_owned(), code as u8);
+            names.insert(name, code as u8);
+        }
+        for &(name, code) in &[
+            ("1NEGATE", 0x4f), ("NOP2", 0xb1), ("CHECKLOCKTIMEVERIFY", 0xb1),
+            ("NOP3", 0xb2), ("CHECKSEQUENCEVERIFY", 0xb2),
+        ] {
+            names.insert(name.to_owned(), code);
+            names.insert(format!("OP_{}", name), code);
+        }
+
+        let mut bytes = vec![];
+        for word in asm.split_whitespace() {
+            let digits = if word.starts_with('-') { &word[1..] } else { word };
+            if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                let n = word.parse::<i64>().expect("number in range");
+                bytes.extend(Builder::new().push_int(n).into_script().into_bytes());
+            } else if word.starts_with("0x") && word.len() > 2 {
+                bytes.extend(Vec::<u8>::from_hex(&word[2..]).expect("valid hex"));
+            } else if word.len() >= 2 && word.starts_with('\'') && word.ends_with('\'') {
+                let data = &word.as_bytes()[1..word.len() - 1];
+                bytes.extend(Builder::new().push_slice(data).into_script().into_bytes());
+            } else {
+                match names.get(word) {
+                    Some(code) => bytes.push(*code),
+                    None => panic!("unknown opcode {} in {}", word, asm),
+                }
+            }
+        }
+        Script::from(bytes)
+    }
+
+    /// Parses the comma separated flag names of Bitcoin Core's test vectors
+    #[cfg(feature = "serde")]
+    fn parse_core_flags(names: &str) -> VerifyFlags {
+        let mut flags = VerifyFlags::NONE;
+        for name in names.split(',').filter(|name| !name.is_empty()) {
+            flags.add(match name {
+                "NONE" => VerifyFlags::NONE,
+                "P2SH" => VerifyFlags::P2SH,
+                "STRICTENC" => VerifyFlags::STRICTENC,
+                "DERSIG" => VerifyFlags::DERSIG,
+                "LOW_S" => VerifyFlags::LOW_S,
+                "NULLDUMMY" => VerifyFlags::NULLDUMMY,
+                "SIGPUSHONLY" => VerifyFlags::SIGPUSHONLY,
+                "MINIMALDATA" => VerifyFlags::MINIMALDATA,
+                "DISCOURAGE_UPGRADABLE_NOPS" => VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS,
+                "CLEANSTACK" => VerifyFlags::CLEANSTACK,
+                "CHECKLOCKTIMEVERIFY" => VerifyFlags::CHECKLOCKTIMEVERIFY,
+                "CHECKSEQUENCEVERIFY" => VerifyFlags::CHECKSEQUENCEVERIFY,
+                "WITNESS" => VerifyFlags::WITNESS,
+                "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM" => VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+                "MINIMALIF" => VerifyFlags::MINIMALIF,
+                "NULLFAIL" => VerifyFlags::NULLFAIL,
+                "WITNESS_PUBKEYTYPE" => VerifyFlags::WITNESS_PUBKEYTYPE,
+                "CONST_SCRIPTCODE" => VerifyFlags::CONST_SCRIPTCODE,
+                _ => panic!("unknown verification flag {}", name),
+            });
+        }
+        flags
+    }
+
+    /// The name Bitcoin Core's test vectors use for a verification result
+    #[cfg(feature = "serde")]
+    fn core_error_name(res: &Result<(), Error>) -> &'static str {
+        match *res {
+            Ok(()) => "OK",
+            Err(Error::EvalFalse) => "EVAL_FALSE",
+            Err(Error::OpReturn) => "OP_RETURN",
+            Err(Error::ScriptSize) => "SCRIPT_SIZE",
+            Err(Error::PushSize) => "PUSH_SIZE",
+            Err(Error::OpCount) => "OP_COUNT",
+            Err(Error::StackSize) => "STACK_SIZE",
+            Err(Error::SigCount) => "SIG_COUNT",
+            Err(Error::PubkeyCount) => "PUBKEY_COUNT",
+            Err(Error::Verify) => "VERIFY",
+            Err(Error::EqualVerify) => "EQUALVERIFY",
+            Err(Error::CheckMultiSigVerify) => "CHECKMULTISIGVERIFY",
+            Err(Error::CheckSigVerify) => "CHECKSIGVERIFY",
+            Err(Error::NumEqualVerify) => "NUMEQUALVERIFY",
+            Err(Error::BadOpcode) | Err(Error::EarlyEndOfScript) => "BAD_OPCODE",
+            Err(Error::DisabledOpcode) => "DISABLED_OPCODE",
+            Err(Error::InvalidStackOperation) => "INVALID_STACK_OPERATION",
+            Err(Error::InvalidAltstackOperation) => "INVALID_ALTSTACK_OPERATION",
+            Err(Error::UnbalancedConditional) => "UNBALANCED_CONDITIONAL",
+            Err(Error::NegativeLockTime) => "NEGATIVE_LOCKTIME",
+            Err(Error::UnsatisfiedLockTime) => "UNSATISFIED_LOCKTIME",
+            Err(Error::SigHashType) => "SIG_HASHTYPE",
+            Err(Error::SigDer) => "SIG_DER",
+            Err(Error::NonMinimalPush) => "MINIMALDATA",
+            Err(Error::SigPushOnly) => "SIG_PUSHONLY",
+            Err(Error::SigHighS) => "SIG_HIGH_S",
+            Err(Error::SigNullDummy) => "SIG_NULLDUMMY",
+            Err(Error::PubkeyType) => "PUBKEYTYPE",
+            Err(Error::CleanStack) => "CLEANSTACK",
+            Err(Error::MinimalIf) => "MINIMALIF",
+            Err(Error::SigNullFail) => "NULLFAIL",
+            Err(Error::DiscourageUpgradableNops) => "DISCOURAGE_UPGRADABLE_NOPS",
+            Err(Error::DiscourageUpgradableWitnessProgram) => "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+            Err(Error::WitnessProgramWrongLength) => "WITNESS_PROGRAM_WRONG_LENGTH",
+            Err(Error::WitnessProgramWitnessEmpty) => "WITNESS_PROGRAM_WITNESS_EMPTY",
+            Err(Error::WitnessProgramMismatch) => "WITNESS_PROGRAM_MISMATCH",
+            Err(Error::WitnessMalleated) => "WITNESS_MALLEATED",
+            Err(Error::WitnessMalleatedP2sh) => "WITNESS_MALLEATED_P2SH",
+            Err(Error::WitnessUnexpected) => "WITNESS_UNEXPECTED",
+            Err(Error::WitnessPubkeyType) => "WITNESS_PUBKEYTYPE",
+            Err(Error::OpCodeSeparator) => "OP_CODESEPARATOR",
+            Err(Error::SigFindAndDelete) => "SIG_FINDANDDELETE",
+            Err(_) => "UNKNOWN_ERROR",
+        }
+    }
+
+    #[test]
+    fn verify_flags_test() {
+        assert_eq!("VerifyFlags(NONE)", VerifyFlags::NONE.to_string());
+        assert_eq!("VerifyFlags(P2SH|WITNESS)", (VerifyFlags::P2SH | VerifyFlags::WITNESS).to_string());
+        assert_eq!("VerifyFlags(P2SH|0x200000)", VerifyFlags::from(0x200001).to_string());
+        assert!(VerifyFlags::STANDARD.has(VerifyFlags::CONSENSUS));
+        assert!(!VerifyFlags::STANDARD.has(VerifyFlags::SIGPUSHONLY));
+
+        let mut flags = VerifyFlags::STANDARD;
+        assert!(!flags.remove(VerifyFlags::TAPROOT).has(VerifyFlags::TAPROOT));
+        assert!(flags.add(VerifyFlags::TAPROOT).has(VerifyFlags::TAPROOT));
+        assert_eq!(flags, VerifyFlags::STANDARD);
+    }
+
+    /// Bitcoin Core's crediting and spending transactions of a script test
+    #[cfg(feature = "serde")]
+    fn core_test_txs(script_sig: Script, script_pubkey: Script, witness: Vec<Vec<u8>>, amount: u64) -> (Transaction, Transaction) {
+        let credit = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(0).push_int(0).into_script(),
+                sequence: 0xffffffff,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut {
+                value: amount,
+                script_pubkey: script_pubkey,
+            }],
+        };
+        let spend = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(credit.txid(), 0),
+                script_sig: script_sig,
+                sequence: 0xffffffff,
+                witness: Witness::from_vec(witness),
+            }],
+            output: vec![TxOut {
+                value: amount,
+                script_pubkey: Script::new(),
+            }],
+        };
+        (credit, spend)
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn script_tests() {
+        use serde_json::{self, Value};
+
+        let secp = Secp256k1::new();
+        let tests: Vec<Value> = serde_json::from_str(include_str!("../../../test_data/script_tests.json")).unwrap();
+        let mut count = 0;
+        for test in &tests {
+            let test = test.as_array().unwrap();
+            let (witness, amount, pos) = match test[0].as_array() {
+                Some(extra) => {
+                    let (amount, witness) = extra.split_last().unwrap();
+                    let witness = witness.iter().map(|elem| Vec::from_hex(elem.as_str().unwrap()).unwrap()).collect();
+                    ((witness), (amount.as_f64().unwrap() * 100_000_000.0).round() as u64, 1)
+                }
+                None => (vec![], 0, 0),
+            };
+            if test.len() < pos + 4 {
+                continue;
+            }
+            let script_sig = parse_core_asm(test[pos].as_str().unwrap());
+            let script_pubkey = parse_core_asm(test[pos + 1].as_str().unwrap());
+            let flags = parse_core_flags(test[pos + 2].as_str().unwrap());
+            let expected = test[pos + 3].as_str().unwrap();
+
+            let (credit, spend) = core_test_txs(script_sig, script_pubkey, witness, amount);
+            let res = verify_input(&secp, &spend, 0, &Prevouts::One(0, credit.output[0].clone()), flags);
+            assert_eq!(core_error_name(&res), expected, "{:?}: {:?}", test, res);
+            count += 1;
+        }
+        assert!(count > 1000);
+    }
+
+    /// The prevouts and transaction of a `tx_valid.json` or `tx_invalid.json`
+    /// test, if the transaction deserializes
+    #[cfg(feature = "serde")]
+    fn parse_tx_test(test: &[::serde_json::Value]) -> (Vec<(OutPoint, TxOut)>, Option<Transaction>, VerifyFlags) {
+        use hashes::hex::FromHex;
+        use hash_types::Txid;
+
+        let prevouts = test[0].as_array().unwrap().iter().map(|prevout| {
+            let prevout = prevout.as_array().unwrap();
+            let vout = match prevout[1].as_i64().unwrap() {
+                -1 => 0xffffffff,
+                vout => vout as u32,
+            };
+            let outpoint = OutPoint::new(Txid::from_hex(prevout[0].as_str().unwrap()).unwrap(), vout);
+            let txout = TxOut {
+                value: prevout.get(3).map_or(0, |amount| amount.as_u64().unwrap()),
+                script_pubkey: parse_core_asm(prevout[2].as_str().unwrap()),
+            };
+            (outpoint, txout)
+        }).collect();
+        let tx = deserialize(&Vec::from_hex(test[1].as_str().unwrap()).unwrap()).ok();
+        (prevouts, tx, parse_core_flags(test[2].as_str().unwrap()))
+    }
+
+    /// The context free checks of Bitcoin Core's `CheckTransaction`
+    #[cfg(feature = "serde")]
+    fn check_transaction(tx: &Transaction) -> bool {
+        use std::collections::HashSet;
+
+        const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+
+        if tx.input.is_empty() || tx.output.is_empty() || tx.get_size() * 4 > 4_000_000 {
+            return false;
+        }
+        let mut total = 0;
+        for output in &tx.output {
+            total += output.value;
+            if output.value > MAX_MONEY || total > MAX_MONEY {
+                return false;
+            }
+        }
+        let outpoints: HashSet<_> = tx.input.iter().map(|input| input.previous_output).collect();
+        if outpoints.len() != tx.input.len() {
+            return false;
+        }
+        if tx.is_coin_base() {
+            let len = tx.input[0].script_sig.len();
+            len >= 2 && len <= 100
+        } else {
+            tx.input.iter().all(|input| !input.previous_output.is_null())
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn verify_tx_test(secp: &Secp256k1<secp256k1::All>, test: &[::serde_json::Value]) -> Result<(), String> {
+        let (prevouts, tx, flags) = parse_tx_test(test);
+        let tx = tx.ok_or_else(|| "deserialization failed".to_owned())?;
+        if !check_transaction(&tx) {
+            return Err("transaction check failed".to_owned());
+        }
+        let spent = tx.input.iter().map(|input| {
+            prevouts.iter()
+                .find(|&&(ref outpoint, _)| *outpoint == input.previous_output)
+                .map(|&(_, ref txout)| txout.clone())
+                .ok_or_else(|| format!("missing prevout {}", input.previous_output))
+        }).collect::<Result<Vec<_>, _>>()?;
+        for idx in 0..tx.input.len() {
+            verify_input(secp, &tx, idx, &Prevouts::All(&spent), flags)
+                .map_err(|e| format!("input {}: {:?}", idx, e))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn tx_valid_tests() {
+        use serde_json::{self, Value};
+
+        let secp = Secp256k1::new();
+        let tests: Vec<Value> = serde_json::from_str(include_str!("../../../test_data/tx_valid.json")).unwrap();
+        let mut count = 0;
+        for test in tests.iter().filter_map(Value::as_array).filter(|test| test[0].is_array()) {
+            if let Err(e) = verify_tx_test(&secp, test) {
+                panic!("{:?}: {}", test, e);
+            }
+            count += 1;
+        }
+        assert!(count > 100);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn tx_invalid_tests() {
+        use serde_json::{self, Value};
+
+        let secp = Secp256k1::new();
+        let tests: Vec<Value> = serde_json::from_str(include_str!("../../../test_data/tx_invalid.json")).unwrap();
+        let mut count = 0;
+        for test in tests.iter().filter_map(Value::as_array).filter(|test| test[0].is_array()) {
+            assert!(verify_tx_test(&secp, test).is_err(), "{:?}", test);
+            count += 1;
+        }
+        assert!(count > 50);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn bip341_key_path_tests() {
+        use serde_json::{self, Value};
+
+        let secp = Secp256k1::new();
+        let data: Value = serde_json::from_str(include_str!("../../../test_data/bip341_tests.json")).unwrap();
+        let key_path = &data["keyPathSpending"][0];
+        let spent = key_path["given"]["utxosSpent"].as_array().unwrap().iter().map(|utxo| TxOut {
+            value: utxo["amountSats"].as_u64().unwrap(),
+            script_pubkey: Script::from(Vec::from_hex(utxo["scriptPubKey"].as_str().unwrap()).unwrap()),
+        }).collect::<Vec<_>>();
+        let tx_hex = key_path["auxiliary"]["fullySignedTx"].as_str().unwrap();
+        let mut tx: Transaction = deserialize(&Vec::from_hex(tx_hex).unwrap()).unwrap();
+
+        let inputs = key_path["inputSpending"].as_array().unwrap();
+        assert!(!inputs.is_empty());
+        for input in inputs {
+            let idx = input["given"]["txinIndex"].as_u64().unwrap() as usize;
+            assert_eq!(verify_input(&secp, &tx, idx, &Prevouts::All(&spent), VerifyFlags::STANDARD), Ok(()));
+        }
+
+        // Signatures commit to all spent outputs
+        let idx = inputs[0]["given"]["txinIndex"].as_u64().unwrap() as usize;
+        let mut wrong_amounts = spent.clone();
+        wrong_amounts[(idx + 1) % spent.len()].value += 1;
+        assert_eq!(
+            verify_input(&secp, &tx, idx, &Prevouts::All(&wrong_amounts), VerifyFlags::STANDARD),
+            Err(Error::SchnorrSig)
+        );
+        assert_eq!(
+            verify_input(&secp, &tx, idx, &Prevouts::One(idx, spent[idx].clone()), VerifyFlags::STANDARD),
+            Err(Error::SigHash(sighash::Error::PrevoutKind))
+        );
+
+        let mut witness = tx.input[idx].witness.to_vec();
+        witness[0][0] ^= 1;
+        tx.input[idx].witness = Witness::from_vec(witness);
+        assert_eq!(
+            verify_input(&secp, &tx, idx, &Prevouts::All(&spent), VerifyFlags::STANDARD),
+            Err(Error::SchnorrSig)
+        );
+    }
+
+    #[test]
+    fn trace_test() {
+        let secp = Secp256k1::new();
+        let prevout = TxOut { value: 0, script_pubkey: Script::new() };
+        let tx = tapscript_spend(vec![]);
+        let prevouts = Prevouts::One(0, prevout);
+
+        let script = Builder::new()
+            .push_int(1)
+            .push_opcode(OP_IF)
+            .push_int(2).push_opcode(OP_TOALTSTACK)
+            .push_opcode(OP_ELSE)
+            .push_int(3)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let steps = script.trace(&secp, &tx, 0, &prevouts, SigVersion::Base, VerifyFlags::STANDARD).unwrap()
+            .with_stack(vec![vec![7]])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let positions = steps.iter().map(|step| step.position).collect::<Vec<_>>();
+        assert_eq!(positions, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(steps[1], Step {
+            position: 1,
+            instruction: Instruction::Op(OP_IF),
+            stack: vec![vec![7]],
+            altstack: vec![],
+            cond_stack: vec![true],
+        });
+        assert_eq!(steps[3].altstack, vec![vec![2]]);
+        assert_eq!(steps[4].cond_stack, vec![false]);
+        // Skipped instructions are yielded without changing the stack
+        assert_eq!(steps[5].instruction, Instruction::Op(OP_PUSHNUM_3));
+        assert_eq!(steps[5].stack, vec![vec![7]]);
+        assert!(steps[6].cond_stack.is_empty());
+
+        let script = Builder::new().push_int(1).push_opcode(OP_VERIFY).push_opcode(OP_VERIFY).into_script();
+        let mut trace = script.trace(&secp, &tx, 0, &prevouts, SigVersion::Base, VerifyFlags::STANDARD).unwrap();
+        assert_eq!(trace.next().unwrap().unwrap().stack, vec![vec![1]]);
+        assert_eq!(trace.next().unwrap().unwrap().stack, Vec::<Vec<u8>>::new());
+        assert_eq!(trace.next(), Some(Err(Error::InvalidStackOperation)));
+        assert_eq!(trace.position(), 2);
+        assert_eq!(trace.next(), None);
+
+        let script = Builder::new().push_int(0).push_opcode(OP_NOTIF).into_script();
+        let mut trace = script.trace(&secp, &tx, 0, &prevouts, SigVersion::Base, VerifyFlags::STANDARD).unwrap();
+        assert_eq!(trace.nth(1).unwrap().unwrap().cond_stack, vec![true]);
+        assert_eq!(trace.next(), Some(Err(Error::UnbalancedConditional)));
+        assert_eq!(trace.next(), None);
+        assert_eq!(trace.stack(), &[] as &[Vec<u8>]);
+    }
+
+    #[test]
+    fn trace_tapscript_test() {
+        let secp = Secp256k1::new();
+        let keypair = KeyPair::from_seckey_str(&secp, "0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710").unwrap();
+        let pubkey = schnorrsig::PublicKey::from_keypair(&secp, &keypair).serialize();
+        let script = Builder::new().push_slice(&pubkey).push_opcode(OP_CHECKSIG).into_script();
+        let (prevout, control) = tapscript_output(&secp, TAPROOT_LEAF_TAPSCRIPT, &script);
+        let mut tx = tapscript_spend(vec![]);
+        let sig = tapscript_sign(&secp, &tx, &prevout, &script, &keypair);
+        tx.input[0].witness = Witness::from_vec(vec![sig.clone(), script.to_bytes(), control]);
+
+        let prevouts = [prevout];
+        let prevouts = Prevouts::All(&prevouts);
+        let steps = script.trace(&secp, &tx, 0, &prevouts, SigVersion::Tapscript, VerifyFlags::STANDARD).unwrap()
+            .with_stack(vec![sig])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].position, 33);
+        assert_eq!(steps[1].stack, vec![vec![1]]);
+
+        // Key path spends have no script to trace
+        let trace = script.trace(&secp, &tx, 0, &prevouts, SigVersion::Taproot, VerifyFlags::STANDARD);
+        assert_eq!(trace.err(), Some(Error::TaprootKeyPath));
+    }
+
+    /// A taproot output committing to a single tapscript leaf, and the
+    /// control block to spend it
+    fn tapscript_output(secp: &Secp256k1<secp256k1::All>, leaf_version: u8, script: &Script) -> (TxOut, Vec<u8>) {
+        let internal_key = schnorrsig::PublicKey::from_slice(
+            &Vec::from_hex("93c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51").unwrap()
+        ).unwrap();
+        let merkle_root = TapBranchHash::from_inner(tap_leaf_hash(leaf_version, script).into_inner());
+        let (output_key, parity) = TweakedPublicKey::from_internal_key(secp, &internal_key, Some(merkle_root));
+        let mut control = vec![leaf_version | parity as u8];
+        control.extend(&internal_key.serialize()[..]);
+        let txout = TxOut {
+            value: 100_000,
+            script_pubkey: Script::new_v1_p2tr_tweaked(output_key),
+        };
+        (txout, control)
+    }
+
+    fn tapscript_spend(witness: Vec<Vec<u8>>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xffffffff,
+                witness: Witness::from_vec(witness),
+            }],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    fn tapscript_sign(secp: &Secp256k1<secp256k1::All>, tx: &Transaction, prevout: &TxOut, script: &Script, keypair: &KeyPair) -> Vec<u8> {
+        let hash = SigHashCache::new(tx).taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(slice::from_ref(prevout)),
+            tap_leaf_hash(TAPROOT_LEAF_TAPSCRIPT, script),
+            SchnorrSigHashType::Default,
+        ).unwrap();
+        let msg = Message::from_slice(&hash[..]).unwrap();
+        secp.schnorrsig_sign_with_aux_rand(&msg, keypair, &[0u8; 32])[..].to_vec()
+    }
+
+    /// Verifies a tapscript spend, signing with the given keys in witness order
+    fn verify_tapscript(script: &Script, keys: &[&KeyPair], extra: Vec<Vec<u8>>, flags: VerifyFlags) -> Result<(), Error> {
+        let secp = Secp256k1::new();
+        let (prevout, control) = tapscript_output(&secp, TAPROOT_LEAF_TAPSCRIPT, script);
+        let mut tx = tapscript_spend(vec![]);
+        let mut witness: Vec<Vec<u8>> = keys.iter().rev()
+            .map(|keypair| tapscript_sign(&secp, &tx, &prevout, script, keypair))
+            .collect();
+        witness.extend(extra);
+        witness.push(script.to_bytes());
+        witness.push(control);
+        tx.input[0].witness = Witness::from_vec(witness);
+        verify_input(&secp, &tx, 0, &Prevouts::All(&[prevout]), flags)
+    }
+
+    #[test]
+    fn tapscript_test() {
+        let secp = Secp256k1::new();
+        let key1 = KeyPair::from_seckey_str(&secp, "0b432b2677937381aef05bb02a66ecd012773062cf3fa2549e44f58ed2401710").unwrap();
+        let key2 = KeyPair::from_seckey_str(&secp, "55d7c5a2e7ff01d4fe1b1d6f8b2b8ee4bd5e0fbea7c8a4e5b9d9d1b4c6f7a8b9").unwrap();
+        let pk1 = schnorrsig::PublicKey::from_keypair(&secp, &key1).serialize();
+        let pk2 = schnorrsig::PublicKey::from_keypair(&secp, &key2).serialize();
+        let flags = VerifyFlags::STANDARD;
+
+        let checksig = Builder::new().push_slice(&pk1).push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(verify_tapscript(&checksig, &[&key1], vec![], flags), Ok(()));
+        assert_eq!(verify_tapscript(&checksig, &[&key2], vec![], flags), Err(Error::SchnorrSig));
+        // An empty signature fails the check without failing the script
+        assert_eq!(verify_tapscript(&checksig, &[], vec![vec![]], flags), Err(Error::EvalFalse));
+
+        let multi = Builder::new()
+            .push_slice(&pk1).push_opcode(OP_CHECKSIG)
+            .push_slice(&pk2).push_opcode(OP_CHECKSIGADD)
+            .push_int(2).push_opcode(OP_NUMEQUAL)
+            .into_script();
+        assert_eq!(verify_tapscript(&multi, &[&key1, &key2], vec![], flags), Ok(()));
+        assert_eq!(verify_tapscript(&multi, &[&key2, &key1], vec![], flags), Err(Error::SchnorrSig));
+
+        let legacy_multi = Builder::new()
+            .push_int(1).push_slice(&pk1).push_int(1).push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        assert_eq!(
+            verify_tapscript(&legacy_multi, &[&key1], vec![vec![]], flags),
+            Err(Error::TapscriptCheckMultiSig)
+        );
+
+        // Unknown public key types succeed unless discouraged
+        let unknown_key = Builder::new().push_slice(&[1; 33]).push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(
+            verify_tapscript(&unknown_key, &[], vec![vec![1; 64]], flags),
+            Err(Error::DiscourageUpgradablePubkeyType)
+        );
+        assert_eq!(verify_tapscript(&unknown_key, &[], vec![vec![1; 64]], VerifyFlags::CONSENSUS), Ok(()));
+
+        // OP_SUCCESSx makes the script succeed even when it can not be decoded
+        let success = Script::from(vec![0x50, OP_PUSHDATA1.into_u8()]);
+        assert_eq!(verify_tapscript(&success, &[], vec![], flags), Err(Error::DiscourageOpSuccess));
+        assert_eq!(verify_tapscript(&success, &[], vec![], VerifyFlags::CONSENSUS), Ok(()));
+
+        let minimal_if = Builder::new()
+            .push_opcode(OP_IF).push_int(1).push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(verify_tapscript(&minimal_if, &[], vec![vec![2]], flags), Err(Error::TapscriptMinimalIf));
+        assert_eq!(verify_tapscript(&minimal_if, &[], vec![vec![1]], flags), Ok(()));
+    }
+
+    #[test]
+    fn tapscript_commitment_test() {
+        let secp = Secp256k1::new();
+        let script = Builder::new().push_int(1).into_script();
+        let (prevout, control) = tapscript_output(&secp, TAPROOT_LEAF_TAPSCRIPT, &script);
+        let verify = |witness: Vec<Vec<u8>>, flags: VerifyFlags| {
+            let tx = tapscript_spend(witness);
+            verify_input(&secp, &tx, 0, &Prevouts::One(0, prevout.clone()), flags)
+        };
+        let flags = VerifyFlags::STANDARD;
+
+        assert_eq!(verify(vec![script.to_bytes(), control.clone()], flags), Ok(()));
+        // The annex is ignored
+        let annex = vec![TAPROOT_ANNEX_PREFIX, 0xff];
+        assert_eq!(verify(vec![script.to_bytes(), control.clone(), annex], flags), Ok(()));
+
+        let mut wrong_parity = control.clone();
+        wrong_parity[0] ^= 1;
+        assert_eq!(verify(vec![script.to_bytes(), wrong_parity], flags), Err(Error::WitnessProgramMismatch));
+        let other_script = Builder::new().push_int(2).into_script();
+        assert_eq!(verify(vec![other_script.to_bytes(), control.clone()], flags), Err(Error::WitnessProgramMismatch));
+        let mut wrong_size = control.clone();
+        wrong_size.push(0);
+        assert_eq!(verify(vec![script.to_bytes(), wrong_size], flags), Err(Error::TaprootWrongControlSize));
+        assert_eq!(verify(vec![], flags), Err(Error::WitnessProgramWitnessEmpty));
+
+        // Without the TAPROOT flag, witness v1 outputs are anyone-can-spend
+        // and not discouraged as an upgradable witness program
+        let consensus = VerifyFlags::CONSENSUS;
+        assert_eq!(verify(vec![], flags.clone().remove(VerifyFlags::TAPROOT)), Ok(()));
+
+        // Unknown leaf versions are not executed
+        let (prevout, control) = tapscript_output(&secp, 0xc2, &Script::from(vec![OP_RETURN.into_u8()]));
+        let tx = tapscript_spend(vec![vec![OP_RETURN.into_u8()], control]);
+        assert_eq!(
+            verify_input(&secp, &tx, 0, &Prevouts::One(0, prevout.clone()), flags),
+            Err(Error::DiscourageUpgradableTaprootVersion)
+        );
+        assert_eq!(verify_input(&secp, &tx, 0, &Prevouts::One(0, prevout), consensus), Ok(()));
+    }
+}
@@ -0,0 +1,2202 @@
+// Rust Bitcoin Library
+// Written in 2014 by
+//     Andrew Poelstra <apoelstra@wpsoftware.net>
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Script
+//!
+//! Scripts define Bitcoin's digital signature scheme: a signature is formed
+//! from a script (the second half of which is defined by a coin to be spent,
+//! and the first half provided by the spending transaction), and is valid
+//! iff the script leaves `TRUE` on the stack after being evaluated.
+//! Bitcoin's script is a stack-based assembly language similar in spirit to
+//! Forth.
+//!
+//! This module provides the structures and functions needed to support scripts.
+//!
+
+use std::default::Default;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::{error, fmt, io, str};
+
+#[cfg(feature = "serde")] use serde;
+
+use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash};
+use blockdata::opcodes;
+use consensus::{encode, Decodable, Encodable};
+use hashes::{Hash, hex};
+use hashes::hex::FromHex;
+#[cfg(feature="bitcoinconsensus")] use bitcoinconsensus;
+#[cfg(feature="bitcoinconsensus")] use std::convert;
+
+use blockdata::transaction::{Transaction, TxOut};
+use blockdata::witness::Witness;
+use secp256k1::{schnorrsig, Secp256k1, Signing, Verification};
+use util::ecdsa::PublicKey;
+use util::schnorr::{TweakedPublicKey, UntweakedPublicKey};
+use util::sighash::{self, Prevouts};
+use util::taproot::TapBranchHash;
+use OutPoint;
+use policy;
+
+pub mod interpreter;
+mod num;
+
+pub use self::interpreter::{SigVersion, VerifyFlags};
+pub use self::num::ScriptNum;
+use self::interpreter::MAX_PUBKEYS_PER_MULTISIG;
+
+#[derive(Clone, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+/// A Bitcoin script
+pub struct Script(Box<[u8]>);
+
+impl AsRef<[u8]> for Script {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Script(")?;
+        self.fmt_asm(f)?;
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &ch in self.0.iter() {
+            write!(f, "{:02x}", ch)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::UpperHex for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &ch in self.0.iter() {
+            write!(f, "{:02X}", ch)?;
+        }
+        Ok(())
+    }
+}
+
+impl hex::FromHex for Script {
+    fn from_byte_iter<I>(iter: I) -> Result<Self, hex::Error>
+        where I: Iterator<Item=Result<u8, hex::Error>> +
+            ExactSizeIterator +
+            DoubleEndedIterator,
+    {
+        Vec::from_byte_iter(iter).map(|v| Script(Box::<[u8]>::from(v)))
+    }
+}
+impl str::FromStr for Script {
+    type Err = hex::Error;
+    fn from_str(s: &str) -> Result<Self, hex::Error> {
+        hex::FromHex::from_hex(s)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+/// An object which can be used to construct a script piece by piece
+pub struct Builder(Vec<u8>, Option<opcodes::All>);
+display_from_debug!(Builder);
+
+/// Ways that a script might fail. Not everything is split up as
+/// much as it could be; patches welcome if more detailed errors
+/// would help you.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Error {
+    /// Something did a non-minimal push; for more information see
+    /// `https://github.com/bitcoin/bips/blob/master/bip-0062.mediawiki#Push_operators`
+    NonMinimalPush,
+    /// Some opcode expected a parameter, but it was missing or truncated
+    EarlyEndOfScript,
+    /// Tried to read an array off the stack as a number when it was longer than
+    /// the operation allows, 4 bytes for most of them
+    NumericOverflow,
+    /// Tried to read an array off the stack as a number when it was not
+    /// minimally encoded, under the MINIMALDATA rule
+    NonMinimalNumber,
+    /// The script finished with an empty or false top stack element
+    EvalFalse,
+    /// An OP_RETURN, or an opcode failing the script unconditionally, was executed
+    OpReturn,
+    /// The script is larger than 10000 bytes
+    ScriptSize,
+    /// A pushed element is larger than 520 bytes
+    PushSize,
+    /// The script executed more than 201 non-push opcodes
+    OpCount,
+    /// The stack and alt stack together hold more than 1000 elements
+    StackSize,
+    /// The signature count of an OP_CHECKMULTISIG is negative or greater than the key count
+    SigCount,
+    /// The key count of an OP_CHECKMULTISIG is negative or greater than 20
+    PubkeyCount,
+    /// An OP_VERIFY failed
+    Verify,
+    /// An OP_EQUALVERIFY failed
+    EqualVerify,
+    /// An OP_CHECKMULTISIGVERIFY failed
+    CheckMultiSigVerify,
+    /// An OP_CHECKSIGVERIFY failed
+    CheckSigVerify,
+    /// An OP_NUMEQUALVERIFY failed
+    NumEqualVerify,
+    /// An unknown or reserved opcode was executed, or the script is malformed
+    BadOpcode,
+    /// A disabled opcode is present in the script
+    DisabledOpcode,
+    /// An opcode needs more stack elements than the stack has
+    InvalidStackOperation,
+    /// An OP_FROMALTSTACK was executed with an empty alt stack
+    InvalidAltstackOperation,
+    /// An OP_ELSE or OP_ENDIF without OP_IF, or an OP_IF without OP_ENDIF
+    UnbalancedConditional,
+    /// OP_CHECKLOCKTIMEVERIFY or OP_CHECKSEQUENCEVERIFY got a negative argument
+    NegativeLockTime,
+    /// The lock time or sequence required by the script is not satisfied by the transaction
+    UnsatisfiedLockTime,
+    /// The signature hash type is not a defined one, under the STRICTENC rule
+    SigHashType,
+    /// The signature is not strictly DER encoded
+    SigDer,
+    /// The scriptSig contains non-push opcodes
+    SigPushOnly,
+    /// The S value of the signature is unnecessarily high, under the LOW_S rule
+    SigHighS,
+    /// The dummy element consumed by OP_CHECKMULTISIG is not empty, under the NULLDUMMY rule
+    SigNullDummy,
+    /// The public key is neither compressed nor uncompressed, under the STRICTENC rule
+    PubkeyType,
+    /// The stack does not hold exactly one element after execution
+    CleanStack,
+    /// The argument of OP_IF or OP_NOTIF is not minimal, under the MINIMALIF rule
+    MinimalIf,
+    /// A failed signature check had a non-empty signature, under the NULLFAIL rule
+    SigNullFail,
+    /// An upgradable OP_NOP was executed, under the DISCOURAGE_UPGRADABLE_NOPS rule
+    DiscourageUpgradableNops,
+    /// An unknown witness version was spent, under the DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM rule
+    DiscourageUpgradableWitnessProgram,
+    /// An unknown taproot leaf version was spent, under the DISCOURAGE_UPGRADABLE_TAPROOT_VERSION rule
+    DiscourageUpgradableTaprootVersion,
+    /// A tapscript contains an OP_SUCCESSx, under the DISCOURAGE_OP_SUCCESS rule
+    DiscourageOpSuccess,
+    /// A tapscript checked a signature against an unknown public key type,
+    /// under the DISCOURAGE_UPGRADABLE_PUBKEYTYPE rule
+    DiscourageUpgradablePubkeyType,
+    /// A segwit v0 program is neither 20 nor 32 bytes long
+    WitnessProgramWrongLength,
+    /// A witness program was spent with an empty witness
+    WitnessProgramWitnessEmpty,
+    /// The witness doesn't match the witness program
+    WitnessProgramMismatch,
+    /// A native witness program was spent with a non-empty scriptSig
+    WitnessMalleated,
+    /// A P2SH-wrapped witness program was spent with a scriptSig which is not
+    /// a single push of the redeem script
+    WitnessMalleatedP2sh,
+    /// A witness was provided for an input which is not a witness program
+    WitnessUnexpected,
+    /// A segwit v0 script checked a signature against an uncompressed public
+    /// key, under the WITNESS_PUBKEYTYPE rule
+    WitnessPubkeyType,
+    /// A schnorr signature is neither 64 nor 65 bytes long
+    SchnorrSigSize,
+    /// A schnorr signature has an invalid sighash type
+    SchnorrSigHashType,
+    /// A schnorr signature is invalid
+    SchnorrSig,
+    /// The taproot control block has an invalid size
+    TaprootWrongControlSize,
+    /// A script was to be executed as a taproot key path spend, which
+    /// executes no script
+    TaprootKeyPath,
+    /// A tapscript checks more signatures than its witness size allows
+    TapscriptValidationWeight,
+    /// OP_CHECKMULTISIG or OP_CHECKMULTISIGVERIFY was executed in a tapscript
+    TapscriptCheckMultiSig,
+    /// The argument of OP_IF or OP_NOTIF in a tapscript is not minimal
+    TapscriptMinimalIf,
+    /// An OP_CODESEPARATOR is present in a legacy script, under the CONST_SCRIPTCODE rule
+    OpCodeSeparator,
+    /// A signature is found in the legacy script code, under the CONST_SCRIPTCODE rule
+    SigFindAndDelete,
+    /// The signature hash of a transaction input could not be computed
+    SigHash(sighash::Error),
+    #[cfg(feature="bitcoinconsensus")]
+    /// Error validating the script with bitcoinconsensus library
+    BitcoinConsensus(bitcoinconsensus::Error),
+    /// The script is not of the `m <pubkey>... n OP_CHECKMULTISIG` form
+    NotMultisig,
+    /// Can not find the spent output
+    UnknownSpentOutput(OutPoint),
+    #[cfg(feature="bitcoinconsensus")]
+    /// Can not serialize the spending transaction
+    SerializationError
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match *self {
+            Error::NonMinimalPush => "non-minimal datapush",
+            Error::EarlyEndOfScript => "unexpected end of script",
+            Error::NumericOverflow => "numeric overflow (number on stack larger than the operation allows)",
+            Error::NonMinimalNumber => "non-minimally encoded number on stack",
+            Error::EvalFalse => "script evaluated without error but finished with a false/empty top stack element",
+            Error::OpReturn => "OP_RETURN was encountered",
+            Error::ScriptSize => "script is too big",
+            Error::PushSize => "push value size limit exceeded",
+            Error::OpCount => "operation limit exceeded",
+            Error::StackSize => "stack size limit exceeded",
+            Error::SigCount => "signature count negative or greater than pubkey count",
+            Error::PubkeyCount => "pubkey count negative or limit exceeded",
+            Error::Verify => "script failed an OP_VERIFY operation",
+            Error::EqualVerify => "script failed an OP_EQUALVERIFY operation",
+            Error::CheckMultiSigVerify => "script failed an OP_CHECKMULTISIGVERIFY operation",
+            Error::CheckSigVerify => "script failed an OP_CHECKSIGVERIFY operation",
+            Error::NumEqualVerify => "script failed an OP_NUMEQUALVERIFY operation",
+            Error::BadOpcode => "opcode missing or not understood",
+            Error::DisabledOpcode => "attempted to use a disabled opcode",
+            Error::InvalidStackOperation => "operation not valid with the current stack size",
+            Error::InvalidAltstackOperation => "operation not valid with the current altstack size",
+            Error::UnbalancedConditional => "invalid OP_IF construction",
+            Error::NegativeLockTime => "negative locktime",
+            Error::UnsatisfiedLockTime => "locktime requirement not satisfied",
+            Error::SigHashType => "signature hash type missing or not understood",
+            Error::SigDer => "non-canonical DER signature",
+            Error::SigPushOnly => "only push operators allowed in signatures",
+            Error::SigHighS => "non-canonical signature: S value is unnecessarily high",
+            Error::SigNullDummy => "dummy CHECKMULTISIG argument must be zero",
+            Error::PubkeyType => "public key is neither compressed or uncompressed",
+            Error::CleanStack => "stack size must be exactly one after execution",
+            Error::MinimalIf => "OP_IF/NOTIF argument must be minimal",
+            Error::SigNullFail => "signature must be zero for failed CHECK(MULTI)SIG operation",
+            Error::DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            Error::DiscourageUpgradableWitnessProgram => "witness version reserved for soft-fork upgrades",
+            Error::DiscourageUpgradableTaprootVersion => "taproot version reserved for soft-fork upgrades",
+            Error::DiscourageOpSuccess => "OP_SUCCESSx reserved for soft-fork upgrades",
+            Error::DiscourageUpgradablePubkeyType => "public key version reserved for soft-fork upgrades",
+            Error::WitnessProgramWrongLength => "witness program has incorrect length",
+            Error::WitnessProgramWitnessEmpty => "witness program was passed an empty witness",
+            Error::WitnessProgramMismatch => "witness program hash mismatch",
+            Error::WitnessMalleated => "witness requires empty scriptSig",
+            Error::WitnessMalleatedP2sh => "witness requires only-redeemscript scriptSig",
+            Error::WitnessUnexpected => "witness provided for non-witness script",
+            Error::WitnessPubkeyType => "using non-compressed keys in segwit",
+            Error::SchnorrSigSize => "invalid schnorr signature size",
+            Error::SchnorrSigHashType => "invalid schnorr signature hash type",
+            Error::SchnorrSig => "invalid schnorr signature",
+            Error::TaprootWrongControlSize => "invalid taproot control block size",
+            Error::TaprootKeyPath => "taproot key path spends execute no script",
+            Error::TapscriptValidationWeight => "too much signature validation relative to witness weight",
+            Error::TapscriptCheckMultiSig => "OP_CHECKMULTISIG(VERIFY) is not available in tapscript",
+            Error::TapscriptMinimalIf => "OP_IF/NOTIF argument must be minimal in tapscript",
+            Error::OpCodeSeparator => "using OP_CODESEPARATOR in non-witness script",
+            Error::SigFindAndDelete => "signature is found in scriptCode",
+            Error::SigHash(ref _e) => "signature hash computation failed",
+            #[cfg(feature="bitcoinconsensus")]
+            Error::BitcoinConsensus(ref _n) => "bitcoinconsensus verification failed",
+            Error::NotMultisig => "not a CHECKMULTISIG script",
+            Error::UnknownSpentOutput(ref _point) => "unknown spent output Transaction::verify()",
+            #[cfg(feature="bitcoinconsensus")]
+            Error::SerializationError => "can not serialize the spending transaction in Transaction::verify()",
+        };
+        f.write_str(str)
+    }
+}
+
+impl error::Error for Error {}
+
+#[doc(hidden)]
+impl From<sighash::Error> for Error {
+    fn from(e: sighash::Error) -> Error {
+        Error::SigHash(e)
+    }
+}
+
+/// Ways that parsing the assembly representation of a script might fail, see
+/// [Script::from_asm]. Each variant holds the byte position of the offending
+/// word in the parsed string.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum AsmError {
+    /// The word is neither an opcode name, a number nor hex data
+    UnknownOpcode(usize, String),
+    /// A push opcode is not followed by the pushed data
+    MissingPushData(usize),
+    /// The pushed data is not valid hex
+    InvalidHex(usize, hex::Error),
+    /// The pushed data does not have the length of its push opcode
+    InvalidPushLength(usize),
+    /// The number can not be encoded as a script number
+    NumberOutOfRange(usize),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AsmError::UnknownOpcode(pos, ref word) => write!(f, "unknown opcode `{}` at position {}", word, pos),
+            AsmError::MissingPushData(pos) => write!(f, "missing push data at position {}", pos),
+            AsmError::InvalidHex(pos, ref e) => write!(f, "invalid hex push data at position {}: {}", pos, e),
+            AsmError::InvalidPushLength(pos) => write!(f, "push data length does not match its opcode at position {}", pos),
+            AsmError::NumberOutOfRange(pos) => write!(f, "number out of range at position {}", pos),
+        }
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// The type of an output script and the data it pays to, as recognized by
+/// Bitcoin Core's `Solver`, see [Script::classify]
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ScriptType {
+    /// Pay to public key
+    P2pk(PublicKey),
+    /// Pay to public key hash
+    P2pkh(PubkeyHash),
+    /// Pay to script hash
+    P2sh(ScriptHash),
+    /// Pay to witness public key hash
+    P2wpkh(WPubkeyHash),
+    /// Pay to witness script hash
+    P2wsh(WScriptHash),
+    /// Pay to taproot
+    P2tr(TweakedPublicKey),
+    /// Bare multisig, requiring signatures of `required` of the `keys`
+    Multisig {
+        /// The number of required signatures
+        required: usize,
+        /// The public keys, in script order
+        keys: Vec<PublicKey>,
+    },
+    /// OP_RETURN followed by pushes only, which carries data
+    NullData,
+    /// A witness program of a version or length with no defined meaning yet
+    WitnessUnknown {
+        /// The witness program version
+        version: ::bech32::u5,
+        /// The witness program
+        program: Vec<u8>,
+    },
+    /// Any other script
+    NonStandard,
+}
+
+#[cfg(feature="bitcoinconsensus")]
+#[doc(hidden)]
+impl convert::From<bitcoinconsensus::Error> for Error {
+    fn from(err: bitcoinconsensus::Error) -> Error {
+        match err {
+            _ => Error::BitcoinConsensus(err)
+        }
+    }
+}
+/// Helper to encode an integer in script format
+fn build_scriptint(n: i64) -> Vec<u8> {
+    ScriptNum::from(n).to_bytes()
+}
+
+/// Helper to decode an integer in script format
+/// Notice that this fails on overflow: the result is the same as in
+/// bitcoind, that only 4-byte signed-magnitude values may be read as
+/// numbers. They can be added or subtracted (and a long time ago,
+/// multiplied and divided), and this may result in numbers which
+/// can't be written out in 4 bytes or less. This is ok! The number
+/// just can't be read as a number again.
+/// This is a bit crazy and subtle, but it makes sense: you can load
+/// 32-bit numbers and do anything with them, which back when mult/div
+/// was allowed, could result in up to a 64-bit number. We don't want
+/// overflow since that's surprising --- and we don't want numbers that
+/// don't fit in 64 bits (for efficiency on modern processors) so we
+/// simply say, anything in excess of 32 bits is no longer a number.
+/// This is basically a ranged type implementation.
+pub fn read_scriptint(v: &[u8]) -> Result<i64, Error> {
+    ScriptNum::from_slice(v, false, ScriptNum::MAX_SIZE).map(ScriptNum::to_i64)
+}
+
+/// This is like "`read_scriptint` then map 0 to false and everything
+/// else as true", except that the overflow rules don't apply.
+#[inline]
+pub fn read_scriptbool(v: &[u8]) -> bool {
+    !(v.is_empty() ||
+        ((v[v.len() - 1] == 0 || v[v.len() - 1] == 0x80) &&
+         v.iter().rev().skip(1).all(|&w| w == 0)))
+}
+
+/// Read a script-encoded unsigned integer
+pub fn read_uint(data: &[u8], size: usize) -> Result<usize, Error> {
+    if data.len() < size {
+        Err(Error::EarlyEndOfScript)
+    } else {
+        let mut ret = 0;
+        for (i, item) in data.iter().take(size).enumerate() {
+            ret += (*item as usize) << (i * 8);
+        }
+        Ok(ret)
+    }
+}
+
+impl Script {
+    /// Creates a new empty script
+    pub fn new() -> Script { Script(vec![].into_boxed_slice()) }
+
+    /// Generates P2PK-type of scriptPubkey
+    pub fn new_p2pk(pubkey: &PublicKey) -> Script {
+        Builder::new()
+            .push_key(pubkey)
+            .push_opcode(opcodes::all::OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Generates a `m <pubkey>... n OP_CHECKMULTISIG` script requiring
+    /// `required` signatures of the keys, in the given order.
+    ///
+    /// Fails with [Error::PubkeyCount] if there are more than 20 keys, and with
+    /// [Error::SigCount] unless between one and all the keys are required.
+    pub fn new_multisig(required: usize, keys: &[PublicKey]) -> Result<Script, Error> {
+        if keys.len() > MAX_PUBKEYS_PER_MULTISIG as usize {
+            return Err(Error::PubkeyCount);
+        }
+        if required == 0 || required > keys.len() {
+            return Err(Error::SigCount);
+        }
+        let builder = keys.iter().fold(Builder::new().push_int(required as i64), |builder, key| {
+            builder.push_key(key)
+        });
+        Ok(builder
+            .push_int(keys.len() as i64)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script())
+    }
+
+    /// Generates a multisig script like [Script::new_multisig], with the keys
+    /// sorted by their serialization as BIP67 specifies for compressed keys
+    pub fn new_sorted_multisig(required: usize, keys: &[PublicKey]) -> Result<Script, Error> {
+        let mut keys = keys.to_vec();
+        keys.sort_by_key(|key| key.to_bytes());
+        Script::new_multisig(required, &keys)
+    }
+
+    /// Generates P2PKH-type of scriptPubkey
+    pub fn new_p2pkh(pubkey_hash: &PubkeyHash) -> Script {
+        Builder::new()
+            .push_opcode(opcodes::all::OP_DUP)
+            .push_opcode(opcodes::all::OP_HASH160)
+            .push_slice(&pubkey_hash[..])
+            .push_opcode(opcodes::all::OP_EQUALVERIFY)
+            .push_opcode(opcodes::all::OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Generates P2SH-type of scriptPubkey with a given hash of the redeem script
+    pub fn new_p2sh(script_hash: &ScriptHash) -> Script {
+        Builder::new()
+            .push_opcode(opcodes::all::OP_HASH160)
+            .push_slice(&script_hash[..])
+            .push_opcode(opcodes::all::OP_EQUAL)
+            .into_script()
+    }
+
+    /// Generates P2WPKH-type of scriptPubkey
+    pub fn new_v0_wpkh(pubkey_hash: &WPubkeyHash) -> Script {
+        Script::new_witness_program(::bech32::u5::try_from_u8(0).unwrap(), &pubkey_hash.to_vec())
+    }
+
+    /// Generates P2WSH-type of scriptPubkey with a given hash of the redeem script
+    pub fn new_v0_wsh(script_hash: &WScriptHash) -> Script {
+        Script::new_witness_program(::bech32::u5::try_from_u8(0).unwrap(), &script_hash.to_vec())
+    }
+
+    /// Generates P2TR-type of scriptPubkey committing to an internal key and
+    /// the merkle root of a script tree, or to no script if `merkle_root` is `None`
+    pub fn new_v1_p2tr<C: Verification>(secp: &Secp256k1<C>, internal_key: UntweakedPublicKey, merkle_root: Option<TapBranchHash>) -> Script {
+        let (output_key, _) = TweakedPublicKey::from_internal_key(secp, &internal_key, merkle_root);
+        Script::new_v1_p2tr_tweaked(output_key)
+    }
+
+    /// Generates P2TR-type of scriptPubkey with an already tweaked output key
+    pub fn new_v1_p2tr_tweaked(output_key: TweakedPublicKey) -> Script {
+        Script::new_witness_program(::bech32::u5::try_from_u8(1).unwrap(), &output_key.serialize())
+    }
+
+    /// Generates P2WSH-type of scriptPubkey with a given hash of the redeem script
+    pub fn new_witness_program(ver: ::bech32::u5, program: &[u8]) -> Script {
+        let mut verop = ver.to_u8();
+        assert!(verop <= 16, "incorrect witness version provided: {}", verop);
+        if verop > 0 {
+            verop = 0x50 + verop;
+        }
+        Builder::new()
+            .push_opcode(verop.into())
+            .push_slice(&program)
+            .into_script()
+    }
+
+    /// Generates OP_RETURN-type of scriptPubkey for a given data
+    pub fn new_op_return(data: &[u8]) -> Script {
+        Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(data)
+            .into_script()
+    }
+
+    /// Returns 160-bit hash of the script
+    pub fn script_hash(&self) -> ScriptHash {
+        ScriptHash::hash(&self.as_bytes())
+    }
+
+    /// Returns 256-bit hash of the script for P2WSH outputs
+    pub fn wscript_hash(&self) -> WScriptHash {
+        WScriptHash::hash(&self.as_bytes())
+    }
+
+    /// The length in bytes of the script
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Whether the script is the empty script
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Returns the script data
+    pub fn as_bytes(&self) -> &[u8] { &*self.0 }
+
+    /// Returns a copy of the script data
+    pub fn to_bytes(&self) -> Vec<u8> { self.0.clone().into_vec() }
+
+    /// Convert the script into a byte vector
+    pub fn into_bytes(self) -> Vec<u8> { self.0.into_vec() }
+
+    /// Compute the P2SH output corresponding to this redeem script
+    pub fn to_p2sh(&self) -> Script {
+        Script::new_p2sh(&self.script_hash())
+    }
+
+    /// Compute the P2WSH output corresponding to this witnessScript (aka the "witness redeem
+    /// script")
+    pub fn to_v0_p2wsh(&self) -> Script {
+        Script::new_v0_wsh(&self.wscript_hash())
+    }
+
+    /// Checks whether a script pubkey is a p2sh output
+    #[inline]
+    pub fn is_p2sh(&self) -> bool {
+        self.0.len() == 23 &&
+        self.0[0] == opcodes::all::OP_HASH160.into_u8() &&
+        self.0[1] == opcodes::all::OP_PUSHBYTES_20.into_u8() &&
+        self.0[22] == opcodes::all::OP_EQUAL.into_u8()
+    }
+
+    /// Checks whether a script pubkey is a p2pkh output
+    #[inline]
+    pub fn is_p2pkh(&self) -> bool {
+        self.0.len() == 25 &&
+        self.0[0] == opcodes::all::OP_DUP.into_u8() &&
+        self.0[1] == opcodes::all::OP_HASH160.into_u8() &&
+        self.0[2] == opcodes::all::OP_PUSHBYTES_20.into_u8() &&
+        self.0[23] == opcodes::all::OP_EQUALVERIFY.into_u8() &&
+        self.0[24] == opcodes::all::OP_CHECKSIG.into_u8()
+    }
+
+    /// Checks whether a script pubkey is a p2pk output
+    #[inline]
+    pub fn is_p2pk(&self) -> bool {
+        (self.0.len() == 67 &&
+            self.0[0] == opcodes::all::OP_PUSHBYTES_65.into_u8() &&
+            self.0[66] == opcodes::all::OP_CHECKSIG.into_u8())
+     || (self.0.len() == 35 &&
+            self.0[0] == opcodes::all::OP_PUSHBYTES_33.into_u8() &&
+            self.0[34] == opcodes::all::OP_CHECKSIG.into_u8())
+    }
+
+    /// Checks whether a script pubkey is a Segregated Witness (segwit) program.
+    #[inline]
+    pub fn is_witness_program(&self) -> bool {
+        // A scriptPubKey (or redeemScript as defined in BIP16/P2SH) that consists of a 1-byte
+        // push opcode (for 0 to 16) followed by a data push between 2 and 40 bytes gets a new
+        // special meaning. The value of the first push is called the "version byte". The following
+        // byte vector pushed is called the "witness program".
+        let min_vernum: u8 = opcodes::all::OP_PUSHNUM_1.into_u8();
+        let max_vernum: u8 = opcodes::all::OP_PUSHNUM_16.into_u8();
+        self.0.len() >= 4
+            && self.0.len() <= 42
+            // Version 0 or PUSHNUM_1-PUSHNUM_16
+            && (self.0[0] == 0 || self.0[0] >= min_vernum && self.0[0] <= max_vernum)
+            // Second byte push opcode 2-40 bytes
+            && self.0[1] >= opcodes::all::OP_PUSHBYTES_2.into_u8()
+            && self.0[1] <= opcodes::all::OP_PUSHBYTES_40.into_u8()
+            // Check that the rest of the script has the correct size
+            && self.0.len() - 2 == self.0[1] as usize
+    }
+
+    /// Checks whether a script pubkey is a p2wsh output
+    #[inline]
+    pub fn is_v0_p2wsh(&self) -> bool {
+        self.0.len() == 34 &&
+        self.0[0] == opcodes::all::OP_PUSHBYTES_0.into_u8() &&
+        self.0[1] == opcodes::all::OP_PUSHBYTES_32.into_u8()
+    }
+
+    /// Checks whether a script pubkey is a p2tr output
+    #[inline]
+    pub fn is_v1_p2tr(&self) -> bool {
+        self.0.len() == 34 &&
+        self.0[0] == opcodes::all::OP_PUSHNUM_1.into_u8() &&
+        self.0[1] == opcodes::all::OP_PUSHBYTES_32.into_u8()
+    }
+
+    /// Checks whether a script pubkey is a p2wpkh output
+    #[inline]
+    pub fn is_v0_p2wpkh(&self) -> bool {
+        self.0.len() == 22 &&
+            self.0[0] == opcodes::all::OP_PUSHBYTES_0.into_u8() &&
+            self.0[1] == opcodes::all::OP_PUSHBYTES_20.into_u8()
+    }
+
+    /// Check if this is an OP_RETURN output
+    pub fn is_op_return (&self) -> bool {
+        !self.0.is_empty() && (opcodes::All::from(self.0[0]) == opcodes::all::OP_RETURN)
+    }
+
+    /// Whether a script can be proven to have no satisfying input
+    pub fn is_provably_unspendable(&self) -> bool {
+        !self.0.is_empty() && (opcodes::All::from(self.0[0]).classify() == opcodes::Class::ReturnOp ||
+                               opcodes::All::from(self.0[0]).classify() == opcodes::Class::IllegalOp)
+    }
+
+    /// Classifies an output script by the standard template it matches,
+    /// mirroring Bitcoin Core's `Solver`.
+    ///
+    /// Unlike Bitcoin Core, which only checks the size of public keys, keys
+    /// which are not valid curve points make P2PK, P2TR and multisig scripts
+    /// non-standard. Witness v0 programs which are neither 20 nor 32 bytes
+    /// long are non-standard too, as they are unspendable.
+    pub fn classify(&self) -> ScriptType {
+        if self.is_p2sh() {
+            return ScriptType::P2sh(ScriptHash::from_slice(&self.0[2..22]).expect("20 bytes"));
+        }
+        if self.is_witness_program() {
+            let program = &self.0[2..];
+            return match self.0[0] {
+                0 if program.len() == 20 => ScriptType::P2wpkh(WPubkeyHash::from_slice(program).expect("20 bytes")),
+                0 if program.len() == 32 => ScriptType::P2wsh(WScriptHash::from_slice(program).expect("32 bytes")),
+                0 => ScriptType::NonStandard,
+                verop if verop == opcodes::all::OP_PUSHNUM_1.into_u8() && program.len() == 32 => {
+                    match schnorrsig::PublicKey::from_slice(program) {
+                        Ok(key) => ScriptType::P2tr(TweakedPublicKey::dangerous_assume_tweaked(key)),
+                        Err(_) => ScriptType::NonStandard,
+                    }
+                }
+                verop => ScriptType::WitnessUnknown {
+                    version: ::bech32::u5::try_from_u8(verop - (opcodes::all::OP_PUSHNUM_1.into_u8() - 1))
+                        .expect("witness version is at most 16"),
+                    program: program.to_vec(),
+                },
+            };
+        }
+        if self.is_op_return() {
+            let push_only = Script::from(self.0[1..].to_vec()).instructions().all(|instruction| match instruction {
+                Ok(Instruction::PushBytes(_)) => true,
+                Ok(Instruction::Op(op)) => op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8(),
+                Err(_) => false,
+            });
+            return if push_only { ScriptType::NullData } else { ScriptType::NonStandard };
+        }
+        if self.is_p2pk() {
+            return match PublicKey::from_slice(&self.0[1..self.0.len() - 1]) {
+                Ok(key) => ScriptType::P2pk(key),
+                Err(_) => ScriptType::NonStandard,
+            };
+        }
+        if self.is_p2pkh() {
+            return ScriptType::P2pkh(PubkeyHash::from_slice(&self.0[3..23]).expect("20 bytes"));
+        }
+        // Like Bitcoin Core, accept keys which are not pushed minimally
+        match Script::parse_multisig_from(self.instructions()) {
+            Ok((required, keys)) => {
+                // Bitcoin Core only recognizes counts pushed with OP_1 to OP_16
+                if keys.len() <= 16 {
+                    ScriptType::Multisig { required: required, keys: keys }
+                } else {
+                    ScriptType::NonStandard
+                }
+            }
+            Err(_) => ScriptType::NonStandard,
+        }
+    }
+
+    /// Parses a `m <pubkey>... n OP_CHECKMULTISIG` script into the number of
+    /// required signatures and the keys, in script order.
+    ///
+    /// The numbers must be minimally encoded and the keys valid compressed or
+    /// uncompressed keys, failing with [Error::PubkeyType] otherwise. The
+    /// counts are checked like [Script::new_multisig] does.
+    pub fn parse_multisig(&self) -> Result<(usize, Vec<PublicKey>), Error> {
+        Script::parse_multisig_from(self.instructions_minimal())
+    }
+
+    fn parse_multisig_from(instructions: Instructions) -> Result<(usize, Vec<PublicKey>), Error> {
+        let read_count = |instruction: Option<Result<Instruction, Error>>| match instruction {
+            Some(Ok(Instruction::Op(op))) => match op.classify() {
+                opcodes::Class::PushNum(n) if n >= 0 => Ok(n as i64),
+                _ => Err(Error::NotMultisig),
+            },
+            Some(Ok(Instruction::PushBytes(&[]))) => Ok(0),
+            Some(Ok(Instruction::PushBytes(data))) => match read_scriptint(data) {
+                Ok(n) if n > 16 && build_scriptint(n) == data => Ok(n),
+                _ => Err(Error::NotMultisig),
+            },
+            _ => Err(Error::NotMultisig),
+        };
+        let mut instructions = instructions.peekable();
+        let required = read_count(instructions.next())?;
+        let mut keys = vec![];
+        while let Some(&Ok(Instruction::PushBytes(data))) = instructions.peek() {
+            if data.len() != 33 && data.len() != 65 {
+                break;
+            }
+            keys.push(PublicKey::from_slice(data).map_err(|_| Error::PubkeyType)?);
+            instructions.next();
+        }
+        let total = read_count(instructions.next())?;
+        match (instructions.next(), instructions.next()) {
+            (Some(Ok(Instruction::Op(opcodes::all::OP_CHECKMULTISIG))), None) => {}
+            _ => return Err(Error::NotMultisig),
+        }
+        if total != keys.len() as i64 {
+            return Err(Error::NotMultisig);
+        }
+        if total > MAX_PUBKEYS_PER_MULTISIG {
+            return Err(Error::PubkeyCount);
+        }
+        if required == 0 || required > total {
+            return Err(Error::SigCount);
+        }
+        Ok((required as usize, keys))
+    }
+
+    /// Counts the signature operations of the script, like Bitcoin Core's
+    /// `GetSigOpCount`.
+    ///
+    /// An OP_CHECKMULTISIG(VERIFY) counts as 20 operations, or with `accurate`
+    /// as its number of keys when they are pushed with OP_1 to OP_16. Counting
+    /// stops at the first undecodable instruction.
+    pub fn count_sigops(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut last_opcode = None;
+        for instruction in self.instructions() {
+            let opcode = match instruction {
+                Ok(Instruction::Op(op)) => op,
+                Ok(Instruction::PushBytes(_)) => {
+                    last_opcode = None;
+                    continue;
+                }
+                Err(_) => break,
+            };
+            match opcode {
+                opcodes::all::OP_CHECKSIG | opcodes::all::OP_CHECKSIGVERIFY => count += 1,
+                opcodes::all::OP_CHECKMULTISIG | opcodes::all::OP_CHECKMULTISIGVERIFY => {
+                    count += match last_opcode.map(opcodes::All::classify) {
+                        Some(opcodes::Class::PushNum(n)) if accurate && n > 0 => n as usize,
+                        _ => MAX_PUBKEYS_PER_MULTISIG as usize,
+                    };
+                }
+                _ => {}
+            }
+            last_opcode = Some(opcode);
+        }
+        count
+    }
+
+    /// Counts the signature operations of the redeem script revealed by
+    /// `script_sig` if this is a P2SH output, like Bitcoin Core's
+    /// `GetSigOpCount(scriptSig)`. Counts zero for other outputs.
+    pub fn count_p2sh_sigops(&self, script_sig: &Script) -> usize {
+        if !self.is_p2sh() {
+            return 0;
+        }
+        match script_sig.last_push_only_data() {
+            Some(redeem_script) => Script::from(redeem_script.to_vec()).count_sigops(true),
+            None => 0,
+        }
+    }
+
+    /// Counts the signature operations of a witness program spent with
+    /// `script_sig` and `witness`, either native or wrapped in P2SH, like
+    /// Bitcoin Core's `CountWitnessSigOps`. Counts zero for other outputs.
+    ///
+    /// P2WPKH spends count one operation, P2WSH spends the operations of their
+    /// witness script, and taproot spends none as they are limited by their
+    /// validation weight instead.
+    pub fn count_witness_sigops(&self, script_sig: &Script, witness: &Witness) -> usize {
+        if self.is_witness_program() {
+            return witness_program_sigops(self, witness);
+        }
+        if self.is_p2sh() {
+            if let Some(redeem_script) = script_sig.last_push_only_data() {
+                let redeem_script = Script::from(redeem_script.to_vec());
+                if redeem_script.is_witness_program() {
+                    return witness_program_sigops(&redeem_script, witness);
+                }
+            }
+        }
+        0
+    }
+
+    /// Whether the script only pushes data, counting OP_RESERVED as a push
+    /// like Bitcoin Core's `IsPushOnly` does
+    pub fn is_push_only(&self) -> bool {
+        self.last_push_only_data().is_some()
+    }
+
+    /// The data of the last push of a push only script, which is empty if the
+    /// last push is a number
+    pub(crate) fn last_push_only_data(&self) -> Option<&[u8]> {
+        let mut data: &[u8] = &[];
+        for instruction in self.instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(bytes)) => data = bytes,
+                Ok(Instruction::Op(op)) if op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8() => data = &[],
+                _ => return None,
+            }
+        }
+        Some(data)
+    }
+
+    /// The minimum value an output to a witness script must have in order to be
+    /// broadcastable on today's bitcoin network.
+    #[deprecated(since = "0.26.1", note = "please use `dust_value` or `policy::dust_threshold` instead")]
+    pub const WITNESS_OUTPUT_DUST_THRESHOLD: u64 = 294;
+
+    /// The minimum value an output to a non-witness script must have in order to be
+    /// broadcastable on today's bitcoin network.
+    #[deprecated(since = "0.26.1", note = "please use `dust_value` or `policy::dust_threshold` instead")]
+    pub const LEGACY_OUTPUT_DUST_THRESHOLD: u64 = 546;
+
+    /// Gets the minimum value an output with this script should have in order to be
+    /// broadcastable on today's bitcoin network, at the default dust relay fee.
+    /// Use [policy::dust_threshold] for other fee rates.
+    pub fn dust_value(&self) -> u64 {
+        policy::dust_threshold(self, policy::DUST_RELAY_TX_FEE)
+    }
+
+    /// Iterate over the script in the form of `Instruction`s, which are an enum covering
+    /// opcodes, datapushes and errors. At most one error will be returned and then the
+    /// iterator will end. To instead iterate over the script as sequence of bytes, treat
+    /// it as a slice using `script[..]` or convert it to a vector using `into_bytes()`.
+    ///
+    /// To force minimal pushes, use [Self::instructions_minimal].
+    pub fn instructions(&self) -> Instructions {
+        Instructions {
+            data: &self.0[..],
+            enforce_minimal: false,
+        }
+    }
+
+    /// Iterate over the script in the form of `Instruction`s while enforcing
+    /// minimal pushes.
+    pub fn instructions_minimal(&self) -> Instructions {
+        Instructions {
+            data: &self.0[..],
+            enforce_minimal: true,
+        }
+    }
+
+    /// Iterate over the script like [Self::instructions], yielding each
+    /// `Instruction` along with its byte offset in the script.
+    pub fn instruction_indices(&self) -> InstructionIndices {
+        InstructionIndices::from_slice(&self.0[..], 0, false)
+    }
+
+    /// Iterate over the script like [Self::instructions_minimal], yielding
+    /// each `Instruction` along with its byte offset in the script.
+    pub fn instruction_indices_minimal(&self) -> InstructionIndices {
+        InstructionIndices::from_slice(&self.0[..], 0, true)
+    }
+
+    /// Splits the script in two at the byte offset `pos`, which must be the
+    /// start of an instruction or the end of the script. Returns `None` if
+    /// it isn't, or if the script fails to parse before it.
+    pub fn split_at_instruction(&self, pos: usize) -> Option<(Script, Script)> {
+        let mut instructions = self.instruction_indices();
+        while instructions.pos() < pos {
+            match instructions.next() {
+                Some(Ok(_)) => {}
+                _ => return None,
+            }
+        }
+        if instructions.pos() == pos {
+            Some((Script::from(self.0[..pos].to_vec()), Script::from(self.0[pos..].to_vec())))
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature="bitcoinconsensus")]
+    /// verify spend of an input script
+    /// # Parameters
+    ///  * index - the input index in spending which is spending this transaction
+    ///  * amount - the amount this script guards
+    ///  * spending - the transaction that attempts to spend the output holding this script
+    pub fn verify (&self, index: usize, amount: u64, spending: &[u8]) -> Result<(), Error> {
+        Ok(bitcoinconsensus::verify (&self.0[..], amount, spending, index)?)
+    }
+
+    /// Verify the spend of an output holding this script with the native
+    /// script interpreter
+    /// # Parameters
+    ///  * secp - the secp256k1 context used to check signatures
+    ///  * index - the input index in spending which is spending this transaction
+    ///  * amount - the amount this script guards
+    ///  * spending - the transaction that attempts to spend the output holding this script
+    ///  * flags - the script verification rules to enforce
+    ///
+    /// Taproot signatures committing to all the spent outputs can't be checked
+    /// from this output alone, use [interpreter::verify_input] for them.
+    pub fn verify_native<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: usize,
+        amount: u64,
+        spending: &Transaction,
+        flags: VerifyFlags,
+    ) -> Result<(), Error> {
+        let prevout = TxOut { value: amount, script_pubkey: self.clone() };
+        interpreter::verify_input(secp, spending, index, &Prevouts::One(index, prevout), flags)
+    }
+
+    /// Execute this script step by step as the script of an input, yielding
+    /// the execution state after each instruction, see [interpreter::Trace]
+    /// # Parameters
+    ///  * secp - the secp256k1 context used to check signatures
+    ///  * spending - the transaction whose input executes this script
+    ///  * index - the input index in spending
+    ///  * prevouts - the outputs spent by spending, see [interpreter::verify_input]
+    ///  * sigversion - whether this is a legacy script, a witness script or a tapscript
+    ///  * flags - the script verification rules to enforce
+    pub fn trace<'a, C: Signing + Verification, T: Borrow<TxOut>>(
+        &'a self,
+        secp: &'a Secp256k1<C>,
+        spending: &'a Transaction,
+        index: usize,
+        prevouts: &'a Prevouts<'a, T>,
+        sigversion: SigVersion,
+        flags: VerifyFlags,
+    ) -> Result<interpreter::Trace<'a, C, T>, Error> {
+        interpreter::Trace::new(secp, self, spending, index, prevouts, sigversion, flags)
+    }
+
+    /// Write the assembly decoding of the script bytes to the formatter.
+    pub fn bytes_to_asm_fmt(script: &[u8], f: &mut dyn fmt::Write) -> fmt::Result {
+        let mut index = 0;
+        while index < script.len() {
+            let opcode = opcodes::All::from(script[index]);
+            index += 1;
+
+            let data_len = if let opcodes::Class::PushBytes(n) = opcode.classify() {
+                n as usize
+            } else {
+                match opcode {
+                    opcodes::all::OP_PUSHDATA1 => {
+                        if script.len() < index + 1 {
+                            f.write_str("<unexpected end>")?;
+                            break;
+                        }
+                        match read_uint(&script[index..], 1) {
+                            Ok(n) => { index += 1; n as usize }
+                            Err(_) => { f.write_str("<bad length>")?; break; }
+                        }
+                    }
+                    opcodes::all::OP_PUSHDATA2 => {
+                        if script.len() < index + 2 {
+                            f.write_str("<unexpected end>")?;
+                            break;
+                        }
+                        match read_uint(&script[index..], 2) {
+                            Ok(n) => { index += 2; n as usize }
+                            Err(_) => { f.write_str("<bad length>")?; break; }
+                        }
+                    }
+                    opcodes::all::OP_PUSHDATA4 => {
+                        if script.len() < index + 4 {
+                            f.write_str("<unexpected end>")?;
+                            break;
+                        }
+                        match read_uint(&script[index..], 4) {
+                            Ok(n) => { index += 4; n as usize }
+                            Err(_) => { f.write_str("<bad length>")?; break; }
+                        }
+                    }
+                    _ => 0
+                }
+            };
+
+            if index > 1 { f.write_str(" ")?; }
+            // Write the opcode
+            if opcode == opcodes::all::OP_PUSHBYTES_0 {
+                f.write_str("OP_0")?;
+            } else {
+                write!(f, "{:?}", opcode)?;
+            }
+            // Write any pushdata
+            if data_len > 0 {
+                f.write_str(" ")?;
+                if index + data_len <= script.len() {
+                    for ch in &script[index..index + data_len] {
+                            write!(f, "{:02x}", ch)?;
+                    }
+                    index += data_len;
+                } else {
+                    f.write_str("<push past end>")?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the assembly decoding of the script to the formatter.
+    pub fn fmt_asm(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        Script::bytes_to_asm_fmt(self.as_ref(), f)
+    }
+
+    /// Create an assembly decoding of the script in the given byte slice.
+    pub fn bytes_to_asm(script: &[u8]) -> String {
+        let mut buf = String::new();
+        Script::bytes_to_asm_fmt(script, &mut buf).unwrap();
+        buf
+    }
+
+    /// Get the assembly decoding of the script.
+    pub fn asm(&self) -> String {
+        Script::bytes_to_asm(self.as_ref())
+    }
+
+    /// Parse a script from its assembly representation, the inverse of
+    /// [Script::asm].
+    ///
+    /// The string is a whitespace separated list of
+    ///  * opcode names, with or without the `OP_` prefix, including the `OP_0`
+    ///    to `OP_16`, `OP_1NEGATE`, `FALSE`, `TRUE`, `CHECKLOCKTIMEVERIFY` and
+    ///    `CHECKSEQUENCEVERIFY` aliases
+    ///  * `OP_PUSHBYTES_n`, `OP_PUSHDATA1`, `OP_PUSHDATA2` or `OP_PUSHDATA4`
+    ///    followed by the hex data they push, which is encoded as given
+    ///  * decimal numbers, pushed as script numbers like [Builder::push_int] does
+    ///  * `0x`-prefixed hex data, pushed with the smallest push opcode
+    pub fn from_asm(asm: &str) -> Result<Script, AsmError> {
+        let names = opcode_names();
+        let mut bytes = vec![];
+        let mut words = AsmWords { asm: asm, pos: 0 };
+        while let Some((pos, word)) = words.next() {
+            if is_decimal(word) {
+                match word.parse::<i64>() {
+                    Ok(n) if n != i64::min_value() => bytes.extend(&Builder::new().push_int(n).0),
+                    _ => return Err(AsmError::NumberOutOfRange(pos)),
+                }
+                continue;
+            }
+            if word.starts_with("0x") {
+                let data = Vec::<u8>::from_hex(&word[2..]).map_err(|e| AsmError::InvalidHex(pos, e))?;
+                bytes.extend(&Builder::new().push_slice(&data).0);
+                continue;
+            }
+            let name = if word.starts_with("OP_") { &word[3..] } else { word };
+            let opcode = match names.get(name) {
+                Some(code) => opcodes::All::from(*code),
+                None => return Err(AsmError::UnknownOpcode(pos, word.to_owned())),
+            };
+            let max_len = match opcode.classify() {
+                opcodes::Class::PushBytes(0) => None,
+                opcodes::Class::PushBytes(n) => Some(n as usize),
+                _ => match opcode {
+                    opcodes::all::OP_PUSHDATA1 => Some(0xff),
+                    opcodes::all::OP_PUSHDATA2 => Some(0xffff),
+                    opcodes::all::OP_PUSHDATA4 => Some(0xffffffff),
+                    _ => None,
+                },
+            };
+            bytes.push(opcode.into_u8());
+            if let Some(max_len) = max_len {
+                let (data_pos, data) = words.next().ok_or(AsmError::MissingPushData(pos))?;
+                let data = if data.starts_with("0x") { &data[2..] } else { data };
+                let data = Vec::<u8>::from_hex(data).map_err(|e| AsmError::InvalidHex(data_pos, e))?;
+                let len_size = match opcode {
+                    opcodes::all::OP_PUSHDATA1 => 1,
+                    opcodes::all::OP_PUSHDATA2 => 2,
+                    opcodes::all::OP_PUSHDATA4 => 4,
+                    _ => 0,
+                };
+                if (len_size == 0 && data.len() != max_len) || data.len() > max_len {
+                    return Err(AsmError::InvalidPushLength(data_pos));
+                }
+                for i in 0..len_size {
+                    bytes.push((data.len() >> (8 * i)) as u8);
+                }
+                bytes.extend(data);
+            }
+        }
+        Ok(Script::from(bytes))
+    }
+}
+
+/// Counts the signature operations of a witness program spend
+fn witness_program_sigops(program: &Script, witness: &Witness) -> usize {
+    if program[0] != opcodes::all::OP_PUSHBYTES_0.into_u8() {
+        return 0;
+    }
+    match (program.len() - 2, witness.last()) {
+        (20, _) => 1,
+        (32, Some(witness_script)) => Script::from(witness_script.to_vec()).count_sigops(true),
+        _ => 0,
+    }
+}
+
+/// Iterator over the whitespace separated words of an assembly string and
+/// their byte positions
+struct AsmWords<'a> {
+    asm: &'a str,
+    pos: usize,
+}
+
+impl<'a> Iterator for AsmWords<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let rest = &self.asm[self.pos..];
+        let start = self.pos + rest.find(|c: char| !c.is_whitespace())?;
+        let rest = &self.asm[start..];
+        let end = start + rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.pos = end;
+        Some((start, &self.asm[start..end]))
+    }
+}
+
+/// Whether an assembly word is a decimal number
+fn is_decimal(word: &str) -> bool {
+    let digits = if word.starts_with('-') { &word[1..] } else { word };
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Opcodes by their assembly names without the `OP_` prefix
+fn opcode_names() -> HashMap<String, u8> {
+    let mut names = HashMap::new();
+    for code in 0..=255u8 {
+        let name = format!("{:?}", opcodes::All::from(code));
+        names.insert(name[3..].to_owned(), code);
+    }
+    names.insert("0".to_owned(), opcodes::all::OP_PUSHBYTES_0.into_u8());
+    names.insert("1NEGATE".to_owned(), opcodes::all::OP_PUSHNUM_NEG1.into_u8());
+    for n in 1..17 {
+        names.insert(n.to_string(), opcodes::all::OP_PUSHNUM_1.into_u8() + n - 1);
+    }
+    names.insert("FALSE".to_owned(), opcodes::all::OP_PUSHBYTES_0.into_u8());
+    names.insert("TRUE".to_owned(), opcodes::all::OP_PUSHNUM_1.into_u8());
+    names.insert("CHECKLOCKTIMEVERIFY".to_owned(), opcodes::all::OP_CLTV.into_u8());
+    names.insert("CHECKSEQUENCEVERIFY".to_owned(), opcodes::all::OP_CSV.into_u8());
+    names
+}
+
+/// Creates a new script from an existing vector
+impl From<Vec<u8>> for Script {
+    fn from(v: Vec<u8>) -> Script { Script(v.into_boxed_slice()) }
+}
+
+impl_index_newtype!(Script, u8);
+
+/// A "parsed opcode" which allows iterating over a Script in a more sensible way
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Instruction<'a> {
+    /// Push a bunch of data
+    PushBytes(&'a [u8]),
+    /// Some non-push opcode
+    Op(opcodes::All),
+}
+
+/// Iterator over a script returning parsed opcodes
+pub struct Instructions<'a> {
+    data: &'a [u8],
+    enforce_minimal: bool,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, Error>;
+
+    fn next(&mut self) -> Option<Result<Instruction<'a>, Error>> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        match opcodes::All::from(self.data[0]).classify() {
+            opcodes::Class::PushBytes(n) => {
+                let n = n as usize;
+                if self.data.len() < n + 1 {
+                    self.data = &[];  // Kill iterator so that it does not return an infinite stream of errors
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                if self.enforce_minimal {
+                    if n == 1 && (self.data[1] == 0x81 || (self.data[1] > 0 && self.data[1] <= 16)) {
+                        self.data = &[];
+                        return Some(Err(Error::NonMinimalPush));
+                    }
+                }
+                let ret = Some(Ok(Instruction::PushBytes(&self.data[1..n+1])));
+                self.data = &self.data[n + 1..];
+                ret
+            }
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA1) => {
+                if self.data.len() < 2 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                let n = match read_uint(&self.data[1..], 1) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        self.data = &[];
+                        return Some(Err(e));
+                    }
+                };
+                if self.data.len() < n + 2 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                if self.enforce_minimal && n < 76 {
+                    self.data = &[];
+                    return Some(Err(Error::NonMinimalPush));
+                }
+                let ret = Some(Ok(Instruction::PushBytes(&self.data[2..n+2])));
+                self.data = &self.data[n + 2..];
+                ret
+            }
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA2) => {
+                if self.data.len() < 3 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                let n = match read_uint(&self.data[1..], 2) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        self.data = &[];
+                        return Some(Err(e));
+                    }
+                };
+                if self.enforce_minimal && n < 0x100 {
+                    self.data = &[];
+                    return Some(Err(Error::NonMinimalPush));
+                }
+                if self.data.len() < n + 3 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                let ret = Some(Ok(Instruction::PushBytes(&self.data[3..n + 3])));
+                self.data = &self.data[n + 3..];
+                ret
+            }
+            opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA4) => {
+                if self.data.len() < 5 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                let n = match read_uint(&self.data[1..], 4) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        self.data = &[];
+                        return Some(Err(e));
+                    }
+                };
+                if self.enforce_minimal && n < 0x10000 {
+                    self.data = &[];
+                    return Some(Err(Error::NonMinimalPush));
+                }
+                if self.data.len() < n + 5 {
+                    self.data = &[];
+                    return Some(Err(Error::EarlyEndOfScript));
+                }
+                let ret = Some(Ok(Instruction::PushBytes(&self.data[5..n + 5])));
+                self.data = &self.data[n + 5..];
+                ret
+            }
+            // Everything else we can push right through
+            _ => {
+                let ret = Some(Ok(Instruction::Op(opcodes::All::from(self.data[0]))));
+                self.data = &self.data[1..];
+                ret
+            }
+        }
+    }
+}
+
+/// Iterator over a script returning parsed opcodes with their byte offsets
+pub struct InstructionIndices<'a> {
+    instructions: Instructions<'a>,
+    len: usize,
+}
+
+impl<'a> InstructionIndices<'a> {
+    /// Iterates over the instructions of a script from the byte offset `pos`,
+    /// which may be in the middle of an instruction
+    pub(crate) fn from_slice(script: &'a [u8], pos: usize, enforce_minimal: bool) -> InstructionIndices<'a> {
+        InstructionIndices {
+            instructions: Instructions {
+                data: &script[pos..],
+                enforce_minimal: enforce_minimal,
+            },
+            len: script.len(),
+        }
+    }
+
+    /// The byte offset of the next instruction, which is the length of the
+    /// script once the iterator is exhausted, or after an error
+    pub fn pos(&self) -> usize {
+        self.len - self.instructions.data.len()
+    }
+}
+
+impl<'a> Iterator for InstructionIndices<'a> {
+    type Item = Result<(usize, Instruction<'a>), Error>;
+
+    fn next(&mut self) -> Option<Result<(usize, Instruction<'a>), Error>> {
+        let pos = self.pos();
+        self.instructions.next().map(|instruction| instruction.map(|instruction| (pos, instruction)))
+    }
+}
+
+impl Builder {
+    /// Creates a new empty script
+    pub fn new() -> Self {
+        Builder(vec![], None)
+    }
+
+    /// The length in bytes of the script
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Whether the script is the empty script
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Adds instructions to push an integer onto the stack. Integers are
+    /// encoded as little-endian signed-magnitude numbers, but there are
+    /// dedicated opcodes to push some small integers.
+    pub fn push_int(self, data: i64) -> Builder {
+        // We can special-case -1, 1-16
+        if data == -1 || (data >= 1 && data <= 16) {
+            let opcode = opcodes::All::from(
+                (data - 1 + opcodes::OP_TRUE.into_u8() as i64) as u8
+            );
+            self.push_opcode(opcode)
+        }
+        // We can also special-case zero
+        else if data == 0 {
+            self.push_opcode(opcodes::OP_FALSE)
+        }
+        // Otherwise encode it as data
+        else { self.push_scriptint(data) }
+    }
+
+    /// Adds instructions to push an integer onto the stack, using the explicit
+    /// encoding regardless of the availability of dedicated opcodes.
+    pub fn push_scriptint(self, data: i64) -> Builder {
+        self.push_slice(&build_scriptint(data))
+    }
+
+    /// Adds instructions to push some arbitrary data onto the stack
+    pub fn push_slice(mut self, data: &[u8]) -> Builder {
+        // Start with a PUSH opcode
+        match data.len() as u64 {
+            n if n < opcodes::Ordinary::OP_PUSHDATA1 as u64 => { self.0.push(n as u8); },
+            n if n < 0x100 => {
+                self.0.push(opcodes::Ordinary::OP_PUSHDATA1.into_u8());
+                self.0.push(n as u8);
+            },
+            n if n < 0x10000 => {
+                self.0.push(opcodes::Ordinary::OP_PUSHDATA2.into_u8());
+                self.0.push((n % 0x100) as u8);
+                self.0.push((n / 0x100) as u8);
+            },
+            n if n < 0x100000000 => {
+                self.0.push(opcodes::Ordinary::OP_PUSHDATA4.into_u8());
+                self.0.push((n % 0x100) as u8);
+                self.0.push(((n / 0x100) % 0x100) as u8);
+                self.0.push(((n / 0x10000) % 0x100) as u8);
+                self.0.push((n / 0x1000000) as u8);
+            }
+            _ => panic!("tried to put a 4bn+ sized object into a script!")
+        }
+        // Then push the raw bytes
+        self.0.extend(data.iter().cloned());
+        self.1 = None;
+        self
+    }
+
+    /// Pushes a public key
+    pub fn push_key(self, key: &PublicKey) -> Builder {
+        if key.compressed {
+            self.push_slice(&key.key.serialize()[..])
+        } else {
+            self.push_slice(&key.key.serialize_uncompressed()[..])
+        }
+    }
+
+    /// Adds a single opcode to the script
+    pub fn push_opcode(mut self, data: opcodes::All) -> Builder {
+        self.0.push(data.into_u8());
+        self.1 = Some(data);
+        self
+    }
+
+    /// Adds an `OP_VERIFY` to the script, unless the most-recently-added
+    /// opcode has an alternate `VERIFY` form, in which case that opcode
+    /// is replaced. e.g. `OP_CHECKSIG` will become `OP_CHECKSIGVERIFY`.
+    pub fn push_verify(mut self) -> Builder {
+        match self.1 {
+            Some(opcodes::all::OP_EQUAL) => {
+                self.0.pop();
+                self.push_opcode(opcodes::all::OP_EQUALVERIFY)
+            },
+            Some(opcodes::all::OP_NUMEQUAL) => {
+                self.0.pop();
+                self.push_opcode(opcodes::all::OP_NUMEQUALVERIFY)
+            },
+            Some(opcodes::all::OP_CHECKSIG) => {
+                self.0.pop();
+                self.push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+            },
+            Some(opcodes::all::OP_CHECKMULTISIG) => {
+                self.0.pop();
+                self.push_opcode(opcodes::all::OP_CHECKMULTISIGVERIFY)
+            },
+            _ => self.push_opcode(opcodes::all::OP_VERIFY),
+        }
+    }
+
+    /// Converts the `Builder` into an unmodifiable `Script`
+    pub fn into_script(self) -> Script {
+        Script(self.0.into_boxed_slice())
+    }
+}
+
+/// Adds an individual opcode to the script
+impl Default for Builder {
+    fn default() -> Builder { Builder::new() }
+}
+
+/// Creates a new script from an existing vector
+impl From<Vec<u8>> for Builder {
+    fn from(v: Vec<u8>) -> Builder {
+        let script = Script(v.into_boxed_slice());
+        let last_op = match script.instructions().last() {
+            Some(Ok(Instruction::Op(op))) => Some(op),
+            _ => None,
+        };
+        Builder(script.into_bytes(), last_op)
+    }
+}
+
+impl_index_newtype!(Builder, u8);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Script {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use std::fmt::Formatter;
+        use hashes::hex::FromHex;
+
+        struct Visitor;
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Script;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a script")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let v = Vec::from_hex(v).map_err(E::custom)?;
+                Ok(Script::from(v))
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_str(v)
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_str(&v)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Script {
+    /// User-facing serialization for `Script`.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&format!("{:x}", self))
+    }
+}
+
+// Network serialization
+impl Encodable for Script {
+    #[inline]
+    fn consensus_encode<S: io::Write>(
+        &self,
+        s: S,
+    ) -> Result<usize, io::Error> {
+        self.0.consensus_encode(s)
+    }
+}
+
+impl Decodable for Script {
+    #[inline]
+    fn consensus_decode<D: io::Read>(d: D) -> Result<Self, encode::Error> {
+        Ok(Script(Decodable::consensus_decode(d)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use super::build_scriptint;
+
+    use hashes::hex::{FromHex, ToHex};
+    use consensus::encode::{deserialize, serialize};
+    use blockdata::opcodes;
+    use util::ecdsa::PublicKey;
+    use util::psbt::serialize::Serialize;
+
+    #[test]
+    fn script() {
+        let mut comp = vec![];
+        let mut script = Builder::new();
+        assert_eq!(&script[..], &comp[..]);
+
+        // small ints
+        script = script.push_int(1);  comp.push(81u8); assert_eq!(&script[..], &comp[..]);
+        script = script.push_int(0);  comp.push(0u8);  assert_eq!(&script[..], &comp[..]);
+        script = script.push_int(4);  comp.push(84u8); assert_eq!(&script[..], &comp[..]);
+        script = script.push_int(-1); comp.push(79u8); assert_eq!(&script[..], &comp[..]);
+        // forced scriptint
+        script = script.push_scriptint(4); comp.extend([1u8, 4].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+        // big ints
+        script = script.push_int(17); comp.extend([1u8, 17].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+        script = script.push_int(10000); comp.extend([2u8, 16, 39].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+        // notice the sign bit set here, hence the extra zero/128 at the end
+        script = script.push_int(10000000); comp.extend([4u8, 128, 150, 152, 0].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+        script = script.push_int(-10000000); comp.extend([4u8, 128, 150, 152, 128].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+
+        // data
+        script = script.push_slice("NRA4VR".as_bytes()); comp.extend([6u8, 78, 82, 65, 52, 86, 82].iter().cloned()); assert_eq!(&script[..], &comp[..]);
+
+        // keys
+        let keystr = "21032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af";
+        let key = PublicKey::from_str(&keystr[2..]).unwrap();
+        script = script.push_key(&key); comp.extend(Vec::from_hex(keystr).unwrap().iter().cloned()); assert_eq!(&script[..], &comp[..]);
+        let keystr = "41042e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af191923a2964c177f5b5923ae500fca49e99492d534aa3759d6b25a8bc971b133";
+        let key = PublicKey::from_str(&keystr[2..]).unwrap();
+        script = script.push_key(&key); comp.extend(Vec::from_hex(keystr).unwrap().iter().cloned()); assert_eq!(&script[..], &comp[..]);
+
+        // opcodes
+        script = script.push_opcode(opcodes::all::OP_CHECKSIG); comp.push(0xACu8); assert_eq!(&script[..], &comp[..]);
+        script = script.push_opcode(opcodes::all::OP_CHECKSIG); comp.push(0xACu8); assert_eq!(&script[..], &comp[..]);
+    }
+
+    #[test]
+    fn script_builder() {
+        // from txid 3bb5e6434c11fb93f64574af5d116736510717f2c595eb45b52c28e31622dfff which was in my mempool when I wrote the test
+        let script = Builder::new().push_opcode(opcodes::all::OP_DUP)
+                                   .push_opcode(opcodes::all::OP_HASH160)
+                                   .push_slice(&Vec::from_hex("16e1ae70ff0fa102905d4af297f6912bda6cce19").unwrap())
+                                   .push_opcode(opcodes::all::OP_EQUALVERIFY)
+                                   .push_opcode(opcodes::all::OP_CHECKSIG)
+                                   .into_script();
+        assert_eq!(&format!("{:x}", script), "76a91416e1ae70ff0fa102905d4af297f6912bda6cce1988ac");
+    }
+
+    #[test]
+    fn script_generators() {
+        let pubkey = PublicKey::from_str("0234e6a79c5359c613762d537e0e19d86c77c1666d8c9ab050f23acd198e97f93e").unwrap();
+        assert!(Script::new_p2pk(&pubkey).is_p2pk());
+
+        let pubkey_hash = PubkeyHash::hash(&pubkey.serialize());
+        assert!(Script::new_p2pkh(&pubkey_hash).is_p2pkh());
+
+        let wpubkey_hash = WPubkeyHash::hash(&pubkey.serialize());
+        assert!(Script::new_v0_wpkh(&wpubkey_hash).is_v0_p2wpkh());
+
+        let script = Builder::new().push_opcode(opcodes::all::OP_NUMEQUAL)
+                                   .push_verify()
+                                   .into_script();
+        let script_hash = ScriptHash::hash(&script.serialize());
+        let p2sh = Script::new_p2sh(&script_hash);
+        assert!(p2sh.is_p2sh());
+        assert_eq!(script.to_p2sh(), p2sh);
+
+        let wscript_hash = WScriptHash::hash(&script.serialize());
+        let p2wsh = Script::new_v0_wsh(&wscript_hash);
+        assert!(p2wsh.is_v0_p2wsh());
+        assert_eq!(script.to_v0_p2wsh(), p2wsh);
+
+        // BIP341 scriptPubKey test vector
+        let secp = Secp256k1::verification_only();
+        let internal_key = ::util::schnorr::PublicKey::from_str("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap();
+        let merkle_root = TapBranchHash::from_slice(&Vec::<u8>::from_hex("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21").unwrap()).unwrap();
+        let p2tr = Script::new_v1_p2tr(&secp, internal_key, Some(merkle_root));
+        assert!(p2tr.is_v1_p2tr());
+        assert!(p2tr.is_witness_program());
+        assert!(!p2wsh.is_v1_p2tr());
+        assert_eq!(p2tr.to_hex(), "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+
+        // Test data are taken from the second output of
+        // 2ccb3a1f745eb4eefcf29391460250adda5fab78aaddb902d25d3cd97d9d8e61 transaction
+        let data = Vec::<u8>::from_hex("aa21a9ed20280f53f2d21663cac89e6bd2ad19edbabb048cda08e73ed19e9268d0afea2a").unwrap();
+        let op_return = Script::new_op_return(&data);
+        assert!(op_return.is_op_return());
+        assert_eq!(op_return.to_hex(), "6a24aa21a9ed20280f53f2d21663cac89e6bd2ad19edbabb048cda08e73ed19e9268d0afea2a");
+    }
+
+    #[test]
+    fn script_builder_verify() {
+        let simple = Builder::new()
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", simple), "69");
+        let simple2 = Builder::from(vec![])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", simple2), "69");
+
+        let nonverify = Builder::new()
+            .push_verify()
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", nonverify), "6969");
+        let nonverify2 = Builder::from(vec![0x69])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", nonverify2), "6969");
+
+        let equal = Builder::new()
+            .push_opcode(opcodes::all::OP_EQUAL)
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", equal), "88");
+        let equal2 = Builder::from(vec![0x87])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", equal2), "88");
+
+        let numequal = Builder::new()
+            .push_opcode(opcodes::all::OP_NUMEQUAL)
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", numequal), "9d");
+        let numequal2 = Builder::from(vec![0x9c])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", numequal2), "9d");
+
+        let checksig = Builder::new()
+            .push_opcode(opcodes::all::OP_CHECKSIG)
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", checksig), "ad");
+        let checksig2 = Builder::from(vec![0xac])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", checksig2), "ad");
+
+        let checkmultisig = Builder::new()
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", checkmultisig), "af");
+        let checkmultisig2 = Builder::from(vec![0xae])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", checkmultisig2), "af");
+
+        let trick_slice = Builder::new()
+            .push_slice(&[0xae]) // OP_CHECKMULTISIG
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", trick_slice), "01ae69");
+        let trick_slice2 = Builder::from(vec![0x01, 0xae])
+            .push_verify()
+            .into_script();
+        assert_eq!(format!("{:x}", trick_slice2), "01ae69");
+   }
+
+    #[test]
+    fn script_serialize() {
+        let hex_script = Vec::from_hex("6c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52").unwrap();
+        let script: Result<Script, _> = deserialize(&hex_script);
+        assert!(script.is_ok());
+        assert_eq!(serialize(&script.unwrap()), hex_script);
+    }
+
+    #[test]
+    fn scriptint_round_trip() {
+        assert_eq!(build_scriptint(-1), vec![0x81]);
+        assert_eq!(build_scriptint(255), vec![255, 0]);
+        assert_eq!(build_scriptint(256), vec![0, 1]);
+        assert_eq!(build_scriptint(257), vec![1, 1]);
+        assert_eq!(build_scriptint(511), vec![255, 1]);
+        for &i in [10, 100, 255, 256, 1000, 10000, 25000, 200000, 5000000, 1000000000,
+                             (1 << 31) - 1, -((1 << 31) - 1)].iter() {
+            assert_eq!(Ok(i), read_scriptint(&build_scriptint(i)));
+            assert_eq!(Ok(-i), read_scriptint(&build_scriptint(-i)));
+        }
+        assert!(read_scriptint(&build_scriptint(1 << 31)).is_err());
+        assert!(read_scriptint(&build_scriptint(-(1 << 31))).is_err());
+    }
+
+    #[test]
+    fn script_hashes() {
+        let script = hex_script!("410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5ac");
+        assert_eq!(script.script_hash().to_hex(), "8292bcfbef1884f73c813dfe9c82fd7e814291ea");
+        assert_eq!(script.wscript_hash().to_hex(), "3e1525eb183ad4f9b3c5fa3175bdca2a52e947b135bbb90383bf9f6408e2c324");
+    }
+
+    #[test]
+    fn provably_unspendable_test() {
+        // p2pk
+        assert_eq!(hex_script!("410446ef0102d1ec5240f0d061a4246c1bdef63fc3dbab7733052fbbf0ecd8f41fc26bf049ebb4f9527f374280259e7cfa99c48b0e3f39c51347a19a5819651503a5ac").is_provably_unspendable(), false);
+        assert_eq!(hex_script!("4104ea1feff861b51fe3f5f8a3b12d0f4712db80e919548a80839fc47c6a21e66d957e9c5d8cd108c7a2d2324bad71f9904ac0ae7336507d785b17a2c115e427a32fac").is_provably_unspendable(), false);
+        // p2pkhash
+        assert_eq!(hex_script!("76a914ee61d57ab51b9d212335b1dba62794ac20d2bcf988ac").is_provably_unspendable(), false);
+        assert_eq!(hex_script!("6aa9149eb21980dc9d413d8eac27314938b9da920ee53e87").is_provably_unspendable(), true);
+    }
+
+    #[test]
+    fn op_return_test() {
+        assert_eq!(hex_script!("6aa9149eb21980dc9d413d8eac27314938b9da920ee53e87").is_op_return(), true);
+        assert_eq!(hex_script!("76a914ee61d57ab51b9d212335b1dba62794ac20d2bcf988ac").is_op_return(), false);
+        assert_eq!(hex_script!("").is_op_return(), false);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn script_json_serialize() {
+        use serde_json;
+
+        let original = hex_script!("827651a0698faaa9a8a7a687");
+        let json = serde_json::to_value(&original).unwrap();
+        assert_eq!(json, serde_json::Value::String("827651a0698faaa9a8a7a687".to_owned()));
+        let des = serde_json::from_value(json).unwrap();
+        assert_eq!(original, des);
+    }
+
+    #[test]
+    fn script_asm() {
+        assert_eq!(hex_script!("6363636363686868686800").asm(),
+                   "OP_IF OP_IF OP_IF OP_IF OP_IF OP_ENDIF OP_ENDIF OP_ENDIF OP_ENDIF OP_ENDIF OP_0");
+        assert_eq!(hex_script!("6363636363686868686800").asm(),
+                   "OP_IF OP_IF OP_IF OP_IF OP_IF OP_ENDIF OP_ENDIF OP_ENDIF OP_ENDIF OP_ENDIF OP_0");
+        assert_eq!(hex_script!("2102715e91d37d239dea832f1460e91e368115d8ca6cc23a7da966795abad9e3b699ac").asm(),
+                   "OP_PUSHBYTES_33 02715e91d37d239dea832f1460e91e368115d8ca6cc23a7da966795abad9e3b699 OP_CHECKSIG");
+        // Elements Alpha peg-out transaction with some signatures removed for brevity. Mainly to test PUSHDATA1
+        assert_eq!(hex_script!("0047304402202457e78cc1b7f50d0543863c27de75d07982bde8359b9e3316adec0aec165f2f02200203fd331c4e4a4a02f48cf1c291e2c0d6b2f7078a784b5b3649fca41f8794d401004cf1552103244e602b46755f24327142a0517288cebd159eccb6ccf41ea6edf1f601e9af952103bbbacc302d19d29dbfa62d23f37944ae19853cf260c745c2bea739c95328fcb721039227e83246bd51140fe93538b2301c9048be82ef2fb3c7fc5d78426ed6f609ad210229bf310c379b90033e2ecb07f77ecf9b8d59acb623ab7be25a0caed539e2e6472103703e2ed676936f10b3ce9149fa2d4a32060fb86fa9a70a4efe3f21d7ab90611921031e9b7c6022400a6bb0424bbcde14cff6c016b91ee3803926f3440abf5c146d05210334667f975f55a8455d515a2ef1c94fdfa3315f12319a14515d2a13d82831f62f57ae").asm(),
+                   "OP_0 OP_PUSHBYTES_71 304402202457e78cc1b7f50d0543863c27de75d07982bde8359b9e3316adec0aec165f2f02200203fd331c4e4a4a02f48cf1c291e2c0d6b2f7078a784b5b3649fca41f8794d401 OP_0 OP_PUSHDATA1 552103244e602b46755f24327142a0517288cebd159eccb6ccf41ea6edf1f601e9af952103bbbacc302d19d29dbfa62d23f37944ae19853cf260c745c2bea739c95328fcb721039227e83246bd51140fe93538b2301c9048be82ef2fb3c7fc5d78426ed6f609ad210229bf310c379b90033e2ecb07f77ecf9b8d59acb623ab7be25a0caed539e2e6472103703e2ed676936f10b3ce9149fa2d4a32060fb86fa9a70a4efe3f21d7ab90611921031e9b7c6022400a6bb0424bbcde14cff6c016b91ee3803926f3440abf5c146d05210334667f975f55a8455d515a2ef1c94fdfa3315f12319a14515d2a13d82831f62f57ae");
+        // 2-of-2 tapscript multisig
+        assert_eq!(hex_script!("20a4fbdf1ee46b4d10e5c9e3fcd1b1e8ae4b92c5d78c2e1d3a9d3c19e6b0f6bd1eac20c5d4a1c3e2b7d1f5e9a2c4b8d7e6f1a3c5b9d2e8f4a6c1b3d5e7f9a2c4b6d8f0ba529c").asm(),
+                   "OP_PUSHBYTES_32 a4fbdf1ee46b4d10e5c9e3fcd1b1e8ae4b92c5d78c2e1d3a9d3c19e6b0f6bd1e OP_CHECKSIG OP_PUSHBYTES_32 c5d4a1c3e2b7d1f5e9a2c4b8d7e6f1a3c5b9d2e8f4a6c1b3d5e7f9a2c4b6d8f0 OP_CHECKSIGADD OP_PUSHNUM_2 OP_NUMEQUAL");
+    }
+
+    #[test]
+    fn script_from_asm() {
+        let scripts = [
+            "6363636363686868686800",
+            "2102715e91d37d239dea832f1460e91e368115d8ca6cc23a7da966795abad9e3b699ac",
+            "0047304402202457e78cc1b7f50d0543863c27de75d07982bde8359b9e3316adec0aec165f2f02200203fd331c4e4a4a02f48cf1c291e2c0d6b2f7078a784b5b3649fca41f8794d401004cf1552103244e602b46755f24327142a0517288cebd159eccb6ccf41ea6edf1f601e9af952103bbbacc302d19d29dbfa62d23f37944ae19853cf260c745c2bea739c95328fcb721039227e83246bd51140fe93538b2301c9048be82ef2fb3c7fc5d78426ed6f609ad210229bf310c379b90033e2ecb07f77ecf9b8d59acb623ab7be25a0caed539e2e6472103703e2ed676936f10b3ce9149fa2d4a32060fb86fa9a70a4efe3f21d7ab90611921031e9b7c6022400a6bb0424bbcde14cff6c016b91ee3803926f3440abf5c146d05210334667f975f55a8455d515a2ef1c94fdfa3315f12319a14515d2a13d82831f62f57ae",
+            // Non-minimal pushes, OP_EQUAL OP_VERIFY and opcodes without a name
+            "4c01ff4d0200abcd4e0100000011879d6900bbff",
+        ];
+        for script in &scripts {
+            let script = hex_script!(script);
+            assert_eq!(Script::from_asm(&script.asm()), Ok(script));
+        }
+        // Every opcode round trips
+        let all = Script::from((0..=255u8).filter(|code| *code == 0 || *code > 0x4e).collect::<Vec<_>>());
+        assert_eq!(Script::from_asm(&all.asm()), Ok(all));
+
+        assert_eq!(
+            Script::from_asm("DUP OP_HASH160 0x02306a7c23f3e8010de41e9e591348bb83f11daa EQUALVERIFY\tCHECKSIG"),
+            Ok(hex_script!("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac"))
+        );
+        assert_eq!(
+            Script::from_asm(" 0 -1 1 16 17 -129 1000 CHECKLOCKTIMEVERIFY\n"),
+            Ok(hex_script!("004f5160011102818002e803b1"))
+        );
+        assert_eq!(Script::from_asm("OP_TRUE FALSE OP_PUSHBYTES_0"), Ok(hex_script!("510000")));
+        assert_eq!(Script::from_asm("OP_PUSHDATA2 0xabcd"), Ok(hex_script!("4d0200abcd")));
+        assert_eq!(Script::from_asm(""), Ok(Script::new()));
+
+        assert_eq!(
+            Script::from_asm("OP_DUP OP_FOO"),
+            Err(AsmError::UnknownOpcode(7, "OP_FOO".to_owned()))
+        );
+        assert_eq!(Script::from_asm("OP_1 OP_PUSHDATA1"), Err(AsmError::MissingPushData(5)));
+        assert_eq!(
+            Script::from_asm("OP_PUSHBYTES_1 0xag"),
+            Err(AsmError::InvalidHex(15, hex::Error::InvalidChar(b'g')))
+        );
+        assert_eq!(Script::from_asm("OP_PUSHBYTES_2 ab"), Err(AsmError::InvalidPushLength(15)));
+        assert_eq!(Script::from_asm(&("OP_PUSHDATA1 ".to_owned() + &"ab".repeat(256))), Err(AsmError::InvalidPushLength(13)));
+        assert_eq!(Script::from_asm("-9223372036854775808"), Err(AsmError::NumberOutOfRange(0)));
+        assert_eq!(
+            Script::from_asm("OP_DUP OP_FOO").unwrap_err().to_string(),
+            "unknown opcode `OP_FOO` at position 7"
+        );
+    }
+
+    #[test]
+    fn script_multisig() {
+        let keys = [
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "0202313ca315889b2e69c94cf86901119321c7288139ba53ac022b7af3dc250054",
+            "04ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84c",
+        ].iter().map(|key| PublicKey::from_str(key).unwrap()).collect::<Vec<_>>();
+
+        let script = Script::new_multisig(2, &keys).unwrap();
+        assert_eq!(
+            script.asm(),
+            format!("OP_PUSHNUM_2 OP_PUSHBYTES_33 {} OP_PUSHBYTES_33 {} OP_PUSHBYTES_65 {} OP_PUSHNUM_3 OP_CHECKMULTISIG", keys[0], keys[1], keys[2])
+        );
+        assert_eq!(script.parse_multisig(), Ok((2, keys.clone())));
+
+        let sorted = Script::new_sorted_multisig(1, &keys).unwrap();
+        assert_eq!(sorted.parse_multisig(), Ok((1, vec![keys[1], keys[0], keys[2]])));
+        // BIP67 test vector 1
+        let bip67 = [
+            "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f",
+            "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8",
+        ].iter().map(|key| PublicKey::from_str(key).unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            Script::new_sorted_multisig(2, &[bip67[1], bip67[0]]).unwrap(),
+            hex_script!("522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae")
+        );
+
+        // Counts above 16 are pushed as numbers
+        let many = vec![keys[0]; 20];
+        let script = Script::new_multisig(17, &many).unwrap();
+        assert_eq!(&script[..2], &[0x01, 17]);
+        assert_eq!(script.parse_multisig(), Ok((17, many.clone())));
+        assert_eq!(script.classify(), ScriptType::NonStandard);
+
+        assert_eq!(Script::new_multisig(1, &vec![keys[0]; 21]), Err(Error::PubkeyCount));
+        assert_eq!(Script::new_multisig(0, &keys), Err(Error::SigCount));
+        assert_eq!(Script::new_multisig(4, &keys), Err(Error::SigCount));
+        assert_eq!(Script::new_multisig(1, &[]), Err(Error::SigCount));
+
+        let parse = |asm: &str| Script::from_asm(asm).unwrap().parse_multisig();
+        let key = format!("0x{}", keys[0]);
+        assert_eq!(parse(&format!("1 {} 1 CHECKMULTISIG", key)), Ok((1, vec![keys[0]])));
+        assert_eq!(parse(&format!("0 {} 1 CHECKMULTISIG", key)), Err(Error::SigCount));
+        assert_eq!(parse(&format!("2 {} 1 CHECKMULTISIG", key)), Err(Error::SigCount));
+        assert_eq!(parse(&format!("1 {} 2 CHECKMULTISIG", key)), Err(Error::NotMultisig));
+        assert_eq!(parse(&format!("1 {} 1 CHECKMULTISIGVERIFY", key)), Err(Error::NotMultisig));
+        assert_eq!(parse(&format!("1 {} 1 CHECKMULTISIG NOP", key)), Err(Error::NotMultisig));
+        assert_eq!(parse(&format!("PUSHBYTES_1 01 {} 1 CHECKMULTISIG", key)), Err(Error::NotMultisig));
+        let invalid_key = format!("0x02{}", "00".repeat(32));
+        assert_eq!(parse(&format!("1 {} 1 CHECKMULTISIG", invalid_key)), Err(Error::PubkeyType));
+        let too_many = format!("1 {} 21 CHECKMULTISIG", vec![key; 21].join(" "));
+        assert_eq!(parse(&too_many), Err(Error::PubkeyCount));
+    }
+
+    #[test]
+    fn script_sigops() {
+        let multisig = |asm: &str| Script::from_asm(asm).unwrap();
+        assert_eq!(Script::new().count_sigops(true), 0);
+        assert_eq!(multisig("CHECKSIG CHECKSIGVERIFY CHECKSIGADD").count_sigops(false), 2);
+        assert_eq!(multisig("2 0x02 0x03 3 CHECKMULTISIG").count_sigops(true), 3);
+        assert_eq!(multisig("2 0x02 0x03 3 CHECKMULTISIG").count_sigops(false), 20);
+        assert_eq!(multisig("0x0203 CHECKMULTISIGVERIFY").count_sigops(true), 20);
+        assert_eq!(multisig("0 CHECKMULTISIG").count_sigops(true), 20);
+        // Counting stops at undecodable instructions
+        assert_eq!(hex_script!("acac4c").count_sigops(true), 2);
+        assert_eq!(hex_script!("ac4c01").count_sigops(true), 1);
+
+        let redeem_script = multisig("1 0x02 0x03 0x04 3 CHECKMULTISIG CHECKSIG");
+        let p2sh = redeem_script.to_p2sh();
+        let script_sig = Builder::new().push_int(0).push_slice(&[1; 72]).push_slice(&redeem_script[..]).into_script();
+        assert_eq!(p2sh.count_p2sh_sigops(&script_sig), 4);
+        assert_eq!(redeem_script.count_p2sh_sigops(&script_sig), 0);
+        assert_eq!(p2sh.count_p2sh_sigops(&multisig("0x01 NOP")), 0);
+        assert_eq!(p2sh.count_p2sh_sigops(&Script::new()), 0);
+        assert_eq!(p2sh.count_witness_sigops(&script_sig, &Witness::default()), 0);
+
+        let witness_script = multisig("CHECKSIG CHECKSIG 2 0x02 0x03 2 CHECKMULTISIG");
+        let witness = Witness::from_vec(vec![vec![], witness_script.to_bytes()]);
+        let p2wsh = witness_script.to_v0_p2wsh();
+        assert_eq!(p2wsh.count_witness_sigops(&Script::new(), &witness), 4);
+        assert_eq!(p2wsh.count_witness_sigops(&Script::new(), &Witness::default()), 0);
+        assert_eq!(p2wsh.count_sigops(true), 0);
+        let nested = Builder::new().push_slice(&p2wsh[..]).into_script();
+        assert_eq!(p2wsh.to_p2sh().count_witness_sigops(&nested, &witness), 4);
+        assert_eq!(p2wsh.to_p2sh().count_p2sh_sigops(&nested), 0);
+
+        let p2wpkh = Script::new_v0_wpkh(&WPubkeyHash::hash(&[]));
+        assert_eq!(p2wpkh.count_witness_sigops(&Script::new(), &Witness::default()), 1);
+        let p2tr = hex_script!("5120d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        assert_eq!(p2tr.count_witness_sigops(&Script::new(), &witness), 0);
+    }
+
+    #[test]
+    fn script_classify() {
+        let pk = PublicKey::from_str("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        let pk2 = PublicKey::from_str("0202313ca315889b2e69c94cf86901119321c7288139ba53ac022b7af3dc250054").unwrap();
+        let xonly = "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d";
+
+        assert_eq!(Script::new_p2pk(&pk).classify(), ScriptType::P2pk(pk));
+        assert_eq!(Script::new_p2pkh(&pk.pubkey_hash()).classify(), ScriptType::P2pkh(pk.pubkey_hash()));
+        let script_hash = Script::new().script_hash();
+        assert_eq!(Script::new_p2sh(&script_hash).classify(), ScriptType::P2sh(script_hash));
+        let wpubkey_hash = pk.wpubkey_hash().unwrap();
+        assert_eq!(Script::new_v0_wpkh(&wpubkey_hash).classify(), ScriptType::P2wpkh(wpubkey_hash));
+        let wscript_hash = Script::new().wscript_hash();
+        assert_eq!(Script::new_v0_wsh(&wscript_hash).classify(), ScriptType::P2wsh(wscript_hash));
+        let output_key = TweakedPublicKey::dangerous_assume_tweaked(schnorrsig::PublicKey::from_str(xonly).unwrap());
+        assert_eq!(Script::new_v1_p2tr_tweaked(output_key).classify(), ScriptType::P2tr(output_key));
+
+        let multisig = Builder::new()
+            .push_int(1).push_key(&pk).push_key(&pk2).push_int(2)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        assert_eq!(multisig.classify(), ScriptType::Multisig { required: 1, keys: vec![pk, pk2] });
+        // More required signatures than keys, wrong key count, trailing opcode
+        assert_eq!(Script::from_asm(&format!("2 0x{} 1 CHECKMULTISIG", pk)).unwrap().classify(), ScriptType::NonStandard);
+        assert_eq!(Script::from_asm(&format!("1 0x{} 2 CHECKMULTISIG", pk)).unwrap().classify(), ScriptType::NonStandard);
+        // Keys pushed with OP_PUSHDATA1 are still recognized
+        let mut pushdata = vec![opcodes::all::OP_PUSHNUM_1.into_u8(), opcodes::all::OP_PUSHDATA1.into_u8()];
+        pushdata.push(pk.to_bytes().len() as u8);
+        pushdata.extend(pk.to_bytes());
+        pushdata.extend(&[opcodes::all::OP_PUSHNUM_1.into_u8(), opcodes::all::OP_CHECKMULTISIG.into_u8()]);
+        let pushdata = Script::from(pushdata);
+        assert_eq!(pushdata.parse_multisig(), Err(Error::NotMultisig));
+        assert_eq!(pushdata.classify(), ScriptType::Multisig { required: 1, keys: vec![pk] });
+        let mut trailing = multisig.to_bytes();
+        trailing.push(opcodes::all::OP_NOP.into_u8());
+        assert_eq!(Script::from(trailing).classify(), ScriptType::NonStandard);
+
+        assert_eq!(hex_script!("6a").classify(), ScriptType::NullData);
+        assert_eq!(hex_script!("6a0401020304510050").classify(), ScriptType::NullData);
+        assert_eq!(hex_script!("6a0401020304ac").classify(), ScriptType::NonStandard);
+        assert_eq!(hex_script!("6a4c").classify(), ScriptType::NonStandard);
+
+        assert_eq!(
+            hex_script!("52020102").classify(),
+            ScriptType::WitnessUnknown { version: ::bech32::u5::try_from_u8(2).unwrap(), program: vec![1, 2] }
+        );
+        assert_eq!(
+            hex_script!("5102abcd").classify(),
+            ScriptType::WitnessUnknown { version: ::bech32::u5::try_from_u8(1).unwrap(), program: vec![0xab, 0xcd] }
+        );
+        assert_eq!(hex_script!("0002abcd").classify(), ScriptType::NonStandard);
+        // Not a curve point
+        assert_eq!(hex_script!("5120ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").classify(), ScriptType::NonStandard);
+        assert_eq!(hex_script!("").classify(), ScriptType::NonStandard);
+        assert_eq!(hex_script!("76a914").classify(), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn script_p2sh_p2p2k_template() {
+        // random outputs I picked out of the mempool
+        assert!(hex_script!("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac").is_p2pkh());
+        assert!(!hex_script!("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ac").is_p2sh());
+        assert!(!hex_script!("76a91402306a7c23f3e8010de41e9e591348bb83f11daa88ad").is_p2pkh());
+        assert!(!hex_script!("").is_p2pkh());
+        assert!(hex_script!("a914acc91e6fef5c7f24e5c8b3f11a664aa8f1352ffd87").is_p2sh());
+        assert!(!hex_script!("a914acc91e6fef5c7f24e5c8b3f11a664aa8f1352ffd87").is_p2pkh());
+        assert!(!hex_script!("a314acc91e6fef5c7f24e5c8b3f11a664aa8f1352ffd87").is_p2sh());
+    }
+
+    #[test]
+    fn script_p2pk() {
+        assert!(hex_script!("21021aeaf2f8638a129a3156fbe7e5ef635226b0bafd495ff03afe2c843d7e3a4b51ac").is_p2pk());
+        assert!(hex_script!("410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac").is_p2pk());
+    }
+
+    #[test]
+    fn p2sh_p2wsh_conversion() {
+        // Test vectors taken from Core tests/data/script_tests.json
+        // bare p2wsh
+        let redeem_script = hex_script!("410479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8ac");
+        let expected_witout = hex_script!("0020b95237b48faaa69eb078e1170be3b5cbb3fddf16d0a991e14ad274f7b33a4f64");
+        assert!(redeem_script.to_v0_p2wsh().is_v0_p2wsh());
+        assert_eq!(redeem_script.to_v0_p2wsh(), expected_witout);
+
+        // p2sh
+        let redeem_script = hex_script!("0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8");
+        let expected_p2shout = hex_script!("a91491b24bf9f5288532960ac687abb035127b1d28a587");
+        assert!(redeem_script.to_p2sh().is_p2sh());
+        assert_eq!(redeem_script.to_p2sh(), expected_p2shout);
+
+        // p2sh-p2wsh
+        let redeem_script = hex_script!("410479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8ac");
+        let expected_witout = hex_script!("0020b95237b48faaa69eb078e1170be3b5cbb3fddf16d0a991e14ad274f7b33a4f64");
+        let expected_out = hex_script!("a914f386c2ba255cc56d20cfa6ea8b062f8b5994551887");
+        assert!(redeem_script.to_p2sh().is_p2sh());
+        assert!(redeem_script.to_p2sh().to_v0_p2wsh().is_v0_p2wsh());
+        assert_eq!(redeem_script.to_v0_p2wsh(), expected_witout);
+        assert_eq!(redeem_script.to_v0_p2wsh().to_p2sh(), expected_out);
+    }
+
+    #[test]
+    fn instruction_indices_test() {
+        let script = hex_script!("0169b24c0100ab76");
+        let v: Result<Vec<(usize, Instruction)>, Error> = script.instruction_indices().collect();
+        assert_eq!(
+            v.unwrap(),
+            vec![
+                (0, Instruction::PushBytes(&[0x69])),
+                (2, Instruction::Op(opcodes::all::OP_CSV)),
+                (3, Instruction::PushBytes(&[0x00])),
+                (6, Instruction::Op(opcodes::all::OP_CODESEPARATOR)),
+                (7, Instruction::Op(opcodes::all::OP_DUP)),
+            ]
+        );
+
+        let mut minimal = script.instruction_indices_minimal();
+        assert_eq!(minimal.next(), Some(Ok((0, Instruction::PushBytes(&[0x69])))));
+        assert_eq!(minimal.next(), Some(Ok((2, Instruction::Op(opcodes::all::OP_CSV)))));
+        assert_eq!(minimal.next(), Some(Err(Error::NonMinimalPush)));
+        assert_eq!(minimal.next(), None);
+
+        let truncated = hex_script!("76024c");
+        let mut instructions = truncated.instruction_indices();
+        assert_eq!(instructions.next(), Some(Ok((0, Instruction::Op(opcodes::all::OP_DUP)))));
+        assert_eq!(instructions.pos(), 1);
+        assert_eq!(instructions.next(), Some(Err(Error::EarlyEndOfScript)));
+        assert_eq!(instructions.pos(), 3);
+
+        assert_eq!(
+            script.split_at_instruction(7),
+            Some((hex_script!("0169b24c0100ab"), hex_script!("76")))
+        );
+        assert_eq!(script.split_at_instruction(0), Some((Script::new(), script.clone())));
+        assert_eq!(script.split_at_instruction(8), Some((script.clone(), Script::new())));
+        assert_eq!(script.split_at_instruction(1), None);
+        assert_eq!(script.split_at_instruction(4), None);
+        assert_eq!(script.split_at_instruction(9), None);
+        assert_eq!(truncated.split_at_instruction(1), Some((hex_script!("76"), hex_script!("024c"))));
+        assert_eq!(truncated.split_at_instruction(3), None);
+    }
+
+    #[test]
+    fn test_iterator() {
+        let zero = hex_script!("00");
+        let zeropush = hex_script!("0100");
+
+        let nonminimal = hex_script!("4c0169b2");      // PUSHDATA1 for no reason
+        let minimal = hex_script!("0169b2");           // minimal
+        let nonminimal_alt = hex_script!("026900b2");  // non-minimal number but minimal push (should be OK)
+
+        let v_zero: Result<Vec<Instruction>, Error> = zero.instructions_minimal().collect();
+        let v_zeropush: Result<Vec<Instruction>, Error> = zeropush.instructions_minimal().collect();
+
+        let v_min: Result<Vec<Instruction>, Error> = minimal.instructions_minimal().collect();
+        let v_nonmin: Result<Vec<Instruction>, Error> = nonminimal.instructions_minimal().collect();
+        let v_nonmin_alt: Result<Vec<Instruction>, Error> = nonminimal_alt.instructions_minimal().collect();
+        let slop_v_min: Result<Vec<Instruction>, Error> = minimal.instructions().collect();
+        let slop_v_nonmin: Result<Vec<Instruction>, Error> = nonminimal.instructions().collect();
+        let slop_v_nonmin_alt: Result<Vec<Instruction>, Error> = nonminimal_alt.instructions().collect();
+
+        assert_eq!(
+            v_zero.unwrap(),
+            vec![
+                Instruction::PushBytes(&[]),
+            ]
+        );
+        assert_eq!(
+            v_zeropush.unwrap(),
+            vec![
+                Instruction::PushBytes(&[0]),
+            ]
+        );
+
+        assert_eq!(
+            v_min.clone().unwrap(),
+            vec![
+                Instruction::PushBytes(&[105]),
+                Instruction::Op(opcodes::OP_NOP3),
+            ]
+        );
+
+        assert_eq!(
+            v_nonmin.err().unwrap(),
+            Error::NonMinimalPush
+        );
+
+        assert_eq!(
+            v_nonmin_alt.clone().unwrap(),
+            vec![
+                Instruction::PushBytes(&[105, 0]),
+                Instruction::Op(opcodes::OP_NOP3),
+            ]
+        );
+
+        assert_eq!(v_min.clone().unwrap(), slop_v_min.unwrap());
+        assert_eq!(v_min.unwrap(), slop_v_nonmin.unwrap());
+        assert_eq!(v_nonmin_alt.unwrap(), slop_v_nonmin_alt.unwrap());
+    }
+
+	#[test]
+    fn script_ord() {
+        let script_1 = Builder::new().push_slice(&[1,2,3,4]).into_script();
+        let script_2 = Builder::new().push_int(10).into_script();
+        let script_3 = Builder::new().push_int(15).into_script();
+        let script_4 = Builder::new().push_opcode(opcodes::all::OP_RETURN).into_script();
+
+        assert!(script_1 < script_2);
+        assert!(script_2 < script_3);
+        assert!(script_3 < script_4);
+
+        assert!(script_1 <= script_1);
+        assert!(script_1 >= script_1);
+
+        assert!(script_4 > script_3);
+        assert!(script_3 > script_2);
+        assert!(script_2 > script_1);
+    }
+
+	#[test]
+	#[cfg(feature="bitcoinconsensus")]
+	fn test_bitcoinconsensus () {
+		// a random segwit transaction from the blockchain using native segwit
+		let spent = Builder::from(Vec::from_hex("0020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d").unwrap()).into_script();
+		let spending = Vec::from_hex("010000000001011f97548fbbe7a0db7588a66e18d803d0089315aa7d4cc28360b6ec50ef36718a0100000000ffffffff02df1776000000000017a9146c002a686959067f4866b8fb493ad7970290ab728757d29f0000000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d04004730440220565d170eed95ff95027a69b313758450ba84a01224e1f7f130dda46e94d13f8602207bdd20e307f062594022f12ed5017bbf4a055a06aea91c10110a0e3bb23117fc014730440220647d2dc5b15f60bc37dc42618a370b2a1490293f9e5c8464f53ec4fe1dfe067302203598773895b4b16d37485cbe21b337f4e4b650739880098c592553add7dd4355016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000").unwrap();
+		spent.verify(0, 18393430, spending.as_slice()).unwrap();
+	}
+}
+
@@ -0,0 +1,220 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//   The rust-bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Script numbers
+//!
+//! The numbers the arithmetic, lock time and signature counting opcodes read
+//! from and push onto the stack.
+//!
+
+use std::fmt;
+
+use blockdata::script::Error;
+
+/// A number as script opcodes interpret stack elements.
+///
+/// Numbers are encoded little-endian in sign-magnitude, with the sign in the
+/// most significant bit of the last byte, and zero as the empty element.
+/// Opcodes only read numbers of a limited size, but their results may be
+/// larger: the number type holds any `i64`.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ScriptNum(i64);
+
+impl ScriptNum {
+    /// Maximum size in bytes of the numbers read by arithmetic opcodes
+    pub const MAX_SIZE: usize = 4;
+
+    /// Maximum size in bytes of the lock times read by OP_CHECKLOCKTIMEVERIFY
+    /// and OP_CHECKSEQUENCEVERIFY, as 4-byte times would overflow in 2038
+    pub const MAX_LOCKTIME_SIZE: usize = 5;
+
+    /// Decodes a number of at most `max_size` bytes, which can't exceed 8.
+    ///
+    /// With `require_minimal`, numbers with excess zero bytes are rejected
+    /// with [Error::NonMinimalNumber], like the MINIMALDATA rule requires.
+    /// Larger numbers fail with [Error::NumericOverflow].
+    pub fn from_slice(data: &[u8], require_minimal: bool, max_size: usize) -> Result<ScriptNum, Error> {
+        if data.len() > max_size || data.len() > 8 {
+            return Err(Error::NumericOverflow);
+        }
+        let last = match data.last() {
+            Some(last) => *last,
+            None => return Ok(ScriptNum(0)),
+        };
+        // The most significant byte may only be zero, apart from the sign bit,
+        // if the byte before it needs its own sign bit to be clear
+        if require_minimal && last & 0x7f == 0 && (data.len() == 1 || data[data.len() - 2] & 0x80 == 0) {
+            return Err(Error::NonMinimalNumber);
+        }
+        let mut abs = 0u64;
+        for (i, byte) in data.iter().enumerate() {
+            abs |= (*byte as u64) << (8 * i);
+        }
+        abs &= !(0x80 << (8 * (data.len() - 1)));
+        if last & 0x80 != 0 {
+            Ok(ScriptNum(-(abs as i64)))
+        } else {
+            Ok(ScriptNum(abs as i64))
+        }
+    }
+
+    /// Encodes the number minimally
+    pub fn to_bytes(self) -> Vec<u8> {
+        let neg = self.0 < 0;
+        // Works for i64::min_value() too, whose magnitude doesn't fit an i64
+        let mut abs = if neg { (!(self.0 as u64)).wrapping_add(1) } else { self.0 as u64 };
+        let mut v = vec![];
+        while abs > 0 {
+            v.push(abs as u8);
+            abs >>= 8;
+        }
+        // Add a byte for the sign if the sign bit of the last one is in use
+        match v.last() {
+            Some(&last) if last & 0x80 != 0 => {}
+            Some(_) => {
+                let n = v.len() - 1;
+                v[n] |= if neg { 0x80 } else { 0 };
+                return v;
+            }
+            None => return v,
+        }
+        v.push(if neg { 0x80 } else { 0 });
+        v
+    }
+
+    /// The number as an `i64`
+    pub fn to_i64(self) -> i64 {
+        self.0
+    }
+
+    /// The number as an `i32`, saturated to its range like Bitcoin Core's
+    /// `CScriptNum::getint` does
+    pub fn to_i32(self) -> i32 {
+        if self.0 > i32::max_value() as i64 {
+            i32::max_value()
+        } else if self.0 < i32::min_value() as i64 {
+            i32::min_value()
+        } else {
+            self.0 as i32
+        }
+    }
+
+    /// Adds two numbers, returning `None` on `i64` overflow
+    pub fn checked_add(self, other: ScriptNum) -> Option<ScriptNum> {
+        self.0.checked_add(other.0).map(ScriptNum)
+    }
+
+    /// Subtracts two numbers, returning `None` on `i64` overflow
+    pub fn checked_sub(self, other: ScriptNum) -> Option<ScriptNum> {
+        self.0.checked_sub(other.0).map(ScriptNum)
+    }
+
+    /// Negates the number, returning `None` on `i64` overflow
+    pub fn checked_neg(self) -> Option<ScriptNum> {
+        self.0.checked_neg().map(ScriptNum)
+    }
+
+    /// The absolute value of the number, returning `None` on `i64` overflow
+    pub fn checked_abs(self) -> Option<ScriptNum> {
+        self.0.checked_abs().map(ScriptNum)
+    }
+}
+
+impl From<i64> for ScriptNum {
+    fn from(n: i64) -> ScriptNum {
+        ScriptNum(n)
+    }
+}
+
+impl From<i32> for ScriptNum {
+    fn from(n: i32) -> ScriptNum {
+        ScriptNum(n as i64)
+    }
+}
+
+impl From<ScriptNum> for i64 {
+    fn from(n: ScriptNum) -> i64 {
+        n.0
+    }
+}
+
+impl fmt::Display for ScriptNum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_slice_test() {
+        let read = |data: &[u8], max_size| ScriptNum::from_slice(data, true, max_size).map(ScriptNum::to_i64);
+        assert_eq!(read(&[], 4), Ok(0));
+        assert_eq!(read(&[0x81], 4), Ok(-1));
+        assert_eq!(read(&[0xff, 0xff, 0xff, 0xff], 4), Ok(-0x7fffffff));
+        assert_eq!(read(&[0, 0, 0, 0, 1], 4), Err(Error::NumericOverflow));
+        assert_eq!(read(&[0, 0, 0, 0, 1], ScriptNum::MAX_LOCKTIME_SIZE), Ok(1 << 32));
+        assert_eq!(read(&[1, 0], 4), Err(Error::NonMinimalNumber));
+        assert_eq!(ScriptNum::from_slice(&[1, 0], false, 4), Ok(ScriptNum(1)));
+        assert_eq!(read(&[0x80], 4), Err(Error::NonMinimalNumber));
+        assert_eq!(ScriptNum::from_slice(&[0x80], false, 4), Ok(ScriptNum(0)));
+        assert_eq!(read(&[0xff, 0x80], 4), Ok(-0xff));
+        assert_eq!(read(&[0xff; 8], 8), Ok(-i64::max_value()));
+        assert_eq!(read(&[0; 9], 9), Err(Error::NumericOverflow));
+    }
+
+    #[test]
+    fn to_bytes_test() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[]),
+            (1, &[0x01]),
+            (-1, &[0x81]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x00]),
+            (-128, &[0x80, 0x80]),
+            (255, &[0xff, 0x00]),
+            (256, &[0x00, 0x01]),
+            (-0x7fffffff, &[0xff, 0xff, 0xff, 0xff]),
+            (1 << 32, &[0, 0, 0, 0, 1]),
+            (i64::max_value(), &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+            (i64::min_value(), &[0, 0, 0, 0, 0, 0, 0, 0x80, 0x80]),
+        ];
+        for &(n, bytes) in cases {
+            assert_eq!(ScriptNum::from(n).to_bytes(), bytes);
+            if bytes.len() <= 8 {
+                assert_eq!(ScriptNum::from_slice(bytes, true, 8), Ok(ScriptNum(n)));
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let max = ScriptNum::from(i64::max_value());
+        assert_eq!(ScriptNum::from(2).checked_add(ScriptNum::from(-3)), Some(ScriptNum(-1)));
+        assert_eq!(max.checked_add(ScriptNum::from(1)), None);
+        assert_eq!(ScriptNum::from(2).checked_sub(ScriptNum::from(3)), Some(ScriptNum(-1)));
+        assert_eq!(ScriptNum::from(i64::min_value()).checked_sub(ScriptNum::from(1)), None);
+        assert_eq!(max.checked_neg(), Some(ScriptNum(-i64::max_value())));
+        assert_eq!(ScriptNum::from(i64::min_value()).checked_neg(), None);
+        assert_eq!(ScriptNum::from(-5).checked_abs(), Some(ScriptNum(5)));
+
+        assert_eq!(max.to_i32(), i32::max_value());
+        assert_eq!(ScriptNum::from(i64::min_value()).to_i32(), i32::min_value());
+        assert_eq!(ScriptNum::from(-7).to_i32(), -7);
+        assert_eq!(i64::from(ScriptNum::from(-7)), -7);
+        assert_eq!(ScriptNum::from(-7).to_string(), "-7");
+    }
+}
@@ -24,18 +24,24 @@
 //!
 
 use std::default::Default;
-use std::{error, fmt, io, str};
+use std::{cmp, error, fmt, io, str};
+use std::collections::HashSet;
 
 use hashes::{self, Hash, sha256d};
 use hashes::hex::FromHex;
+use secp256k1::{Secp256k1, Signing, Verification};
 
 use util::endian;
-use blockdata::constants::WITNESS_SCALE_FACTOR;
-#[cfg(feature="bitcoinconsensus")] use blockdata::script;
-use blockdata::script::Script;
+use blockdata::constants::{max_money, MAX_BLOCK_WEIGHT, WITNESS_SCALE_FACTOR};
+use blockdata::locktime::{Confirmation, LockTime, RelativeLockTime, Sequence, SequenceLocks};
+use blockdata::script::{self, interpreter, Script, VerifyFlags};
+use blockdata::weight::Weight;
+use blockdata::witness::Witness;
 use consensus::{encode, Decodable, Encodable};
 use consensus::encode::MAX_VEC_SIZE;
 use hash_types::{SigHash, Txid, Wtxid};
+use network::constants::Network;
+use util::sighash::Prevouts;
 use VarInt;
 
 /// A reference to a transaction output
@@ -192,7 +198,7 @@ pub struct TxIn {
     /// Encodable/Decodable, as it is (de)serialized at the end of the full
     /// Transaction. It *is* (de)serialized with the rest of the TxIn in other
     /// (de)serialization routines.
-    pub witness: Vec<Vec<u8>>
+    pub witness: Witness
 }
 
 impl Default for TxIn {
@@ -201,7 +207,7 @@ impl Default for TxIn {
             previous_output: OutPoint::default(),
             script_sig: Script::new(),
             sequence: u32::max_value(),
-            witness: Vec::new(),
+            witness: Witness::default(),
         }
     }
 }
@@ -275,7 +281,7 @@ impl Transaction {
         let cloned_tx = Transaction {
             version: self.version,
             lock_time: self.lock_time,
-            input: self.input.iter().map(|txin| TxIn { script_sig: Script::new(), witness: vec![], .. *txin }).collect(),
+            input: self.input.iter().map(|txin| TxIn { script_sig: Script::new(), witness: Witness::default(), .. *txin }).collect(),
             output: self.output.clone(),
         };
         cloned_tx.txid().into()
@@ -352,7 +358,7 @@ impl Transaction {
                 previous_output: self.input[input_index].previous_output,
                 script_sig: script_pubkey.clone(),
                 sequence: self.input[input_index].sequence,
-                witness: vec![],
+                witness: Witness::default(),
             }];
         } else {
             tx.input = Vec::with_capacity(self.input.len());
@@ -361,7 +367,7 @@ impl Transaction {
                     previous_output: input.previous_output,
                     script_sig: if n == input_index { script_pubkey.clone() } else { Script::new() },
                     sequence: if n != input_index && (sighash == SigHashType::Single || sighash == SigHashType::None) { 0 } else { input.sequence },
-                    witness: vec![],
+                    witness: Witness::default(),
                 });
             }
         }
@@ -419,12 +425,78 @@ impl Transaction {
         self.get_scaled_size(WITNESS_SCALE_FACTOR)
     }
 
+    /// Gets the weight of this transaction, as [Transaction::get_weight] does
+    #[inline]
+    pub fn weight(&self) -> Weight {
+        Weight::from_wu(self.get_weight() as u64)
+    }
+
+    /// Gets the virtual size of this transaction, its weight divided by four
+    /// and rounded up, as defined by BIP141
+    #[inline]
+    pub fn vsize(&self) -> usize {
+        self.weight().to_vbytes_ceil() as usize
+    }
+
     /// Gets the regular byte-wise consensus-serialized size of this transaction.
     #[inline]
     pub fn get_size(&self) -> usize {
         self.get_scaled_size(1)
     }
 
+    /// Gets the size of this transaction without its witness data, as it is
+    /// serialized to compute its txid.
+    #[inline]
+    pub fn get_strippedsize(&self) -> usize {
+        // The weight counts non-witness bytes three more times than the size
+        (self.get_weight() - self.get_size()) / (WITNESS_SCALE_FACTOR - 1)
+    }
+
+    /// Predicts the maximum weight of this unsigned transaction once its
+    /// inputs are signed, given how each input is spent. The `script_sig`s
+    /// and witnesses the transaction already has are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `spends` doesn't have one item per input.
+    pub fn predict_weight(&self, spends: &[SpendType]) -> Weight {
+        assert_eq!(spends.len(), self.input.len(), "one spend type per input is required");
+        let mut input_size = 0;
+        let mut witness_size = 0;
+        let mut inputs_with_witnesses = 0;
+        for spend in spends {
+            let script_sig_len = spend.script_sig_len();
+            input_size += 32 + 4 + 4 + VarInt(script_sig_len as u64).len() + script_sig_len;
+            let elements = spend.witness_element_lens();
+            if !elements.is_empty() {
+                inputs_with_witnesses += 1;
+                witness_size += VarInt(elements.len() as u64).len();
+                for len in elements {
+                    witness_size += VarInt(len as u64).len() + len;
+                }
+            }
+        }
+        let mut output_size = 0;
+        for output in &self.output {
+            output_size += 8 +
+                VarInt(output.script_pubkey.len() as u64).len() +
+                output.script_pubkey.len();
+        }
+        let non_witness_size = 4 +
+            VarInt(self.input.len() as u64).len() +
+            input_size +
+            VarInt(self.output.len() as u64).len() +
+            output_size +
+            4;
+        if inputs_with_witnesses > 0 {
+            // The segwit marker and flag, and the empty witnesses of the
+            // inputs which have none
+            witness_size += 2 + self.input.len() - inputs_with_witnesses;
+        }
+        Weight::from_non_witness_data_size(non_witness_size as u64) +
+            Weight::from_witness_data_size(witness_size as u64)
+    }
+
     /// Internal utility function for get_{size,weight}
     fn get_scaled_size(&self, scale_factor: usize) -> usize {
         let mut input_weight = 0;
@@ -435,10 +507,7 @@ impl Transaction {
                 input.script_sig.len());
             if !input.witness.is_empty() {
                 inputs_with_witnesses += 1;
-                input_weight += VarInt(input.witness.len() as u64).len();
-                for elem in &input.witness {
-                    input_weight += VarInt(elem.len() as u64).len() + elem.len();
-                }
+                input_weight += input.witness.serialized_len();
             }
         }
         let mut output_size = 0;
@@ -479,6 +548,70 @@ impl Transaction {
         Ok(())
     }
 
+    /// Verify that this transaction is able to spend its inputs with the
+    /// native script interpreter, enforcing the given verification rules.
+    /// The lambda spent should not return the same TxOut twice!
+    pub fn verify_native<C, S>(&self, secp: &Secp256k1<C>, mut spent: S, flags: VerifyFlags) -> Result<(), script::Error>
+        where C: Signing + Verification, S: FnMut(&OutPoint) -> Option<TxOut> {
+        let mut prevouts = Vec::with_capacity(self.input.len());
+        for input in &self.input {
+            match spent(&input.previous_output) {
+                Some(output) => prevouts.push(output),
+                None => return Err(script::Error::UnknownSpentOutput(input.previous_output)),
+            }
+        }
+        for idx in 0..self.input.len() {
+            interpreter::verify_input(secp, self, idx, &Prevouts::All(&prevouts), flags)?;
+        }
+        Ok(())
+    }
+
+    /// Counts the signature operations of the scriptSigs and output scripts,
+    /// like Bitcoin Core's `GetLegacySigOpCount`
+    pub fn legacy_sigop_count(&self) -> usize {
+        let inputs = self.input.iter().map(|input| input.script_sig.count_sigops(false));
+        let outputs = self.output.iter().map(|output| output.script_pubkey.count_sigops(false));
+        inputs.chain(outputs).sum()
+    }
+
+    /// Counts the signature operations of the redeem scripts of the P2SH
+    /// outputs this transaction spends, like Bitcoin Core's `GetP2SHSigOpCount`.
+    pub fn p2sh_sigop_count<S>(&self, mut spent: S) -> Result<usize, script::Error>
+        where S: FnMut(&OutPoint) -> Option<TxOut> {
+        if self.is_coin_base() {
+            return Ok(0);
+        }
+        let mut count = 0;
+        for input in &self.input {
+            let output = spent(&input.previous_output)
+                .ok_or(script::Error::UnknownSpentOutput(input.previous_output))?;
+            count += output.script_pubkey.count_p2sh_sigops(&input.script_sig);
+        }
+        Ok(count)
+    }
+
+    /// Computes the signature operations cost of this transaction (BIP141),
+    /// like Bitcoin Core's `GetTransactionSigOpCost`. Redeem scripts are only
+    /// counted under the P2SH flag, and witness scripts under the WITNESS flag.
+    pub fn sigop_cost<S>(&self, mut spent: S, flags: VerifyFlags) -> Result<usize, script::Error>
+        where S: FnMut(&OutPoint) -> Option<TxOut> {
+        let mut cost = self.legacy_sigop_count() * WITNESS_SCALE_FACTOR;
+        if self.is_coin_base() {
+            return Ok(cost);
+        }
+        for input in &self.input {
+            let output = spent(&input.previous_output)
+                .ok_or(script::Error::UnknownSpentOutput(input.previous_output))?;
+            if flags.has(VerifyFlags::P2SH) {
+                cost += output.script_pubkey.count_p2sh_sigops(&input.script_sig) * WITNESS_SCALE_FACTOR;
+            }
+            if flags.has(VerifyFlags::WITNESS) {
+                cost += output.script_pubkey.count_witness_sigops(&input.script_sig, &input.witness);
+            }
+        }
+        Ok(cost)
+    }
+
     /// Is this a coin base transaction?
     pub fn is_coin_base(&self) -> bool {
         self.input.len() == 1 && self.input[0].previous_output.is_null()
@@ -488,7 +621,220 @@ impl Transaction {
     /// **does not** cover the case where a transaction becomes replaceable due to ancestors being
     /// RBF.
     pub fn is_explicitly_rbf(&self) -> bool {
-        self.input.iter().any(|input| input.sequence < (0xffffffff - 1))
+        self.input.iter().any(|input| Sequence(input.sequence).is_rbf())
+    }
+
+    /// Whether the lock time of this transaction allows it in the block at
+    /// `height`, whose previous block has the median time past `time`, like
+    /// Bitcoin Core's `IsFinalTx`. A zero lock time is always satisfied, and
+    /// the lock time is disabled when all the inputs have final sequence
+    /// numbers.
+    pub fn is_final(&self, height: u32, time: u32) -> bool {
+        self.lock_time == 0 ||
+            LockTime::from_consensus(self.lock_time).is_satisfied_by(height, time) ||
+            self.input.iter().all(|input| Sequence(input.sequence).is_final())
+    }
+
+    /// Combines the BIP68 relative lock times of the inputs of this
+    /// transaction, given where each spent output was confirmed, like Bitcoin
+    /// Core's `CalculateSequenceLocks`. Relative lock times only apply from
+    /// version 2 transactions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `spent` doesn't have one item per input.
+    pub fn sequence_locks(&self, spent: &[Confirmation]) -> SequenceLocks {
+        assert_eq!(spent.len(), self.input.len(), "one confirmation per input is required");
+        if (self.version as u32) < 2 {
+            return SequenceLocks::default();
+        }
+        // The last locked height and time, where -1 means unlocked
+        let mut min_height: i64 = -1;
+        let mut min_time: i64 = -1;
+        for (input, confirmation) in self.input.iter().zip(spent) {
+            match Sequence(input.sequence).to_relative_lock_time() {
+                Some(RelativeLockTime::Blocks(n)) => {
+                    min_height = cmp::max(min_height, confirmation.height as i64 + n as i64 - 1);
+                }
+                Some(RelativeLockTime::Time(n)) => {
+                    min_time = cmp::max(min_time, confirmation.time as i64 + ((n as i64) << 9) - 1);
+                }
+                None => {}
+            }
+        }
+        let to_lock = |value: i64| if value < 0 { None } else { Some(cmp::min(value, u32::max_value() as i64) as u32) };
+        SequenceLocks {
+            min_height: to_lock(min_height),
+            min_time: to_lock(min_time),
+        }
+    }
+
+    /// Checks the consensus rules which don't depend on the chain state, like
+    /// Bitcoin Core's `CheckTransaction`. Any decoded transaction must pass
+    /// them to be valid.
+    pub fn check(&self) -> Result<(), CheckTransactionError> {
+        if self.input.is_empty() {
+            return Err(CheckTransactionError::NoInputs);
+        }
+        if self.output.is_empty() {
+            return Err(CheckTransactionError::NoOutputs);
+        }
+        let stripped_weight = Weight::from_non_witness_data_size(self.get_strippedsize() as u64);
+        if stripped_weight > Weight::from_wu(MAX_BLOCK_WEIGHT as u64) {
+            return Err(CheckTransactionError::Oversize(stripped_weight));
+        }
+
+        let max_money = max_money(Network::Bitcoin);
+        let mut total: u64 = 0;
+        for (i, output) in self.output.iter().enumerate() {
+            // Bitcoin Core reads values as signed integers
+            if output.value > i64::max_value() as u64 {
+                return Err(CheckTransactionError::NegativeOutput(i));
+            }
+            if output.value > max_money {
+                return Err(CheckTransactionError::OutputTooLarge(i));
+            }
+            total += output.value;
+            if total > max_money {
+                return Err(CheckTransactionError::TotalOutputTooLarge);
+            }
+        }
+
+        let mut outpoints = HashSet::with_capacity(self.input.len());
+        for (i, input) in self.input.iter().enumerate() {
+            if !outpoints.insert(input.previous_output) {
+                return Err(CheckTransactionError::DuplicateInput(i));
+            }
+        }
+
+        if self.is_coin_base() {
+            match self.input[0].script_sig.len() {
+                2..=100 => {}
+                len => return Err(CheckTransactionError::CoinbaseScriptSigSize(len)),
+            }
+        } else if let Some(i) = self.input.iter().position(|input| input.previous_output.is_null()) {
+            return Err(CheckTransactionError::NullPrevout(i));
+        }
+        Ok(())
+    }
+}
+
+/// A violation of the context-free consensus rules, see [Transaction::check]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CheckTransactionError {
+    /// The transaction has no inputs
+    NoInputs,
+    /// The transaction has no outputs
+    NoOutputs,
+    /// The transaction without its witness data weighs more than a block
+    Oversize(Weight),
+    /// The value of the output at this index is negative as a signed integer
+    NegativeOutput(usize),
+    /// The value of the output at this index is more than all the bitcoins
+    OutputTooLarge(usize),
+    /// The total value of the outputs is more than all the bitcoins
+    TotalOutputTooLarge,
+    /// The input at this index spends the same output as an earlier input
+    DuplicateInput(usize),
+    /// The `script_sig` of the coinbase input has this length, outside of the
+    /// 2 to 100 bytes range
+    CoinbaseScriptSigSize(usize),
+    /// The input at this index of a non-coinbase transaction has a null
+    /// previous output
+    NullPrevout(usize),
+}
+
+impl fmt::Display for CheckTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CheckTransactionError::NoInputs => f.write_str("transaction has no inputs"),
+            CheckTransactionError::NoOutputs => f.write_str("transaction has no outputs"),
+            CheckTransactionError::Oversize(weight) => write!(f, "transaction weight {} without witness exceeds the block weight", weight),
+            CheckTransactionError::NegativeOutput(i) => write!(f, "output {} has a negative value", i),
+            CheckTransactionError::OutputTooLarge(i) => write!(f, "value of output {} exceeds the money supply", i),
+            CheckTransactionError::TotalOutputTooLarge => f.write_str("total output value exceeds the money supply"),
+            CheckTransactionError::DuplicateInput(i) => write!(f, "input {} spends the same output as an earlier input", i),
+            CheckTransactionError::CoinbaseScriptSigSize(len) => write!(f, "coinbase script_sig length {} is not between 2 and 100", len),
+            CheckTransactionError::NullPrevout(i) => write!(f, "input {} of a non-coinbase transaction spends a null outpoint", i),
+        }
+    }
+}
+
+impl error::Error for CheckTransactionError {}
+
+/// Maximum size of a DER encoded ECDSA signature with a low S value and a
+/// sighash type byte, as standardness requires
+const MAX_ECDSA_SIG_SIZE: usize = 72;
+
+/// How a transaction input is spent, to predict the weight of the signed
+/// transaction with [Transaction::predict_weight]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SpendType {
+    /// Spend of a P2PKH output, whose key may be uncompressed
+    P2pkh {
+        /// Whether the public key is compressed
+        compressed: bool,
+    },
+    /// Spend of a P2WPKH output
+    P2wpkh,
+    /// Spend of a P2WPKH output nested in P2SH
+    P2shP2wpkh,
+    /// Spend of a P2WSH output to a bare multisig script with compressed keys
+    P2wshMultisig {
+        /// Number of signatures required
+        threshold: usize,
+        /// Number of keys
+        keys: usize,
+    },
+    /// Key path spend of a P2TR output
+    P2trKeyPath {
+        /// Whether the signature uses `SIGHASH_DEFAULT`, which saves the
+        /// sighash type byte
+        default_sighash: bool,
+    },
+    /// Any other spend, given the sizes of its `script_sig` and witness
+    /// elements
+    Custom {
+        /// Size of the `script_sig`, without its length prefix
+        script_sig_len: usize,
+        /// Sizes of the witness elements, without their length prefixes
+        witness_element_lens: Vec<usize>,
+    },
+}
+
+impl SpendType {
+    /// Size of the `script_sig` spending the output
+    fn script_sig_len(&self) -> usize {
+        match *self {
+            SpendType::P2pkh { compressed } => {
+                1 + MAX_ECDSA_SIG_SIZE + 1 + if compressed { 33 } else { 65 }
+            }
+            // Push of the 22 byte witness program
+            SpendType::P2shP2wpkh => 23,
+            SpendType::Custom { script_sig_len, .. } => script_sig_len,
+            _ => 0,
+        }
+    }
+
+    /// Sizes of the witness elements spending the output
+    fn witness_element_lens(&self) -> Vec<usize> {
+        match *self {
+            SpendType::P2pkh { .. } => vec![],
+            SpendType::P2wpkh | SpendType::P2shP2wpkh => vec![MAX_ECDSA_SIG_SIZE, 33],
+            SpendType::P2wshMultisig { threshold, keys } => {
+                // Small numbers up to 16 have their own opcodes, larger ones
+                // take a push of one byte
+                let num_size = |n| if n <= 16 { 1 } else { 2 };
+                let script_len = num_size(threshold) + keys * 34 + num_size(keys) + 1;
+                // The dummy element OP_CHECKMULTISIG pops, and the signatures
+                let mut lens = vec![0];
+                lens.extend(vec![MAX_ECDSA_SIG_SIZE; threshold]);
+                lens.push(script_len);
+                lens
+            }
+            SpendType::P2trKeyPath { default_sighash } => vec![if default_sighash { 64 } else { 65 }],
+            SpendType::Custom { ref witness_element_lens, .. } => witness_element_lens.clone(),
+        }
     }
 }
 
@@ -530,7 +876,7 @@ impl Decodable for TxIn {
             previous_output: Decodable::consensus_decode(&mut d)?,
             script_sig: Decodable::consensus_decode(&mut d)?,
             sequence: Decodable::consensus_decode(d)?,
-            witness: vec![],
+            witness: Witness::default(),
         })
     }
 }
@@ -745,11 +1091,14 @@ impl From<SigHashType> for u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{OutPoint, ParseOutPointError, Transaction, TxIn, NonStandardSigHashType};
+    use super::{OutPoint, ParseOutPointError, Transaction, TxIn, TxOut, NonStandardSigHashType, SpendType, CheckTransactionError};
 
     use std::str::FromStr;
     use blockdata::constants::WITNESS_SCALE_FACTOR;
-    use blockdata::script::Script;
+    use blockdata::opcodes;
+    use blockdata::script::{Builder, Script};
+    use blockdata::weight::Weight;
+    use blockdata::witness::Witness;
     use consensus::encode::serialize;
     use consensus::encode::deserialize;
 
@@ -759,6 +1108,56 @@ mod tests {
     use hash_types::*;
     use SigHashType;
 
+    #[test]
+    fn test_sigop_cost() {
+        use blockdata::script::{Builder, VerifyFlags};
+        use blockdata::transaction::TxOut;
+        use util::ecdsa::PublicKey;
+
+        let key = PublicKey::from_str("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        let redeem_script = Script::new_multisig(2, &[key, key, key]).unwrap();
+        let witness_script = Script::new_p2pk(&key);
+        let spent = vec![
+            Script::new_p2pk(&key),
+            redeem_script.to_p2sh(),
+            witness_script.to_v0_p2wsh(),
+            witness_script.to_v0_p2wsh().to_p2sh(),
+        ];
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: spent.iter().enumerate().map(|(idx, script_pubkey)| TxIn {
+                previous_output: OutPoint::new(Txid::hash(&[idx as u8]), 0),
+                script_sig: if script_pubkey.is_p2sh() {
+                    let redeem_script = if idx == 1 { redeem_script.clone() } else { witness_script.to_v0_p2wsh() };
+                    Builder::new().push_int(0).push_slice(&redeem_script[..]).into_script()
+                } else {
+                    Script::new()
+                },
+                sequence: 0xffffffff,
+                witness: if script_pubkey.is_p2sh() && idx == 1 { Witness::default() } else { Witness::from_vec(vec![witness_script.to_bytes()]) },
+            }).collect(),
+            output: vec![
+                TxOut { value: 0, script_pubkey: Script::new_p2pk(&key) },
+                TxOut { value: 0, script_pubkey: redeem_script.clone() },
+            ],
+        };
+        let find_spent = |outpoint: &OutPoint| {
+            tx.input.iter().position(|input| input.previous_output == *outpoint)
+                .map(|idx| TxOut { value: 0, script_pubkey: spent[idx].clone() })
+        };
+
+        assert_eq!(tx.legacy_sigop_count(), 21);
+        assert_eq!(tx.p2sh_sigop_count(&find_spent), Ok(3));
+        assert_eq!(tx.sigop_cost(&find_spent, VerifyFlags::NONE), Ok(84));
+        assert_eq!(tx.sigop_cost(&find_spent, VerifyFlags::P2SH), Ok(96));
+        assert_eq!(tx.sigop_cost(&find_spent, VerifyFlags::P2SH | VerifyFlags::WITNESS), Ok(98));
+        assert_eq!(
+            tx.sigop_cost(|_| None, VerifyFlags::P2SH),
+            Err(::blockdata::script::Error::UnknownSpentOutput(tx.input[0].previous_output))
+        );
+    }
+
     #[test]
     fn test_outpoint() {
         assert_eq!(OutPoint::from_str("i don't care"),
@@ -820,6 +1219,130 @@ mod tests {
         assert!(!tx.is_coin_base());
     }
 
+    #[test]
+    fn test_check() {
+        use network::constants::Network;
+        use blockdata::constants;
+
+        let genesis = constants::genesis_block(Network::Bitcoin);
+        assert_eq!(genesis.txdata[0].check(), Ok(()));
+        let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
+        let tx: Transaction = deserialize(&tx_bytes).unwrap();
+        assert_eq!(tx.check(), Ok(()));
+
+        let mut bad = tx.clone();
+        bad.input.clear();
+        assert_eq!(bad.check(), Err(CheckTransactionError::NoInputs));
+        let mut bad = tx.clone();
+        bad.output.clear();
+        assert_eq!(bad.check(), Err(CheckTransactionError::NoOutputs));
+
+        let mut bad = tx.clone();
+        bad.output[0].script_pubkey = Script::from(vec![0; constants::MAX_BLOCK_WEIGHT as usize / 4]);
+        assert_eq!(bad.check(), Err(CheckTransactionError::Oversize(Weight::from_wu(serialize(&bad).len() as u64 * 4))));
+        // Witness data doesn't count
+        let mut big_witness = tx.clone();
+        big_witness.input[0].witness.push(vec![0; constants::MAX_BLOCK_WEIGHT as usize]);
+        assert_eq!(big_witness.check(), Ok(()));
+
+        let max_money = constants::max_money(Network::Bitcoin);
+        let mut bad = tx.clone();
+        bad.output[0].value = u64::max_value();
+        assert_eq!(bad.check(), Err(CheckTransactionError::NegativeOutput(0)));
+        bad.output[0].value = max_money + 1;
+        assert_eq!(bad.check(), Err(CheckTransactionError::OutputTooLarge(0)));
+        bad.output[0].value = max_money;
+        assert_eq!(bad.check(), Ok(()));
+        bad.output.push(bad.output[0].clone());
+        assert_eq!(bad.check(), Err(CheckTransactionError::TotalOutputTooLarge));
+
+        let mut bad = tx.clone();
+        bad.input.push(tx.input[0].clone());
+        assert_eq!(bad.check(), Err(CheckTransactionError::DuplicateInput(1)));
+        bad.input[1].previous_output = OutPoint::null();
+        assert_eq!(bad.check(), Err(CheckTransactionError::NullPrevout(1)));
+
+        let mut coinbase = genesis.txdata[0].clone();
+        coinbase.input[0].script_sig = Script::from(vec![0; 101]);
+        assert_eq!(coinbase.check(), Err(CheckTransactionError::CoinbaseScriptSigSize(101)));
+        coinbase.input[0].script_sig = Script::from(vec![0; 1]);
+        assert_eq!(coinbase.check(), Err(CheckTransactionError::CoinbaseScriptSigSize(1)));
+        coinbase.input[0].script_sig = Script::from(vec![0; 2]);
+        assert_eq!(coinbase.check(), Ok(()));
+    }
+
+    #[test]
+    fn test_is_final() {
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 700_000,
+            input: vec![TxIn { sequence: 0xfffffffe, ..Default::default() }, Default::default()],
+            output: vec![],
+        };
+        assert!(tx.is_final(700_001, 0));
+        assert!(!tx.is_final(700_000, u32::max_value()));
+        tx.lock_time = 1_600_000_000;
+        assert!(tx.is_final(0, 1_600_000_001));
+        assert!(!tx.is_final(u32::max_value(), 1_600_000_000));
+        // A zero lock time is satisfied even before the first block
+        tx.lock_time = 0;
+        assert!(tx.is_final(0, 0));
+        tx.lock_time = 1_600_000_000;
+        // Final sequence numbers disable the lock time
+        tx.input[0].sequence = 0xffffffff;
+        assert!(tx.is_final(0, 0));
+        tx.input.clear();
+        assert!(tx.is_final(0, 0));
+    }
+
+    #[test]
+    fn test_sequence_locks() {
+        use blockdata::locktime::{Confirmation, RelativeLockTime, Sequence, SequenceLocks};
+
+        let input = |lock_time: RelativeLockTime| TxIn { sequence: Sequence::from(lock_time).0, ..Default::default() };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![
+                input(RelativeLockTime::Blocks(10)),
+                input(RelativeLockTime::Blocks(20)),
+                input(RelativeLockTime::Time(2)),
+                // Disabled relative lock time
+                TxIn { sequence: 0x8000ffff, ..Default::default() },
+            ],
+            output: vec![],
+        };
+        let spent = [
+            Confirmation { height: 100, time: 1_600_000_000 },
+            Confirmation { height: 95, time: 1_600_000_000 },
+            Confirmation { height: 100, time: 1_600_000_000 },
+            Confirmation { height: 1000, time: 1_700_000_000 },
+        ];
+        let locks = tx.sequence_locks(&spent);
+        assert_eq!(locks, SequenceLocks { min_height: Some(114), min_time: Some(1_600_001_023) });
+        assert!(locks.is_satisfied_by(115, 1_600_001_024));
+        assert!(!locks.is_satisfied_by(114, 1_600_001_024));
+        assert!(!locks.is_satisfied_by(115, 1_600_001_023));
+
+        // A zero lock time on an output confirmed at height zero doesn't lock
+        tx.input.truncate(1);
+        tx.input[0].sequence = 0;
+        assert_eq!(tx.sequence_locks(&[Confirmation { height: 0, time: 0 }]), SequenceLocks::default());
+
+        // Relative lock times only apply from version 2
+        tx.input[0].sequence = 10;
+        assert_eq!(tx.sequence_locks(&spent[..1]).min_height, Some(109));
+        tx.version = 1;
+        assert_eq!(tx.sequence_locks(&spent[..1]), SequenceLocks::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "one confirmation per input is required")]
+    fn test_sequence_locks_missing_confirmation() {
+        let tx = Transaction { version: 2, lock_time: 0, input: vec![Default::default()], output: vec![] };
+        tx.sequence_locks(&[]);
+    }
+
     #[test]
     fn test_nonsegwit_transaction() {
         let tx_bytes = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000").unwrap();
@@ -875,7 +1398,12 @@ mod tests {
         assert_eq!(format!("{:x}", realtx.wtxid()),
                    "80b7d8a82d5d5bf92905b06f2014dd699e03837ca172e3a59d51426ebbe3e7f5".to_string());
         assert_eq!(realtx.get_weight(), 442);
+        assert_eq!(realtx.weight(), Weight::from_wu(442));
+        assert_eq!(realtx.vsize(), 111);
         assert_eq!(realtx.get_size(), tx_bytes.len());
+        let mut stripped = realtx.clone();
+        stripped.input[0].witness.clear();
+        assert_eq!(realtx.get_strippedsize(), serialize(&stripped).len());
     }
 
     #[test]
@@ -960,6 +1488,7 @@ mod tests {
         assert_eq!(format!("{:x}", tx.wtxid()), "d6ac4a5e61657c4c604dcde855a1db74ec6b3e54f32695d72c5e11c7761ea1b4");
         assert_eq!(format!("{:x}", tx.txid()), "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec");
         assert_eq!(tx.get_weight(), 2718);
+        assert_eq!(tx.vsize(), 680);
 
         // non-segwit tx from my mempool
         let tx_bytes = Vec::from_hex(
@@ -976,6 +1505,94 @@ mod tests {
         assert_eq!(format!("{:x}", tx.txid()), "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd");
     }
 
+    #[test]
+    fn predict_weight_test() {
+        fn input(script_sig: Script, witness: Vec<Vec<u8>>) -> TxIn {
+            TxIn {
+                script_sig: script_sig,
+                witness: Witness::from_vec(witness),
+                ..Default::default()
+            }
+        }
+        fn multisig(threshold: i64, keys: i64) -> Script {
+            let mut builder = Builder::new().push_int(threshold);
+            for _ in 0..keys {
+                builder = builder.push_slice(&[2; 33]);
+            }
+            builder.push_int(keys).push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script()
+        }
+        let sig = vec![0x30; 72];
+        let key = vec![0x02; 33];
+        let p2wpkh_redeem = Builder::new().push_int(0).push_slice(&[0; 20]).into_script();
+
+        let spends = vec![
+            SpendType::P2pkh { compressed: true },
+            SpendType::P2pkh { compressed: false },
+            SpendType::P2wpkh,
+            SpendType::P2shP2wpkh,
+            SpendType::P2wshMultisig { threshold: 2, keys: 3 },
+            SpendType::P2wshMultisig { threshold: 17, keys: 20 },
+            SpendType::P2trKeyPath { default_sighash: true },
+            SpendType::P2trKeyPath { default_sighash: false },
+            SpendType::Custom { script_sig_len: 300, witness_element_lens: vec![0; 253] },
+        ];
+        let signed = vec![
+            input(Builder::new().push_slice(&sig).push_slice(&key).into_script(), vec![]),
+            input(Builder::new().push_slice(&sig).push_slice(&[0x04; 65]).into_script(), vec![]),
+            input(Script::new(), vec![sig.clone(), key.clone()]),
+            input(Builder::new().push_slice(&p2wpkh_redeem[..]).into_script(), vec![sig.clone(), key.clone()]),
+            input(Script::new(), vec![vec![], sig.clone(), sig.clone(), multisig(2, 3).into_bytes()]),
+            input(Script::new(), {
+                let mut witness = vec![vec![]];
+                witness.extend(vec![sig.clone(); 17]);
+                witness.push(multisig(17, 20).into_bytes());
+                witness
+            }),
+            input(Script::new(), vec![vec![0; 64]]),
+            input(Script::new(), vec![vec![0; 65]]),
+            input(Script::from(vec![0; 300]), vec![vec![]; 253]),
+        ];
+        let output = TxOut { value: 0, script_pubkey: Script::from(vec![0; 34]) };
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![Default::default(); spends.len()],
+            output: vec![output.clone()],
+        };
+        let unsigned = tx.clone();
+        tx.input = signed.clone();
+        assert_eq!(unsigned.predict_weight(&spends), tx.weight());
+
+        // A legacy spend alone doesn't pay for the segwit marker
+        let unsigned = Transaction { input: vec![Default::default()], ..unsigned };
+        tx.input = signed[..1].to_vec();
+        assert_eq!(unsigned.predict_weight(&spends[..1]), tx.weight());
+
+        // The input and output counts cross a varint size
+        let unsigned = Transaction {
+            input: vec![Default::default(); 253],
+            output: vec![output; 253],
+            ..unsigned
+        };
+        tx.input = vec![signed[2].clone(); 253];
+        tx.output = unsigned.output.clone();
+        assert_eq!(unsigned.predict_weight(&vec![SpendType::P2wpkh; 253]), tx.weight());
+        tx.input = vec![signed[0].clone(); 253];
+        assert_eq!(unsigned.predict_weight(&vec![SpendType::P2pkh { compressed: true }; 253]), tx.weight());
+    }
+
+    #[test]
+    #[should_panic(expected = "one spend type per input is required")]
+    fn predict_weight_missing_spend_test() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![Default::default(); 2],
+            output: vec![],
+        };
+        tx.predict_weight(&[SpendType::P2wpkh]);
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn test_txn_encode_decode() {
@@ -1388,7 +2005,9 @@ mod tests {
         }).is_err());
 
         // test that we get a failure if we corrupt a signature
-        spending.input[1].witness[0][10] = 42;
+        let mut witness = spending.input[1].witness.to_vec();
+        witness[0][10] = 42;
+        spending.input[1].witness = Witness::from_vec(witness);
         match spending.verify(|point: &OutPoint| {
             if let Some(tx) = spent3.remove(&point.txid) {
                 return tx.output.get(point.vout as usize).cloned();
@@ -1411,6 +2030,35 @@ mod benches {
 
     const SOME_TX: &'static str = "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000";
 
+    /// A transaction spending 100 P2WPKH outputs, serialized by hand
+    fn segwit_tx_bytes() -> Vec<u8> {
+        let mut raw = vec![2, 0, 0, 0, 0, 1, 100];
+        for i in 0..100 {
+            raw.extend_from_slice(&[i; 32]);
+            raw.extend_from_slice(&[0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        }
+        raw.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 22, 0, 20]);
+        raw.extend_from_slice(&[0; 20]);
+        for _ in 0..100 {
+            raw.extend_from_slice(&[2, 72]);
+            raw.extend_from_slice(&[0x30; 72]);
+            raw.push(33);
+            raw.extend_from_slice(&[0x02; 33]);
+        }
+        raw.extend_from_slice(&[0, 0, 0, 0]);
+        raw
+    }
+
+    #[bench]
+    pub fn bench_segwit_transaction_deserialize(bh: &mut Bencher) {
+        let raw_tx = segwit_tx_bytes();
+
+        bh.iter(|| {
+            let tx: Transaction = deserialize(&raw_tx).unwrap();
+            black_box(&tx);
+        });
+    }
+
     #[bench]
     pub fn bench_transaction_get_size(bh: &mut Bencher) {
         let raw_tx = Vec::from_hex(SOME_TX).unwrap();
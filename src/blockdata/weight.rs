@@ -0,0 +1,262 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Weight units
+//!
+//! The weight of transactions and blocks, as defined by BIP141. Non-witness
+//! data weighs four weight units per byte and witness data one, which makes
+//! a virtual byte four weight units.
+//!
+
+use std::{fmt, iter, ops};
+
+use blockdata::constants::WITNESS_SCALE_FACTOR;
+
+#[cfg(feature = "serde")] use serde;
+
+/// A weight, in weight units
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Weight(u64);
+
+impl Weight {
+    /// Zero weight units
+    pub const ZERO: Weight = Weight(0);
+
+    /// The maximum weight of a block
+    pub const MAX_BLOCK: Weight = Weight(4_000_000);
+
+    /// Creates a weight of `wu` weight units
+    pub fn from_wu(wu: u64) -> Weight {
+        Weight(wu)
+    }
+
+    /// Creates a weight of `vb` virtual bytes, returning `None` on overflow
+    pub fn from_vb(vb: u64) -> Option<Weight> {
+        vb.checked_mul(WITNESS_SCALE_FACTOR as u64).map(Weight)
+    }
+
+    /// The weight of `bytes` bytes of non-witness data
+    ///
+    /// # Panics
+    ///
+    /// On overflow, see [Weight::checked_from_non_witness_data_size].
+    pub fn from_non_witness_data_size(bytes: u64) -> Weight {
+        Weight::checked_from_non_witness_data_size(bytes).expect("Weight multiplication error")
+    }
+
+    /// The weight of `bytes` bytes of non-witness data, returning `None` on
+    /// overflow
+    pub fn checked_from_non_witness_data_size(bytes: u64) -> Option<Weight> {
+        bytes.checked_mul(WITNESS_SCALE_FACTOR as u64).map(Weight)
+    }
+
+    /// The weight of `bytes` bytes of witness data
+    pub fn from_witness_data_size(bytes: u64) -> Weight {
+        Weight(bytes)
+    }
+
+    /// The weight in weight units
+    pub fn to_wu(self) -> u64 {
+        self.0
+    }
+
+    /// The weight in virtual bytes, rounded down
+    pub fn to_vbytes_floor(self) -> u64 {
+        self.0 / WITNESS_SCALE_FACTOR as u64
+    }
+
+    /// The weight in virtual bytes, rounded up like Bitcoin Core does when
+    /// computing virtual sizes
+    pub fn to_vbytes_ceil(self) -> u64 {
+        let scale = WITNESS_SCALE_FACTOR as u64;
+        self.0 / scale + if self.0 % scale == 0 { 0 } else { 1 }
+    }
+
+    /// Checked addition.
+    /// Returns [None] if overflow occurred.
+    pub fn checked_add(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_add(rhs.0).map(Weight)
+    }
+
+    /// Checked subtraction.
+    /// Returns [None] if overflow occurred.
+    pub fn checked_sub(self, rhs: Weight) -> Option<Weight> {
+        self.0.checked_sub(rhs.0).map(Weight)
+    }
+
+    /// Checked multiplication.
+    /// Returns [None] if overflow occurred.
+    pub fn checked_mul(self, rhs: u64) -> Option<Weight> {
+        self.0.checked_mul(rhs).map(Weight)
+    }
+
+    /// Checked integer division.
+    /// Returns [None] if `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<Weight> {
+        self.0.checked_div(rhs).map(Weight)
+    }
+}
+
+impl From<Weight> for u64 {
+    fn from(weight: Weight) -> u64 {
+        weight.0
+    }
+}
+
+impl fmt::Debug for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Weight({} wu)", self.0)
+    }
+}
+
+impl fmt::Display for Weight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} wu", self.0)
+    }
+}
+
+impl ops::Add for Weight {
+    type Output = Weight;
+
+    fn add(self, rhs: Weight) -> Self::Output {
+        self.checked_add(rhs).expect("Weight addition error")
+    }
+}
+
+impl ops::AddAssign for Weight {
+    fn add_assign(&mut self, other: Weight) {
+        *self = *self + other
+    }
+}
+
+impl ops::Sub for Weight {
+    type Output = Weight;
+
+    fn sub(self, rhs: Weight) -> Self::Output {
+        self.checked_sub(rhs).expect("Weight subtraction error")
+    }
+}
+
+impl ops::SubAssign for Weight {
+    fn sub_assign(&mut self, other: Weight) {
+        *self = *self - other
+    }
+}
+
+impl ops::Mul<u64> for Weight {
+    type Output = Weight;
+
+    fn mul(self, rhs: u64) -> Self::Output {
+        self.checked_mul(rhs).expect("Weight multiplication error")
+    }
+}
+
+impl ops::MulAssign<u64> for Weight {
+    fn mul_assign(&mut self, rhs: u64) {
+        *self = *self * rhs
+    }
+}
+
+impl ops::Div<u64> for Weight {
+    type Output = Weight;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        self.checked_div(rhs).expect("Weight division error")
+    }
+}
+
+impl ops::DivAssign<u64> for Weight {
+    fn div_assign(&mut self, rhs: u64) {
+        *self = *self / rhs
+    }
+}
+
+impl iter::Sum for Weight {
+    fn sum<I: Iterator<Item = Weight>>(iter: I) -> Weight {
+        iter.fold(Weight::ZERO, ops::Add::add)
+    }
+}
+
+/// Serializes as a number of weight units
+#[cfg(feature = "serde")]
+impl serde::Serialize for Weight {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Weight {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Weight, D::Error> {
+        Ok(Weight(serde::Deserialize::deserialize(d)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_test() {
+        assert_eq!(Weight::from_vb(10), Some(Weight::from_wu(40)));
+        assert_eq!(Weight::from_vb(u64::max_value() / 4 + 1), None);
+        assert_eq!(Weight::from_non_witness_data_size(10), Weight::from_wu(40));
+        assert_eq!(Weight::checked_from_non_witness_data_size(10), Some(Weight::from_wu(40)));
+        assert_eq!(Weight::checked_from_non_witness_data_size(u64::max_value() / 4 + 1), None);
+        assert_eq!(Weight::from_witness_data_size(10), Weight::from_wu(10));
+        assert_eq!(Weight::from_wu(41).to_vbytes_floor(), 10);
+        assert_eq!(Weight::from_wu(41).to_vbytes_ceil(), 11);
+        assert_eq!(Weight::from_wu(40).to_vbytes_ceil(), 10);
+        assert_eq!(Weight::from_wu(u64::max_value()).to_vbytes_ceil(), u64::max_value() / 4 + 1);
+        assert_eq!(u64::from(Weight::MAX_BLOCK), 4_000_000);
+        assert_eq!(Weight::from_wu(7).to_string(), "7 wu");
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let max = Weight::from_wu(u64::max_value());
+        assert_eq!(Weight::from_wu(3) + Weight::from_wu(4), Weight::from_wu(7));
+        assert_eq!(Weight::from_wu(4) - Weight::from_wu(3), Weight::from_wu(1));
+        assert_eq!(Weight::from_wu(4) * 3, Weight::from_wu(12));
+        assert_eq!(Weight::from_wu(13) / 3, Weight::from_wu(4));
+        assert_eq!(max.checked_add(Weight::from_wu(1)), None);
+        assert_eq!(Weight::ZERO.checked_sub(Weight::from_wu(1)), None);
+        assert_eq!(max.checked_mul(2), None);
+        assert_eq!(max.checked_div(0), None);
+        assert_eq!(vec![Weight::from_wu(1), Weight::from_wu(2)].into_iter().sum::<Weight>(), Weight::from_wu(3));
+
+        let mut w = Weight::from_wu(10);
+        w += Weight::from_wu(2);
+        w -= Weight::from_wu(4);
+        w *= 3;
+        w /= 2;
+        assert_eq!(w, Weight::from_wu(12));
+    }
+
+    #[test]
+    #[should_panic(expected = "Weight multiplication error")]
+    fn non_witness_data_size_overflow_test() {
+        Weight::from_non_witness_data_size(u64::max_value() / 4 + 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        use serde_json;
+
+        let weight = Weight::from_wu(565);
+        assert_eq!(serde_json::to_string(&weight).unwrap(), "565");
+        assert_eq!(serde_json::from_str::<Weight>("565").unwrap(), weight);
+    }
+}
@@ -0,0 +1,488 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Witness
+//!
+//! This module contains the [`Witness`] struct and related methods to operate on it
+//!
+
+use std::{cmp, fmt, io, ops};
+
+#[cfg(feature = "serde")] use serde;
+
+use consensus::encode::{self, MAX_VEC_SIZE};
+use consensus::{Decodable, Encodable};
+use util::endian;
+use util::taproot::TAPROOT_ANNEX_PREFIX;
+use VarInt;
+
+/// The witness of a transaction input: a list of byte arrays.
+///
+/// Rather than in one allocation per element, the elements are stored one
+/// after the other in a single buffer, serialized as they are in the
+/// transaction, and followed by an index of their positions in it.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Witness {
+    /// The serialized elements, without the count of elements, followed by
+    /// the position of each of them as a 4-byte little-endian integer
+    content: Vec<u8>,
+
+    /// Number of elements in the witness
+    witness_elements: usize,
+
+    /// Position in `content` of the index of the elements
+    indices_start: usize,
+}
+
+/// An iterator over the elements of a [`Witness`]
+pub struct Iter<'a> {
+    witness: &'a Witness,
+    next: usize,
+}
+
+impl Witness {
+    /// Creates an empty witness
+    pub fn new() -> Witness {
+        Witness::default()
+    }
+
+    /// Creates a witness from a list of elements
+    pub fn from_vec(vec: Vec<Vec<u8>>) -> Witness {
+        let mut witness = Witness::new();
+        for elem in vec {
+            witness.push(elem);
+        }
+        witness
+    }
+
+    /// Copies the elements of the witness into a list
+    pub fn to_vec(&self) -> Vec<Vec<u8>> {
+        self.iter().map(|elem| elem.to_vec()).collect()
+    }
+
+    /// Returns `true` if the witness has no elements
+    pub fn is_empty(&self) -> bool {
+        self.witness_elements == 0
+    }
+
+    /// Number of elements in the witness
+    pub fn len(&self) -> usize {
+        self.witness_elements
+    }
+
+    /// Size of the witness once serialized, including the count of elements
+    pub fn serialized_len(&self) -> usize {
+        VarInt(self.witness_elements as u64).len() + self.indices_start
+    }
+
+    /// Removes all the elements of the witness
+    pub fn clear(&mut self) {
+        self.content.clear();
+        self.witness_elements = 0;
+        self.indices_start = 0;
+    }
+
+    /// Appends an element to the witness
+    pub fn push<T: AsRef<[u8]>>(&mut self, new_element: T) {
+        let new_element = new_element.as_ref();
+        let element_len_varint = VarInt(new_element.len() as u64);
+        let previous_content_end = self.indices_start;
+        let new_item_total_len = element_len_varint.len() + new_element.len();
+        let content_len = self.content.len();
+        self.content.resize(content_len + new_item_total_len + 4, 0);
+        // Moves the index after the room for the new element
+        self.content[previous_content_end..].rotate_right(new_item_total_len);
+        self.indices_start += new_item_total_len;
+        let elements = self.witness_elements;
+        self.write_position(elements, previous_content_end);
+
+        let end_varint = previous_content_end + element_len_varint.len();
+        element_len_varint.consensus_encode(&mut self.content[previous_content_end..end_varint])
+            .expect("writes to a large enough slice");
+        self.content[end_varint..end_varint + new_element.len()].copy_from_slice(new_element);
+        self.witness_elements += 1;
+    }
+
+    /// Returns an iterator over the elements of the witness
+    pub fn iter(&self) -> Iter {
+        Iter {
+            witness: self,
+            next: 0,
+        }
+    }
+
+    /// Returns the `index`th element of the witness, or `None` if there
+    /// are not as many
+    pub fn nth(&self, index: usize) -> Option<&[u8]> {
+        if index >= self.witness_elements {
+            return None;
+        }
+        let pos = self.position(index);
+        let mut slice = &self.content[pos..self.indices_start];
+        let element_len = VarInt::consensus_decode(&mut slice).expect("elements are well-formed").0 as usize;
+        Some(&slice[..element_len])
+    }
+
+    /// Returns the last element of the witness, if any
+    pub fn last(&self) -> Option<&[u8]> {
+        self.nth_from_end(1)
+    }
+
+    /// Returns the second to last element of the witness, if any
+    pub fn second_to_last(&self) -> Option<&[u8]> {
+        self.nth_from_end(2)
+    }
+
+    /// Returns the annex of a taproot witness, if any.
+    ///
+    /// As per BIP341, this is the last element when there are at least two
+    /// of them and it starts with [TAPROOT_ANNEX_PREFIX]. Whether the
+    /// witness spends a taproot output isn't checked.
+    pub fn taproot_annex(&self) -> Option<&[u8]> {
+        match self.last() {
+            Some(last) if self.witness_elements > 1 && last.first() == Some(&TAPROOT_ANNEX_PREFIX) => Some(last),
+            _ => None,
+        }
+    }
+
+    /// Returns the control block of a taproot script path spend, if the
+    /// witness has enough elements to be one: the last one, ignoring the annex
+    pub fn taproot_control_block(&self) -> Option<&[u8]> {
+        self.script_path_element(1)
+    }
+
+    /// Returns the leaf script of a taproot script path spend, if the
+    /// witness has enough elements to be one: the one before the control
+    /// block, ignoring the annex
+    pub fn tapscript(&self) -> Option<&[u8]> {
+        self.script_path_element(2)
+    }
+
+    /// The `n`th element from the end of a script path spend, starting at 1
+    fn script_path_element(&self, n: usize) -> Option<&[u8]> {
+        let annex = if self.taproot_annex().is_some() { 1 } else { 0 };
+        if self.witness_elements < 2 + annex {
+            return None;
+        }
+        self.nth_from_end(n + annex)
+    }
+
+    /// The `n`th element from the end, starting at 1
+    fn nth_from_end(&self, n: usize) -> Option<&[u8]> {
+        if n > self.witness_elements {
+            return None;
+        }
+        self.nth(self.witness_elements - n)
+    }
+
+    /// Position in `content` of the `index`th element
+    fn position(&self, index: usize) -> usize {
+        let start = self.indices_start + 4 * index;
+        endian::slice_to_u32_le(&self.content[start..start + 4]) as usize
+    }
+
+    fn write_position(&mut self, index: usize, position: usize) {
+        let start = self.indices_start + 4 * index;
+        self.content[start..start + 4].copy_from_slice(&endian::u32_to_array_le(position as u32));
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let elem = self.witness.nth(self.next);
+        if elem.is_some() {
+            self.next += 1;
+        }
+        elem
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.witness.witness_elements - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+impl<'a> IntoIterator for &'a Witness {
+    type Item = &'a [u8];
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl ops::Index<usize> for Witness {
+    type Output = [u8];
+
+    fn index(&self, index: usize) -> &[u8] {
+        self.nth(index).expect("out of bounds witness element")
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Witness {
+    fn from(vec: Vec<Vec<u8>>) -> Witness {
+        Witness::from_vec(vec)
+    }
+}
+
+impl fmt::Debug for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Encodable for Witness {
+    fn consensus_encode<S: io::Write>(&self, mut s: S) -> Result<usize, io::Error> {
+        let len = VarInt(self.witness_elements as u64).consensus_encode(&mut s)?;
+        s.write_all(&self.content[..self.indices_start])?;
+        Ok(len + self.indices_start)
+    }
+}
+
+impl Decodable for Witness {
+    fn consensus_decode<D: io::Read>(mut d: D) -> Result<Self, encode::Error> {
+        let witness_elements = VarInt::consensus_decode(&mut d)?.0 as usize;
+        // The index is written first, and moved after the elements at the end
+        let index_size = witness_elements.saturating_mul(4);
+        if index_size > MAX_VEC_SIZE {
+            return Err(encode::Error::OversizedVectorAllocation {
+                requested: index_size,
+                max: MAX_VEC_SIZE,
+            });
+        }
+        // The buffer grows by doubling, which takes fewer reallocations than
+        // growing it to fit each element, and is truncated at the end
+        let mut content = vec![0u8; index_size + 128];
+        let mut cursor = index_size;
+        for i in 0..witness_elements {
+            let element_len = VarInt::consensus_decode(&mut d)?.0 as usize;
+            let element_len_varint = VarInt(element_len as u64);
+            let required = cursor
+                .saturating_add(element_len_varint.len())
+                .saturating_add(element_len);
+            if required > MAX_VEC_SIZE {
+                return Err(encode::Error::OversizedVectorAllocation {
+                    requested: required,
+                    max: MAX_VEC_SIZE,
+                });
+            }
+            content[4 * i..4 * i + 4].copy_from_slice(&endian::u32_to_array_le((cursor - index_size) as u32));
+            if required > content.len() {
+                let new_len = cmp::max(required, content.len().saturating_mul(2));
+                content.resize(new_len, 0);
+            }
+            let end_varint = cursor + element_len_varint.len();
+            element_len_varint.consensus_encode(&mut content[cursor..end_varint])?;
+            d.read_exact(&mut content[end_varint..required])?;
+            cursor = required;
+        }
+        content.truncate(cursor);
+        content.rotate_left(index_size);
+        let indices_start = cursor - index_size;
+        Ok(Witness {
+            content: content,
+            witness_elements: witness_elements,
+            indices_start: indices_start,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Witness {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(Some(self.witness_elements))?;
+        for elem in self.iter() {
+            seq.serialize_element(elem)?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Witness {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let vec: Vec<Vec<u8>> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Witness::from_vec(vec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use consensus::encode::{deserialize, serialize};
+    use hashes::hex::FromHex;
+
+    #[test]
+    fn witness_push_test() {
+        let mut witness = Witness::default();
+        assert_eq!(witness.last(), None);
+        assert_eq!(witness.second_to_last(), None);
+        assert_eq!(witness.nth(0), None);
+        assert_eq!(serialize(&witness), vec![0]);
+
+        witness.push([0u8]);
+        assert_eq!(witness.len(), 1);
+        assert_eq!(witness.last(), Some(&[0u8][..]));
+        assert_eq!(witness.second_to_last(), None);
+        assert_eq!(serialize(&witness), vec![1, 1, 0]);
+
+        witness.push(vec![2u8, 3]);
+        assert_eq!(witness.len(), 2);
+        assert_eq!(witness.last(), Some(&[2u8, 3][..]));
+        assert_eq!(witness.second_to_last(), Some(&[0u8][..]));
+        assert_eq!(&witness[0], &[0u8][..]);
+        assert_eq!(&witness[1], &[2u8, 3][..]);
+        assert_eq!(witness.nth(2), None);
+        assert_eq!(serialize(&witness), vec![2, 1, 0, 2, 2, 3]);
+        assert_eq!(witness.serialized_len(), 6);
+        assert_eq!(witness.to_vec(), vec![vec![0u8], vec![2u8, 3]]);
+        assert_eq!(witness.iter().len(), 2);
+        assert_eq!(witness, Witness::from_vec(vec![vec![0u8], vec![2u8, 3]]));
+        assert_eq!(format!("{:?}", witness), "[[0], [2, 3]]");
+
+        let big = vec![0xaau8; 300];
+        witness.push(&big);
+        assert_eq!(witness.last(), Some(&big[..]));
+        assert_eq!(witness.serialized_len(), 6 + 3 + 300);
+
+        witness.clear();
+        assert!(witness.is_empty());
+        assert_eq!(witness, Witness::default());
+    }
+
+    #[test]
+    fn witness_encode_decode_test() {
+        let hex = "0247304402204ef5d5a31d4ab7b0e3f4c6a3ff8ee8e02cb8ae8feb13b89d21ad60a2d5c3d6a602200a73d85bc3c9c4b5e6b1f0c5c27dbd1a97c1a0ab2d3ac3fc3f8c4c1bd11cb96a012102da1bf6b2ca7bb46f9cbc7c8a28cb7b5f23e3b2b5df6d2c86ae7ea3a7ac0e5e5b";
+        let bytes = Vec::<u8>::from_hex(hex).unwrap();
+        let witness: Witness = deserialize(&bytes).unwrap();
+        assert_eq!(witness.len(), 2);
+        assert_eq!(witness[0].len(), 71);
+        assert_eq!(witness[1].len(), 33);
+        assert_eq!(witness.second_to_last(), Some(&bytes[2..73]));
+        assert_eq!(witness.last(), Some(&bytes[74..]));
+        assert_eq!(witness.serialized_len(), bytes.len());
+        assert_eq!(serialize(&witness), bytes);
+        assert_eq!(witness, Witness::from_vec(witness.to_vec()));
+
+        // Oversized element
+        assert!(deserialize::<Witness>(&[1, 0xfe, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // Missing element
+        assert!(deserialize::<Witness>(&[2, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn witness_taproot_test() {
+        let sig = vec![1u8; 64];
+        let script = vec![0x51u8];
+        let control = vec![0xc0u8; 33];
+        let annex = vec![TAPROOT_ANNEX_PREFIX, 1];
+
+        let key_path = Witness::from_vec(vec![sig.clone()]);
+        assert_eq!(key_path.taproot_annex(), None);
+        assert_eq!(key_path.tapscript(), None);
+        assert_eq!(key_path.taproot_control_block(), None);
+
+        let key_path = Witness::from_vec(vec![sig.clone(), annex.clone()]);
+        assert_eq!(key_path.taproot_annex(), Some(&annex[..]));
+        assert_eq!(key_path.tapscript(), None);
+
+        let script_path = Witness::from_vec(vec![sig.clone(), script.clone(), control.clone()]);
+        assert_eq!(script_path.taproot_annex(), None);
+        assert_eq!(script_path.tapscript(), Some(&script[..]));
+        assert_eq!(script_path.taproot_control_block(), Some(&control[..]));
+
+        let script_path = Witness::from_vec(vec![script.clone(), control.clone(), annex.clone()]);
+        assert_eq!(script_path.taproot_annex(), Some(&annex[..]));
+        assert_eq!(script_path.tapscript(), Some(&script[..]));
+        assert_eq!(script_path.taproot_control_block(), Some(&control[..]));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn witness_serde_test() {
+        use serde_json;
+
+        let witness = Witness::from_vec(vec![vec![1u8, 2], vec![], vec![3u8]]);
+        let json = serde_json::to_string(&witness).unwrap();
+        assert_eq!(json, "[[1,2],[],[3]]");
+        let des: Witness = serde_json::from_str(&json).unwrap();
+        assert_eq!(des, witness);
+    }
+}
+
+#[cfg(all(test, feature = "unstable"))]
+mod benches {
+    use super::Witness;
+    use consensus::{deserialize, serialize};
+    use test::{black_box, Bencher};
+
+    /// A 2-of-3 multisig P2WSH witness: the dummy, two signatures and the
+    /// witness script
+    fn multisig_witness() -> Vec<Vec<u8>> {
+        vec![vec![], vec![0x30; 72], vec![0x30; 71], vec![0x52; 105]]
+    }
+
+    #[bench]
+    pub fn bench_witness_deserialize(bh: &mut Bencher) {
+        let raw = serialize(&multisig_witness());
+
+        bh.iter(|| {
+            let witness: Witness = deserialize(&raw).unwrap();
+            black_box(&witness);
+        });
+    }
+
+    /// The previous representation, for comparison
+    #[bench]
+    pub fn bench_witness_deserialize_vec(bh: &mut Bencher) {
+        let raw = serialize(&multisig_witness());
+
+        bh.iter(|| {
+            let witness: Vec<Vec<u8>> = deserialize(&raw).unwrap();
+            black_box(&witness);
+        });
+    }
+
+    #[bench]
+    pub fn bench_witness_clone(bh: &mut Bencher) {
+        let witness = Witness::from_vec(multisig_witness());
+
+        bh.iter(|| {
+            black_box(witness.clone());
+        });
+    }
+
+    /// The previous representation, for comparison
+    #[bench]
+    pub fn bench_witness_clone_vec(bh: &mut Bencher) {
+        let witness = multisig_witness();
+
+        bh.iter(|| {
+            black_box(witness.clone());
+        });
+    }
+}
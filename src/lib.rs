@@ -68,6 +68,7 @@ pub mod blockdata;
 pub mod util;
 pub mod consensus;
 pub mod hash_types;
+pub mod policy;
 
 pub use hash_types::*;
 pub use blockdata::block::Block;
@@ -78,6 +79,11 @@ pub use blockdata::transaction::TxIn;
 pub use blockdata::transaction::TxOut;
 pub use blockdata::transaction::OutPoint;
 pub use blockdata::transaction::SigHashType;
+pub use blockdata::witness::Witness;
+pub use blockdata::weight::Weight;
+pub use blockdata::fee_rate::FeeRate;
+pub use blockdata::locktime::LockTime;
+pub use blockdata::locktime::Sequence;
 pub use consensus::encode::VarInt;
 pub use network::constants::Network;
 pub use util::Error;
@@ -0,0 +1,490 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Relay policy
+//!
+//! Checks of transactions against the standardness rules Bitcoin Core applies
+//! before relaying them or accepting them to its mempool, on top of the
+//! consensus rules. A transaction failing them is valid in a block, but most
+//! nodes will reject it when it's broadcast.
+//!
+//! The defaults are those of Bitcoin Core 22.0.
+//!
+//! Output scripts are classified with [Script::classify], which is stricter
+//! than Bitcoin Core's `Solver`: a P2PK, P2TR or multisig script whose keys
+//! have the right sizes but are not valid curve points is non-standard here,
+//! while Bitcoin Core relays it. Such P2PK and P2TR outputs can never be
+//! spent, and multisig ones only by signatures of their valid keys.
+//!
+
+use std::{error, fmt};
+use std::borrow::Borrow;
+
+use blockdata::script::{Script, ScriptType};
+use blockdata::script::interpreter::MAX_SCRIPT_SIZE;
+use blockdata::transaction::{Transaction, TxOut};
+use blockdata::weight::Weight;
+use blockdata::witness::Witness;
+use consensus::encode::VarInt;
+use util::amount::Amount;
+use util::taproot::{TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT};
+
+/// Maximum weight of a standard transaction
+pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
+
+/// Maximum version of a standard transaction
+pub const MAX_STANDARD_TX_VERSION: i32 = 2;
+
+/// Maximum size of a standard `script_sig`, enough for a 15-of-15 multisig
+/// P2SH spend with compressed keys
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+
+/// Maximum size of a standard OP_RETURN output script, holding 80 bytes of
+/// data
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// Maximum number of signature operations of a standard P2SH redeem script
+pub const MAX_P2SH_SIGOPS: usize = 15;
+
+/// Maximum size of a standard P2WSH witness script
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+/// Maximum number of witness elements of a standard P2WSH spend, not
+/// counting the witness script
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+
+/// Maximum size of a witness element of a standard P2WSH spend, not
+/// counting the witness script
+pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+
+/// Maximum size of a witness element of a standard tapscript spend, not
+/// counting the leaf script and control block
+pub const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+/// Default fee rate below which spending an output would cost more than its
+/// value, making it dust
+pub const DUST_RELAY_TX_FEE: DustRelayFee = DustRelayFee(3_000);
+
+/// A fee rate in satoshis per 1000 virtual bytes, the unit in which Bitcoin
+/// Core's dust relay fee is set
+///
+/// Unlike [FeeRate](::blockdata::fee_rate::FeeRate), it represents any rate
+/// Bitcoin Core accepts, and rounds fees down like Bitcoin Core's `GetFee`.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct DustRelayFee(u64);
+
+impl DustRelayFee {
+    /// Creates a fee rate of `sat_kvb` satoshis per 1000 virtual bytes
+    pub fn from_sat_per_kvb(sat_kvb: u64) -> DustRelayFee {
+        DustRelayFee(sat_kvb)
+    }
+
+    /// The fee rate in satoshis per 1000 virtual bytes
+    pub fn to_sat_per_kvb(self) -> u64 {
+        self.0
+    }
+
+    /// The fee of `vb` virtual bytes at this rate, rounded down to a whole
+    /// satoshi, except that a non-zero fee is at least one satoshi.
+    /// Returns [None] if overflow occurred.
+    pub fn fee_vb(self, vb: u64) -> Option<Amount> {
+        match self.0.checked_mul(vb)? {
+            fee if fee > 0 && fee < 1000 => Some(Amount::from_sat(1)),
+            fee => Some(Amount::from_sat(fee / 1000)),
+        }
+    }
+}
+
+impl fmt::Display for DustRelayFee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} sat/kvB", self.0)
+    }
+}
+
+/// The reason a transaction is non-standard
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The transaction version is neither 1 nor [MAX_STANDARD_TX_VERSION]
+    Version(i32),
+    /// The transaction weighs more than [MAX_STANDARD_TX_WEIGHT]
+    Weight(Weight),
+    /// The `script_sig` of the input at this index is larger than
+    /// [MAX_STANDARD_SCRIPTSIG_SIZE]
+    ScriptSigSize(usize),
+    /// The `script_sig` of the input at this index contains other opcodes
+    /// than pushes
+    ScriptSigNotPushOnly(usize),
+    /// The output at this index has a non-standard script, which includes
+    /// bare multisig of more than three keys and OP_RETURN scripts larger
+    /// than [MAX_OP_RETURN_RELAY]
+    NonStandardOutput(usize),
+    /// The output at this index is dust
+    DustOutput(usize),
+    /// The transaction has more than one OP_RETURN output
+    MultipleOpReturns,
+    /// The number of spent outputs differs from the number of inputs
+    SpentOutputsSize,
+    /// The input at this index spends a non-standard output, or a P2SH
+    /// output whose redeem script has more than [MAX_P2SH_SIGOPS] signature
+    /// operations
+    NonStandardInput(usize),
+    /// The input at this index has a non-standard witness
+    NonStandardWitness(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Version(version) => write!(f, "non-standard transaction version {}", version),
+            Error::Weight(weight) => write!(f, "transaction weight {} exceeds the standard maximum", weight),
+            Error::ScriptSigSize(i) => write!(f, "script_sig of input {} is too large", i),
+            Error::ScriptSigNotPushOnly(i) => write!(f, "script_sig of input {} is not push only", i),
+            Error::NonStandardOutput(i) => write!(f, "output {} has a non-standard script", i),
+            Error::DustOutput(i) => write!(f, "output {} is dust", i),
+            Error::MultipleOpReturns => f.write_str("more than one OP_RETURN output"),
+            Error::SpentOutputsSize => f.write_str("number of spent outputs differs from the number of inputs"),
+            Error::NonStandardInput(i) => write!(f, "input {} spends a non-standard output", i),
+            Error::NonStandardWitness(i) => write!(f, "input {} has a non-standard witness", i),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The value below which an output to `script_pubkey` is dust at
+/// `dust_relay_fee`: the fee of the output and of an input spending it, like
+/// Bitcoin Core's `GetDustThreshold`. Unspendable outputs are never dust.
+///
+/// The input is assumed to be a P2PKH spend for non-witness outputs, and a
+/// P2WPKH spend for witness outputs.
+pub fn dust_threshold(script_pubkey: &Script, dust_relay_fee: DustRelayFee) -> u64 {
+    if script_pubkey.is_op_return() || script_pubkey.len() > MAX_SCRIPT_SIZE {
+        return 0;
+    }
+    let output_size = 8 + VarInt(script_pubkey.len() as u64).len() + script_pubkey.len();
+    let input_size = if script_pubkey.is_witness_program() {
+        // Outpoint, empty script_sig and sequence, and a discounted witness
+        // of a signature and a compressed key
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    match dust_relay_fee.fee_vb((output_size + input_size) as u64) {
+        Some(fee) => fee.as_sat(),
+        None => u64::max_value(),
+    }
+}
+
+/// Whether `output` is dust at `dust_relay_fee`, see [dust_threshold]
+pub fn is_dust(output: &TxOut, dust_relay_fee: DustRelayFee) -> bool {
+    output.value < dust_threshold(&output.script_pubkey, dust_relay_fee)
+}
+
+/// Checks that a transaction is standard regardless of the outputs it
+/// spends, like Bitcoin Core's `IsStandardTx`, with outputs below
+/// `dust_relay_fee` counting as dust.
+pub fn check_transaction(tx: &Transaction, dust_relay_fee: DustRelayFee) -> Result<(), Error> {
+    if tx.version < 1 || tx.version > MAX_STANDARD_TX_VERSION {
+        return Err(Error::Version(tx.version));
+    }
+    let weight = tx.weight();
+    if weight > Weight::from_wu(MAX_STANDARD_TX_WEIGHT as u64) {
+        return Err(Error::Weight(weight));
+    }
+    for (i, input) in tx.input.iter().enumerate() {
+        if input.script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            return Err(Error::ScriptSigSize(i));
+        }
+        if !input.script_sig.is_push_only() {
+            return Err(Error::ScriptSigNotPushOnly(i));
+        }
+    }
+    let mut op_returns = 0;
+    for (i, output) in tx.output.iter().enumerate() {
+        match output.script_pubkey.classify() {
+            ScriptType::NonStandard => return Err(Error::NonStandardOutput(i)),
+            ScriptType::Multisig { ref keys, .. } if keys.len() > 3 => {
+                return Err(Error::NonStandardOutput(i));
+            }
+            ScriptType::NullData => {
+                if output.script_pubkey.len() > MAX_OP_RETURN_RELAY {
+                    return Err(Error::NonStandardOutput(i));
+                }
+                op_returns += 1;
+            }
+            _ => {}
+        }
+        if is_dust(output, dust_relay_fee) {
+            return Err(Error::DustOutput(i));
+        }
+    }
+    if op_returns > 1 {
+        return Err(Error::MultipleOpReturns);
+    }
+    Ok(())
+}
+
+/// Checks that the inputs of a transaction spending `spent`, the outputs
+/// spent by each input in order, are standard, like Bitcoin Core's
+/// `AreInputsStandard` and `IsWitnessStandard`.
+///
+/// Expects the `script_sig`s to be push only, as [check_transaction]
+/// requires. Coinbase transactions have no spent outputs and always pass.
+pub fn check_inputs<T: Borrow<TxOut>>(tx: &Transaction, spent: &[T]) -> Result<(), Error> {
+    if tx.is_coin_base() {
+        return Ok(());
+    }
+    if spent.len() != tx.input.len() {
+        return Err(Error::SpentOutputsSize);
+    }
+    for (i, (input, spent)) in tx.input.iter().zip(spent).enumerate() {
+        let script_pubkey = &spent.borrow().script_pubkey;
+        let mut program = script_pubkey.clone();
+        match script_pubkey.classify() {
+            ScriptType::NonStandard | ScriptType::WitnessUnknown { .. } => return Err(Error::NonStandardInput(i)),
+            ScriptType::P2sh(_) => {
+                let redeem_script = match input.script_sig.last_push_only_data() {
+                    Some(data) if !input.script_sig.is_empty() => Script::from(data.to_vec()),
+                    _ => return Err(Error::NonStandardInput(i)),
+                };
+                if redeem_script.count_sigops(true) > MAX_P2SH_SIGOPS {
+                    return Err(Error::NonStandardInput(i));
+                }
+                program = redeem_script;
+            }
+            _ => {}
+        }
+        if input.witness.is_empty() {
+            continue;
+        }
+        // A witness is only standard on a witness program, and within the
+        // limits of its version
+        let standard = if program.is_v0_p2wsh() {
+            check_p2wsh_witness(&input.witness)
+        } else if program.is_v1_p2tr() && !script_pubkey.is_p2sh() {
+            check_taproot_witness(&input.witness)
+        } else {
+            program.is_witness_program()
+        };
+        if !standard {
+            return Err(Error::NonStandardWitness(i));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a non-empty P2WSH witness is within the standard limits
+fn check_p2wsh_witness(witness: &Witness) -> bool {
+    let witness_script = witness.last().expect("non-empty witness");
+    witness_script.len() <= MAX_STANDARD_P2WSH_SCRIPT_SIZE &&
+        witness.len() - 1 <= MAX_STANDARD_P2WSH_STACK_ITEMS &&
+        witness.iter().take(witness.len() - 1).all(|item| item.len() <= MAX_STANDARD_P2WSH_STACK_ITEM_SIZE)
+}
+
+/// Whether a taproot witness is within the standard limits
+fn check_taproot_witness(witness: &Witness) -> bool {
+    // Key path spends need a signature
+    if witness.is_empty() {
+        return false;
+    }
+    // The annex is reserved for future extensions
+    if witness.taproot_annex().is_some() {
+        return false;
+    }
+    match witness.taproot_control_block().map(|control_block| control_block.first()) {
+        Some(Some(&leaf_version)) if leaf_version & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT => {
+            witness.iter().take(witness.len() - 2).all(|item| item.len() <= MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE)
+        }
+        // Script path spends need a control block
+        Some(None) => false,
+        // Key path spends and script path spends of unknown leaf versions
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockdata::opcodes;
+    use blockdata::script::Builder;
+    use blockdata::transaction::{OutPoint, TxIn};
+    use hashes::Hash;
+    use hash_types::{PubkeyHash, ScriptHash, WPubkeyHash, WScriptHash};
+    use util::taproot::TAPROOT_ANNEX_PREFIX;
+
+    fn p2wpkh() -> Script {
+        Script::new_v0_wpkh(&WPubkeyHash::hash(&[1]))
+    }
+
+    fn tx(script_sigs: Vec<Script>, outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: script_sigs.into_iter().map(|script_sig| TxIn {
+                previous_output: OutPoint::new(Default::default(), 0),
+                script_sig: script_sig,
+                sequence: 0xffffffff,
+                witness: Witness::new(),
+            }).collect(),
+            output: outputs,
+        }
+    }
+
+    #[test]
+    fn dust_test() {
+        let fee = DUST_RELAY_TX_FEE;
+        let p2pkh = Script::new_p2pkh(&PubkeyHash::hash(&[1]));
+        let p2sh = Script::new_p2sh(&ScriptHash::hash(&[1]));
+        let p2wsh = Script::new_v0_wsh(&WScriptHash::hash(&[1]));
+        assert_eq!(dust_threshold(&p2pkh, fee), 546);
+        assert_eq!(dust_threshold(&p2sh, fee), 540);
+        assert_eq!(dust_threshold(&p2wpkh(), fee), 294);
+        assert_eq!(dust_threshold(&p2wsh, fee), 330);
+        assert_eq!(dust_threshold(&Script::new_op_return(&[1; 80]), fee), 0);
+        assert_eq!(dust_threshold(&p2pkh, DustRelayFee::from_sat_per_kvb(1000)), 182);
+        assert_eq!(dust_threshold(&p2pkh, DustRelayFee::default()), 0);
+        // The fee is rounded down, at rates which are not whole satoshis per
+        // virtual byte too
+        assert_eq!(dust_threshold(&p2pkh, DustRelayFee::from_sat_per_kvb(1234)), 224);
+        assert_eq!(dust_threshold(&p2wpkh(), DustRelayFee::from_sat_per_kvb(1234)), 120);
+        // But never to zero
+        assert_eq!(dust_threshold(&p2pkh, DustRelayFee::from_sat_per_kvb(1)), 1);
+        assert_eq!(dust_threshold(&p2pkh, DustRelayFee::from_sat_per_kvb(u64::max_value())), u64::max_value());
+        assert_eq!(DUST_RELAY_TX_FEE.to_sat_per_kvb(), 3_000);
+        assert_eq!(DUST_RELAY_TX_FEE.to_string(), "3000 sat/kvB");
+        assert_eq!(p2pkh.dust_value(), 546);
+
+        assert!(is_dust(&TxOut { value: 293, script_pubkey: p2wpkh() }, fee));
+        assert!(!is_dust(&TxOut { value: 294, script_pubkey: p2wpkh() }, fee));
+    }
+
+    #[test]
+    fn check_transaction_test() {
+        let fee = DUST_RELAY_TX_FEE;
+        let output = TxOut { value: 1000, script_pubkey: p2wpkh() };
+        let op_return = TxOut { value: 0, script_pubkey: Script::new_op_return(&[1; 80]) };
+        let mut tx = tx(vec![Builder::new().push_slice(&[1; 72]).into_script()], vec![output.clone(), op_return.clone()]);
+        assert_eq!(check_transaction(&tx, fee), Ok(()));
+
+        tx.version = 3;
+        assert_eq!(check_transaction(&tx, fee), Err(Error::Version(3)));
+        tx.version = 1;
+        assert_eq!(check_transaction(&tx, fee), Ok(()));
+
+        tx.input[0].script_sig = Builder::new().push_opcode(opcodes::all::OP_NOP).into_script();
+        assert_eq!(check_transaction(&tx, fee), Err(Error::ScriptSigNotPushOnly(0)));
+        tx.input[0].script_sig = Script::from(vec![0x6a; MAX_STANDARD_SCRIPTSIG_SIZE + 1]);
+        assert_eq!(check_transaction(&tx, fee), Err(Error::ScriptSigSize(0)));
+        tx.input[0].script_sig = Script::new();
+
+        tx.output[0].value = 293;
+        assert_eq!(check_transaction(&tx, fee), Err(Error::DustOutput(0)));
+        assert_eq!(check_transaction(&tx, DustRelayFee::from_sat_per_kvb(1000)), Ok(()));
+        tx.output[0].value = 1000;
+
+        tx.output[1].script_pubkey = Script::new_op_return(&[1; 81]);
+        assert_eq!(check_transaction(&tx, fee), Err(Error::NonStandardOutput(1)));
+        tx.output[1] = op_return.clone();
+        tx.output.push(op_return);
+        assert_eq!(check_transaction(&tx, fee), Err(Error::MultipleOpReturns));
+        tx.output.pop();
+
+        tx.output[0].script_pubkey = Script::from(vec![opcodes::all::OP_NOP.into_u8()]);
+        assert_eq!(check_transaction(&tx, fee), Err(Error::NonStandardOutput(0)));
+
+        let key = "0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352".parse().unwrap();
+        tx.output[0].script_pubkey = Script::new_multisig(1, &[key; 3]).unwrap();
+        assert_eq!(check_transaction(&tx, fee), Ok(()));
+        tx.output[0].script_pubkey = Script::new_multisig(1, &[key; 4]).unwrap();
+        assert_eq!(check_transaction(&tx, fee), Err(Error::NonStandardOutput(0)));
+        tx.output[0] = output;
+
+        tx.input = vec![tx.input[0].clone(); 2500];
+        match check_transaction(&tx, fee) {
+            Err(Error::Weight(weight)) => assert!(weight > Weight::from_wu(MAX_STANDARD_TX_WEIGHT as u64)),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn check_inputs_test() {
+        let redeem_script = Builder::new().push_opcode(opcodes::all::OP_CHECKSIG).into_script();
+        let p2sh = TxOut { value: 1000, script_pubkey: redeem_script.to_p2sh() };
+        let mut tx = tx(vec![Builder::new().push_slice(&redeem_script[..]).into_script()], vec![]);
+        assert_eq!(check_inputs(&tx, &[&p2sh]), Ok(()));
+        assert_eq!(check_inputs(&tx, &[&p2sh, &p2sh]), Err(Error::SpentOutputsSize));
+
+        // Too many signature operations in the redeem script
+        let redeem_script = Builder::new().push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script();
+        tx.input[0].script_sig = Builder::new().push_slice(&redeem_script[..]).into_script();
+        let p2sh = TxOut { value: 1000, script_pubkey: redeem_script.to_p2sh() };
+        assert_eq!(check_inputs(&tx, &[&p2sh]), Err(Error::NonStandardInput(0)));
+
+        let nonstandard = TxOut { value: 1000, script_pubkey: Script::from(vec![opcodes::all::OP_NOP.into_u8()]) };
+        assert_eq!(check_inputs(&tx, &[&nonstandard]), Err(Error::NonStandardInput(0)));
+
+        // A witness on a non-witness output
+        let p2pkh = TxOut { value: 1000, script_pubkey: Script::new_p2pkh(&PubkeyHash::hash(&[1])) };
+        tx.input[0].script_sig = Script::new();
+        assert_eq!(check_inputs(&tx, &[&p2pkh]), Ok(()));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1]]);
+        assert_eq!(check_inputs(&tx, &[&p2pkh]), Err(Error::NonStandardWitness(0)));
+
+        // P2WSH, native and nested in P2SH
+        let witness_script = Builder::new().push_opcode(opcodes::all::OP_DROP).into_script();
+        let p2wsh = TxOut { value: 1000, script_pubkey: witness_script.to_v0_p2wsh() };
+        let p2sh_p2wsh = TxOut { value: 1000, script_pubkey: p2wsh.script_pubkey.to_p2sh() };
+        let p2sh_script_sig = Builder::new().push_slice(&p2wsh.script_pubkey[..]).into_script();
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 80], witness_script.to_bytes()]);
+        assert_eq!(check_inputs(&tx, &[&p2wsh]), Ok(()));
+        tx.input[0].script_sig = p2sh_script_sig;
+        assert_eq!(check_inputs(&tx, &[&p2sh_p2wsh]), Ok(()));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 81], witness_script.to_bytes()]);
+        assert_eq!(check_inputs(&tx, &[&p2sh_p2wsh]), Err(Error::NonStandardWitness(0)));
+        tx.input[0].script_sig = Script::new();
+        assert_eq!(check_inputs(&tx, &[&p2wsh]), Err(Error::NonStandardWitness(0)));
+        tx.input[0].witness = Witness::from_vec(vec![vec![]; 101]);
+        tx.input[0].witness.push(witness_script.as_bytes());
+        assert_eq!(check_inputs(&tx, &[&p2wsh]), Err(Error::NonStandardWitness(0)));
+        let witness_script = Script::from(vec![opcodes::all::OP_NOP.into_u8(); MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1]);
+        let p2wsh = TxOut { value: 1000, script_pubkey: witness_script.to_v0_p2wsh() };
+        tx.input[0].witness = Witness::from_vec(vec![witness_script.to_bytes()]);
+        assert_eq!(check_inputs(&tx, &[&p2wsh]), Err(Error::NonStandardWitness(0)));
+
+        // Taproot
+        let p2tr = TxOut {
+            value: 1000,
+            script_pubkey: "5120d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d".parse().unwrap(),
+        };
+        let tapscript = vec![opcodes::all::OP_DROP.into_u8(), opcodes::all::OP_PUSHNUM_1.into_u8()];
+        let control_block = vec![TAPROOT_LEAF_TAPSCRIPT; 33];
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 64]]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Ok(()));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 80], tapscript.clone(), control_block.clone()]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Ok(()));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 81], tapscript.clone(), control_block]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Err(Error::NonStandardWitness(0)));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 81], tapscript.clone(), vec![0xc2; 33]]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Ok(()));
+        tx.input[0].witness = Witness::from_vec(vec![tapscript, vec![]]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Err(Error::NonStandardWitness(0)));
+        tx.input[0].witness = Witness::from_vec(vec![vec![1; 64], vec![TAPROOT_ANNEX_PREFIX]]);
+        assert_eq!(check_inputs(&tx, &[&p2tr]), Err(Error::NonStandardWitness(0)));
+        assert!(!check_taproot_witness(&Witness::new()));
+
+        // Coinbase transactions spend nothing
+        tx.input[0].previous_output = OutPoint::null();
+        assert_eq!(check_inputs::<TxOut>(&tx, &[]), Ok(()));
+    }
+}
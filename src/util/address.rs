@@ -42,11 +42,14 @@ use std::error;
 
 use bech32;
 use hashes::Hash;
+use secp256k1::{Secp256k1, Verification};
 use hash_types::{PubkeyHash, WPubkeyHash, ScriptHash, WScriptHash};
 use blockdata::script;
 use network::constants::Network;
 use util::base58;
 use util::ecdsa;
+use util::schnorr::{TweakedPublicKey, UntweakedPublicKey};
+use util::taproot::TapBranchHash;
 
 /// Address error.
 #[derive(Debug, PartialEq)]
@@ -57,6 +60,13 @@ pub enum Error {
     Bech32(bech32::Error),
     /// The bech32 payload was empty
     EmptyBech32Payload,
+    /// The wrong checksum algorithm was used. See BIP-0350.
+    InvalidBech32Variant {
+        /// Bech32 variant that is required by the used Witness version
+        expected: bech32::Variant,
+        /// The actual Bech32 variant encoded in the address representation
+        found: bech32::Variant,
+    },
     /// Script version must be 0 to 16 inclusive
     InvalidWitnessVersion(u8),
     /// The witness program must be between 2 and 40 bytes in length.
@@ -73,6 +83,9 @@ impl fmt::Display for Error {
             Error::Base58(ref e) => write!(f, "base58: {}", e),
             Error::Bech32(ref e) => write!(f, "bech32: {}", e),
             Error::EmptyBech32Payload => write!(f, "the bech32 payload was empty"),
+            Error::InvalidBech32Variant { expected, found } => write!(f,
+                "invalid bech32 checksum variant found {:?} when {:?} was expected", found, expected,
+            ),
             Error::InvalidWitnessVersion(v) => write!(f, "invalid witness script version: {}", v),
             Error::InvalidWitnessProgramLength(l) => write!(f,
                 "the witness program must be between 2 and 40 bytes in length: length={}", l,
@@ -122,6 +135,8 @@ pub enum AddressType {
     P2wpkh,
     /// pay-to-witness-script-hash
     P2wsh,
+    /// pay-to-taproot
+    P2tr,
 }
 
 impl fmt::Display for AddressType {
@@ -131,6 +146,7 @@ impl fmt::Display for AddressType {
             AddressType::P2sh => "p2sh",
             AddressType::P2wpkh => "p2wpkh",
             AddressType::P2wsh => "p2wsh",
+            AddressType::P2tr => "p2tr",
         })
     }
 }
@@ -143,6 +159,7 @@ impl FromStr for AddressType {
             "p2sh" => Ok(AddressType::P2sh),
             "p2wpkh" => Ok(AddressType::P2wpkh),
             "p2wsh" => Ok(AddressType::P2wsh),
+            "p2tr" => Ok(AddressType::P2tr),
             _ => Err(()),
         }
     }
@@ -308,6 +325,29 @@ impl Address {
         }
     }
 
+    /// Create a pay to taproot address from an internal key, committing to
+    /// the merkle root of a script tree or to no script if `merkle_root` is `None`
+    pub fn p2tr<C: Verification>(
+        secp: &Secp256k1<C>,
+        internal_key: UntweakedPublicKey,
+        merkle_root: Option<TapBranchHash>,
+        network: Network,
+    ) -> Address {
+        let (output_key, _) = TweakedPublicKey::from_internal_key(secp, &internal_key, merkle_root);
+        Address::p2tr_tweaked(output_key, network)
+    }
+
+    /// Create a pay to taproot address from an already tweaked output key
+    pub fn p2tr_tweaked(output_key: TweakedPublicKey, network: Network) -> Address {
+        Address {
+            network: network,
+            payload: Payload::WitnessProgram {
+                version: bech32::u5::try_from_u8(1).expect("1<32"),
+                program: output_key.serialize().to_vec(),
+            },
+        }
+    }
+
     /// Get the address type of the address.
     /// None if unknown or non-standard.
     pub fn address_type(&self) -> Option<AddressType> {
@@ -318,13 +358,14 @@ impl Address {
                 version: ver,
                 program: ref prog,
             } => {
-                // BIP-141 p2wpkh or p2wsh addresses.
+                // BIP-141 p2wpkh or p2wsh addresses and BIP-341 p2tr addresses.
                 match ver.to_u8() {
                     0 => match prog.len() {
                         20 => Some(AddressType::P2wpkh),
                         32 => Some(AddressType::P2wsh),
                         _ => None,
                     },
+                    1 if prog.len() == 32 => Some(AddressType::P2tr),
                     _ => None,
                 }
             }
@@ -384,7 +425,7 @@ impl Display for Address {
                     Network::Testnet | Network::Signet  => "tb",
                     Network::Regtest => "bcrt",
                 };
-                let mut bech32_writer = bech32::Bech32Writer::new(hrp, fmt)?;
+                let mut bech32_writer = bech32::Bech32Writer::new(hrp, bech32_variant(ver), fmt)?;
                 bech32::WriteBase32::write_u5(&mut bech32_writer, ver)?;
                 bech32::ToBase32::write_base32(&prog, &mut bech32_writer)
             }
@@ -392,6 +433,15 @@ impl Display for Address {
     }
 }
 
+/// The checksum variant required for a witness version, see BIP-0350.
+fn bech32_variant(version: bech32::u5) -> bech32::Variant {
+    if version.to_u8() == 0 {
+        bech32::Variant::Bech32
+    } else {
+        bech32::Variant::Bech32m
+    }
+}
+
 /// Extract the bech32 prefix.
 /// Returns the same slice when no prefix is found.
 fn find_bech32_prefix(bech32: &str) -> &str {
@@ -416,7 +466,7 @@ impl FromStr for Address {
         };
         if let Some(network) = bech32_network {
             // decode as bech32
-            let (_, payload) = bech32::decode(s)?;
+            let (_, payload, variant) = bech32::decode(s)?;
             if payload.is_empty() {
                 return Err(Error::EmptyBech32Payload);
             }
@@ -440,6 +490,12 @@ impl FromStr for Address {
                 return Err(Error::InvalidSegwitV0ProgramLength(program.len()));
             }
 
+            // Encoding check
+            let expected = bech32_variant(version);
+            if expected != variant {
+                return Err(Error::InvalidBech32Variant { expected: expected, found: variant });
+            }
+
             return Ok(Address {
                 payload: Payload::WitnessProgram {
                     version: version,
@@ -632,6 +688,31 @@ mod tests {
         roundtrips(&addr);
     }
 
+    #[test]
+    fn test_p2tr() {
+        // BIP341 scriptPubKey test vectors
+        let secp = Secp256k1::verification_only();
+        let internal_key = UntweakedPublicKey::from_str("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+        let addr = Address::p2tr(&secp, internal_key, None, Bitcoin);
+        assert_eq!(&addr.to_string(), "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5");
+        assert_eq!(addr.script_pubkey(), hex_script!("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"));
+        assert_eq!(addr.address_type(), Some(AddressType::P2tr));
+        roundtrips(&addr);
+
+        let internal_key = UntweakedPublicKey::from_str("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap();
+        let merkle_root = TapBranchHash::from_slice(&hex!("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21")).unwrap();
+        let addr = Address::p2tr(&secp, internal_key, Some(merkle_root), Bitcoin);
+        assert_eq!(&addr.to_string(), "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586");
+        assert_eq!(addr.script_pubkey(), hex_script!("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"));
+        assert_eq!(addr.address_type(), Some(AddressType::P2tr));
+        roundtrips(&addr);
+
+        let output_key = TweakedPublicKey::dangerous_assume_tweaked(
+            ::util::schnorr::PublicKey::from_str("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3").unwrap()
+        );
+        assert_eq!(Address::p2tr_tweaked(output_key, Bitcoin), addr);
+    }
+
     #[test]
     fn test_non_existent_segwit_version() {
         let version = 13;
@@ -650,14 +731,17 @@ mod tests {
     }
 
     #[test]
-    fn test_bip173_vectors() {
+    fn test_bip173_350_vectors() {
+        // Test vectors valid under both BIP-173 and BIP-350
         let valid_vectors = [
             ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
             ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
-            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
-            ("BC1SW50QA3JX3S", "6002751e"),
-            ("bc1zw508d6qejxtdg4y5r3zarvaryvg6kdaj", "5210751e76e8199196d454941c45d1b3a323"),
+            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("BC1SW50QGDZ25J", "6002751e"),
+            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", "5210751e76e8199196d454941c45d1b3a323"),
             ("tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy", "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
         ];
         for vector in &valid_vectors {
             let addr: Address = vector.0.parse().unwrap();
@@ -666,6 +750,31 @@ mod tests {
         }
 
         let invalid_vectors = [
+            // BIP-350 invalid human-readable part
+            "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+            // BIP-350 invalid checksums (Bech32 instead of Bech32m and vice versa)
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf",
+            "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+            "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+            // BIP-350 invalid character in checksum
+            "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+            // BIP-350 invalid witness version
+            "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+            // BIP-350 invalid program lengths
+            "bc1pw5dgrnzv",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+            "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P",
+            // BIP-350 mixed case
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+            // BIP-350 invalid padding
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+            "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j",
+            // BIP-350 empty data section
+            "bc1gmk9yu",
+
+            // BIP-173 invalid vectors
             "tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty",
             "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
             "BC13W508D6QEJXTDG4Y5R3ZARVARY0C5XW7KN40WF2",
@@ -675,11 +784,24 @@ mod tests {
             "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7",
             "bc1zw508d6qejxtdg4y5r3zarvaryvqyzf3du",
             "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3pjxtptv",
-            "bc1gmk9yu",
+
+            // BIP-173 valid vectors made obsolete by BIP-350
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+            "BC1SW50QA3JX3S",
+            "bc1zw508d6qejxtdg4y5r3zarvaryvg6kdaj",
         ];
         for vector in &invalid_vectors {
             assert!(vector.parse::<Address>().is_err());
         }
+
+        assert_eq!(
+            Address::from_str("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd"),
+            Err(Error::InvalidBech32Variant { expected: bech32::Variant::Bech32m, found: bech32::Variant::Bech32 })
+        );
+        assert_eq!(
+            Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+            Err(Error::InvalidBech32Variant { expected: bech32::Variant::Bech32, found: bech32::Variant::Bech32m })
+        );
     }
 
     #[test]
@@ -21,6 +21,9 @@ use std::ops;
 use std::str::FromStr;
 use std::cmp::Ordering;
 
+use blockdata::fee_rate::FeeRate;
+use blockdata::weight::Weight;
+
 /// A set of denominations in which amounts can be expressed.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Denomination {
@@ -415,6 +418,20 @@ impl Amount {
         self.0.checked_rem(rhs).map(Amount)
     }
 
+    /// The fee rate this fee pays for `weight`, rounded down to a whole
+    /// satoshi per 1000 weight units.
+    /// Returns [None] if overflow occurred or `weight` is zero.
+    pub fn checked_div_by_weight(self, weight: Weight) -> Option<FeeRate> {
+        self.0.checked_mul(1000)?.checked_div(weight.to_wu()).map(FeeRate::from_sat_per_kwu)
+    }
+
+    /// The fee rate this fee pays for `vsize` virtual bytes, rounded down to
+    /// a whole satoshi per 1000 weight units.
+    /// Returns [None] if overflow occurred or `vsize` is zero.
+    pub fn checked_div_by_vsize(self, vsize: u64) -> Option<FeeRate> {
+        self.checked_div_by_weight(Weight::from_vb(vsize)?)
+    }
+
     /// Convert to a signed amount.
     pub fn to_signed(self) -> Result<SignedAmount, ParseAmountError> {
         if self.as_sat() > SignedAmount::max_value().as_sat() as u64 {
@@ -17,6 +17,10 @@
 //! See Appendix A of the Blockstream sidechains whitepaper
 //! at <http://blockstream.com/sidechains.pdf> for details of
 //! what this does.
+//!
+//! Contract scripts can be paid to as P2SH, P2WSH or P2SH-wrapped P2WSH
+//! outputs, see [create_address], [create_p2wsh_address] and
+//! [create_p2shwsh_address].
 
 #![cfg_attr(not(test), deprecated)]
 
@@ -42,9 +46,9 @@ pub enum Error {
     Secp(secp256k1::Error),
     /// Script parsing error
     Script(script::Error),
-    /// Encountered an uncompressed key in a script we were deserializing. The
-    /// reserialization will compress it which might be surprising so we call
-    /// this an error.
+    /// Encountered an uncompressed key in a script we were deserializing, or
+    /// in the keys of a segwit script. The reserialization will compress it
+    /// which might be surprising so we call this an error.
     UncompressedKey,
     /// Expected a public key when deserializing a script, but we got something else.
     ExpectedKey,
@@ -82,13 +86,18 @@ impl error::Error for Error {
 }
 
 /// An element of a script template
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 enum TemplateElement {
     Op(opcodes::All),
+    /// A data push, including its push opcode and length prefix
+    Push(Vec<u8>),
     Key
 }
 
 /// A script template
+///
+/// Besides keys and opcodes, templates may push data such as the lock times
+/// of `OP_CHECKSEQUENCEVERIFY` or the hashes of hash locks.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Template(Vec<TemplateElement>);
 
@@ -96,21 +105,24 @@ impl Template {
     /// Instantiate a template
     pub fn to_script(&self, keys: &[PublicKey]) -> Result<script::Script, Error> {
         let mut key_index = 0;
-        let mut ret = script::Builder::new();
+        let mut ret = vec![];
         for elem in &self.0 {
-            ret = match *elem {
-                TemplateElement::Op(opcode) => ret.push_opcode(opcode),
+            match *elem {
+                TemplateElement::Op(opcode) => ret.push(opcode.into_u8()),
+                TemplateElement::Push(ref push) => ret.extend_from_slice(push),
                 TemplateElement::Key => {
                     if key_index == keys.len() {
                         return Err(Error::TooFewKeys(key_index));
                     }
+                    let key = keys[key_index].to_bytes();
+                    ret.push(key.len() as u8);
+                    ret.extend(key);
                     key_index += 1;
-                    ret.push_key(&keys[key_index - 1])
                 }
             }
         }
         if key_index == keys.len() {
-            Ok(ret.into_script())
+            Ok(script::Script::from(ret))
         } else {
             Err(Error::TooManyKeys(keys.len()))
         }
@@ -139,15 +151,32 @@ impl Template {
     }
 }
 
+/// Reads a template from script bytes with the byte `0xFE` in place of keys.
+///
+/// Data pushes are kept byte for byte, so non-minimal pushes survive. A push
+/// whose data runs past the end of the slice is read as opcodes.
 impl<'a> From<&'a [u8]> for Template {
     fn from(slice: &'a [u8]) -> Template {
-        Template(slice.iter().map(|&byte| {
-            if byte == PUBKEY {
-                TemplateElement::Key
-            } else {
-                TemplateElement::Op(opcodes::All::from(byte))
-            }
-        }).collect())
+        let mut ret = vec![];
+        let mut pos = 0;
+        while pos < slice.len() {
+            let mut instructions = script::InstructionIndices::from_slice(slice, pos, false);
+            pos = match instructions.next() {
+                Some(Ok((_, script::Instruction::PushBytes(_)))) => {
+                    ret.push(TemplateElement::Push(slice[pos..instructions.pos()].to_vec()));
+                    instructions.pos()
+                }
+                _ => {
+                    ret.push(if slice[pos] == PUBKEY {
+                        TemplateElement::Key
+                    } else {
+                        TemplateElement::Op(opcodes::All::from(slice[pos]))
+                    });
+                    pos + 1
+                }
+            };
+        }
+        Template(ret)
     }
 }
 
@@ -187,6 +216,16 @@ pub fn tweak_secret_key<C: secp256k1::Signing>(secp: &Secp256k1<C>, key: &Privat
     Ok(key)
 }
 
+/// Tweaks the keys with the contract and instantiates the template with them
+pub fn create_script<C: secp256k1::Verification>(secp: &Secp256k1<C>,
+                     contract: &[u8],
+                     keys: &[PublicKey],
+                     template: &Template)
+                     -> Result<script::Script, Error> {
+    let keys = tweak_keys(secp, keys, contract);
+    template.to_script(&keys)
+}
+
 /// Takes a contract, template and key set and runs through all the steps
 pub fn create_address<C: secp256k1::Verification>(secp: &Secp256k1<C>,
                       network: Network,
@@ -194,8 +233,7 @@ pub fn create_address<C: secp256k1::Verification>(secp: &Secp256k1<C>,
                       keys: &[PublicKey],
                       template: &Template)
                       -> Result<address::Address, Error> {
-    let keys = tweak_keys(secp, keys, contract);
-    let script = template.to_script(&keys)?;
+    let script = create_script(secp, contract, keys, template)?;
     Ok(address::Address {
         network: network,
         payload: address::Payload::ScriptHash(
@@ -204,9 +242,47 @@ pub fn create_address<C: secp256k1::Verification>(secp: &Secp256k1<C>,
     })
 }
 
+/// Like [create_address], but pays to the script as a P2WSH output, which
+/// requires the keys to be compressed
+pub fn create_p2wsh_address<C: secp256k1::Verification>(secp: &Secp256k1<C>,
+                            network: Network,
+                            contract: &[u8],
+                            keys: &[PublicKey],
+                            template: &Template)
+                            -> Result<address::Address, Error> {
+    Ok(address::Address::p2wsh(&create_segwit_script(secp, contract, keys, template)?, network))
+}
+
+/// Like [create_address], but pays to the script as a P2WSH output nested in
+/// P2SH, which requires the keys to be compressed
+pub fn create_p2shwsh_address<C: secp256k1::Verification>(secp: &Secp256k1<C>,
+                              network: Network,
+                              contract: &[u8],
+                              keys: &[PublicKey],
+                              template: &Template)
+                              -> Result<address::Address, Error> {
+    Ok(address::Address::p2shwsh(&create_segwit_script(secp, contract, keys, template)?, network))
+}
+
+fn create_segwit_script<C: secp256k1::Verification>(secp: &Secp256k1<C>,
+                        contract: &[u8],
+                        keys: &[PublicKey],
+                        template: &Template)
+                        -> Result<script::Script, Error> {
+    if keys.iter().any(|key| !key.compressed) {
+        return Err(Error::UncompressedKey);
+    }
+    create_script(secp, contract, keys, template)
+}
+
 /// Extract the keys and template from a completed script
+///
+/// Keys must come in runs which end in an `OP_CHECKSIG` or `OP_CHECKSIGVERIFY`,
+/// or in a number followed by an `OP_CHECKMULTISIG` or `OP_CHECKMULTISIGVERIFY`.
+/// Any other push before a run of keys is copied into the template as data,
+/// byte for byte.
 pub fn untemplate(script: &script::Script) -> Result<(Template, Vec<PublicKey>), Error> {
-    let mut ret = script::Builder::new();
+    let mut ret = vec![];
     let mut retkeys = vec![];
 
     #[derive(Copy, Clone, PartialEq, Eq)]
@@ -217,26 +293,26 @@ pub fn untemplate(script: &script::Script) -> Result<(Template, Vec<PublicKey>),
     }
 
     let mut mode = Mode::SeekingKeys;
-    for instruction in script.instructions() {
-        if let Err(e) = instruction {
-            return Err(Error::Script(e));
-        }
-        match instruction.unwrap() {
+    let mut instructions = script.instruction_indices();
+    while let Some(instruction) = instructions.next() {
+        let (pos, instruction) = instruction.map_err(Error::Script)?;
+        match instruction {
             script::Instruction::PushBytes(data) => {
-                let n = data.len();
-                ret = match PublicKey::from_slice(data) {
+                match PublicKey::from_slice(data) {
                     Ok(key) => {
-                        if n == 65 { return Err(Error::UncompressedKey); }
+                        if !key.compressed { return Err(Error::UncompressedKey); }
                         if mode == Mode::SeekingCheckMulti { return Err(Error::ExpectedChecksig); }
                         retkeys.push(key);
                         mode = Mode::CopyingKeys;
-                        ret.push_opcode(opcodes::All::from(PUBKEY))
+                        ret.push(TemplateElement::Key);
                     }
                     Err(_) => {
                         // Arbitrary pushes are only allowed before we've found any keys.
                         // Otherwise we have to wait for a N CHECKSIG pair.
                         match mode {
-                            Mode::SeekingKeys => { ret.push_slice(data) }
+                            Mode::SeekingKeys => {
+                                ret.push(TemplateElement::Push(script[pos..instructions.pos()].to_vec()));
+                            }
                             Mode::CopyingKeys => { return Err(Error::ExpectedKey); },
                             Mode::SeekingCheckMulti => { return Err(Error::ExpectedChecksig); }
                         }
@@ -266,11 +342,11 @@ pub fn untemplate(script: &script::Script) -> Result<(Template, Vec<PublicKey>),
                     // All other opcodes do nothing
                     _ => {}
                 }
-                ret = ret.push_opcode(op);
+                ret.push(TemplateElement::Op(op));
             }
         }
     }
-    Ok((Template::from(&ret[..]), retkeys))
+    Ok((Template(ret), retkeys))
 }
 
 #[cfg(test)]
@@ -386,6 +462,104 @@ mod tests {
         );
     }
 
+    #[test]
+    fn segwit() {
+        let secp = Secp256k1::new();
+        let keys = &alpha_keys!()[..4];
+        let contract = hex!("5032534894ffbf32c1f1c0d3089b27c98fd991d5d7329ebd7d711223e2cde5a9417a1fa3e852c576");
+        // 2-of-3 multisig, or the fourth key after 144 blocks
+        let template = Template::from(&hex!("6352fefefe53ae6702900000b275feac68")[..]);
+
+        let script = create_script(&secp, &contract, keys, &template).unwrap();
+        assert_eq!(
+            create_address(&secp, Network::Bitcoin, &contract, keys, &template).unwrap(),
+            address::Address::p2sh(&script, Network::Bitcoin)
+        );
+        assert_eq!(
+            create_p2wsh_address(&secp, Network::Bitcoin, &contract, keys, &template).unwrap(),
+            address::Address::p2wsh(&script, Network::Bitcoin)
+        );
+        assert_eq!(
+            create_p2shwsh_address(&secp, Network::Bitcoin, &contract, keys, &template).unwrap(),
+            address::Address::p2shwsh(&script, Network::Bitcoin)
+        );
+
+        let mut uncompressed = keys.to_vec();
+        uncompressed[0].compressed = false;
+        assert!(create_address(&secp, Network::Bitcoin, &contract, &uncompressed, &template).is_ok());
+        assert_eq!(
+            create_p2wsh_address(&secp, Network::Bitcoin, &contract, &uncompressed, &template),
+            Err(Error::UncompressedKey)
+        );
+        assert_eq!(
+            create_p2shwsh_address(&secp, Network::Bitcoin, &contract, &uncompressed, &template),
+            Err(Error::UncompressedKey)
+        );
+    }
+
+    #[test]
+    fn untemplate_roundtrip() {
+        let keys = &alpha_keys!()[..4];
+        let templates = [
+            // Timelocked multisig
+            "6352fefefe53ae6702900000b275feac68",
+            // Hash lock, with a hash which contains the key placeholder byte
+            "82012088a820fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe88feac",
+            // The hash lock again, with non-minimal pushes
+            "824c012088a84d2000fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe8852fefe52ae",
+            // A 33-byte push which is not a key
+            "21fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe75feac",
+            // A lock time after the keys, and an empty push
+            "fefe52ae69030065cdb1007c",
+        ];
+        for template in templates.iter() {
+            let template = Template::from(&hex!(template)[..]);
+            let keys = &keys[..template.required_keys()];
+            let script = template.to_script(keys).unwrap();
+            assert_eq!(untemplate(&script).unwrap(), (template.clone(), keys.to_vec()));
+            assert_eq!(untemplate(&script).unwrap().0.to_script(keys).unwrap(), script);
+        }
+
+        let non_key = Template::from(&hex!("21fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe75feac")[..]);
+        let script = non_key.to_script(&keys[..1]).unwrap();
+        assert_eq!(&script[..35], &hex!("21fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe75")[..]);
+        assert_eq!(
+            untemplate(&script).unwrap().0,
+            Template(vec![
+                TemplateElement::Push(hex!("21fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe")),
+                TemplateElement::Op(opcodes::all::OP_DROP),
+                TemplateElement::Key,
+                TemplateElement::Op(opcodes::all::OP_CHECKSIG),
+            ])
+        );
+
+        let hash_lock = Template::from(&hex!("82012088a820fefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefe88feac")[..]);
+        assert_eq!(hash_lock.required_keys(), 1);
+        // A truncated push is read as opcodes
+        assert_eq!(Template::from(&hex!("4cfe")[..]).required_keys(), 1);
+        assert_eq!(Template::from(&hex!("03fefe")[..]).required_keys(), 2);
+    }
+
+    #[test]
+    fn untemplate_shape() {
+        let keys = &alpha_keys!()[..2];
+        let untemplate_hex = |hex: &str| {
+            let template = Template::from(&hex!(hex)[..]);
+            untemplate(&template.to_script(&keys[..template.required_keys()]).unwrap())
+        };
+
+        // Pay to public key hash has no key before its CHECKSIG
+        assert_eq!(untemplate_hex("76a914fefefefefefefefefefefefefefefefefefefefe88ac"), Err(Error::ExpectedKey));
+        // Data between keys
+        assert_eq!(untemplate_hex("fe0105feac"), Err(Error::ExpectedKey));
+        // A CHECKMULTISIG without a number
+        assert_eq!(untemplate_hex("52fefeae"), Err(Error::ExpectedKey));
+        // A number after the keys which is not followed by CHECKMULTISIG
+        assert_eq!(untemplate_hex("52fefe5252ae"), Err(Error::ExpectedChecksig));
+        assert_eq!(untemplate_hex("52fe52feae"), Err(Error::ExpectedChecksig));
+        assert!(untemplate_hex("52fefe52ae").is_ok());
+    }
+
     #[test]
     fn bad_key_number() {
         let alpha_keys = alpha_keys!();
@@ -0,0 +1,943 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Output script descriptors
+//!
+//! Output script descriptors describe the output scripts of a wallet, as
+//! defined by BIP380 to BIP386. This module parses and prints the `pk()`,
+//! `pkh()`, `wpkh()`, `sh()`, `wsh()`, `multi()`, `sortedmulti()`, `tr()`,
+//! `addr()` and `raw()` descriptors, with key origins and extended public
+//! keys, and derives the scripts and addresses they describe.
+//!
+//! ```rust
+//! use std::str::FromStr;
+//! use bitcoin::network::constants::Network;
+//! use bitcoin::secp256k1::Secp256k1;
+//! use bitcoin::util::descriptor::Descriptor;
+//!
+//! let secp = Secp256k1::verification_only();
+//! let desc = Descriptor::from_str(
+//!     "wpkh([d34db33f/84'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*)"
+//! ).unwrap();
+//! assert!(desc.is_ranged());
+//! let address = desc.address(&secp, 0, Network::Bitcoin).unwrap();
+//! ```
+//!
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use hashes::hex::{self, FromHex};
+use secp256k1::{Secp256k1, Verification};
+
+use blockdata::opcodes;
+use blockdata::script::{self, Builder, Script};
+use blockdata::script::interpreter::{MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE};
+use network::constants::Network;
+use util::address::{self, Address};
+use util::bip32::{self, ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint, KeySource};
+use util::ecdsa::PublicKey;
+use util::schnorr;
+use util::taproot::{TaprootBuilder, TaprootBuilderError, TAPROOT_CONTROL_MAX_NODE_COUNT};
+
+/// The characters descriptors may contain, in the order the checksum
+/// algorithm assigns them values
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// The characters of the checksum
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// A descriptor error
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The descriptor has a character outside of the descriptor character set
+    InvalidCharacter(char),
+    /// The checksum is malformed or doesn't match the descriptor
+    InvalidChecksum,
+    /// The descriptor is malformed
+    Syntax(String),
+    /// An unknown script function
+    UnknownFunction(String),
+    /// A script function used where it isn't allowed, e.g. `wpkh()` in `wsh()`
+    InvalidContext(String),
+    /// A key expression that can't be parsed, or a key of a kind not allowed
+    /// where it is used, e.g. an x-only key outside of `tr()`
+    InvalidKey(String),
+    /// An uncompressed key in a segwit or taproot descriptor
+    UncompressedKey,
+    /// A hardened derivation step after an extended public key, which would
+    /// need the private key to derive
+    HardenedDerivation,
+    /// A multisig threshold of zero or of more than the number of keys
+    InvalidThreshold(usize),
+    /// More multisig keys than allowed where the multisig is used
+    TooManyKeys(usize),
+    /// An invalid `addr()` address
+    Address(address::Error),
+    /// An invalid `raw()` script
+    Hex(hex::Error),
+    /// A key derivation error
+    Bip32(bip32::Error),
+    /// A script construction error
+    Script(script::Error),
+    /// An invalid taproot script tree
+    TaprootBuilder(TaprootBuilderError),
+    /// The descriptor describes a script without an address, like bare
+    /// multisig
+    NoAddress,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidCharacter(c) => write!(f, "invalid descriptor character {:?}", c),
+            Error::InvalidChecksum => f.write_str("invalid descriptor checksum"),
+            Error::Syntax(ref s) => write!(f, "invalid descriptor: {}", s),
+            Error::UnknownFunction(ref name) => write!(f, "unknown script function {}()", name),
+            Error::InvalidContext(ref name) => write!(f, "{}() is not allowed here", name),
+            Error::InvalidKey(ref key) => write!(f, "invalid key {}", key),
+            Error::UncompressedKey => f.write_str("uncompressed keys are not allowed in segwit and taproot descriptors"),
+            Error::HardenedDerivation => f.write_str("hardened derivation from an extended public key"),
+            Error::InvalidThreshold(k) => write!(f, "invalid multisig threshold {}", k),
+            Error::TooManyKeys(n) => write!(f, "too many multisig keys: {}", n),
+            Error::Address(ref e) => write!(f, "invalid address: {}", e),
+            Error::Hex(ref e) => write!(f, "invalid script hex: {}", e),
+            Error::Bip32(ref e) => fmt::Display::fmt(e, f),
+            Error::Script(ref e) => fmt::Display::fmt(e, f),
+            Error::TaprootBuilder(ref e) => fmt::Display::fmt(e, f),
+            Error::NoAddress => f.write_str("the descriptor has no address"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Address(ref e) => Some(e),
+            Error::Hex(ref e) => Some(e),
+            Error::Bip32(ref e) => Some(e),
+            Error::Script(ref e) => Some(e),
+            Error::TaprootBuilder(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<bip32::Error> for Error {
+    fn from(e: bip32::Error) -> Error {
+        Error::Bip32(e)
+    }
+}
+
+/// A single public key
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SinglePubKey {
+    /// A public key, which may be uncompressed in legacy descriptors
+    FullKey(PublicKey),
+    /// An x-only public key, only allowed in taproot descriptors
+    XOnly(schnorr::PublicKey),
+}
+
+/// A key expression of a descriptor
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DescriptorPublicKey {
+    /// A single public key
+    Single {
+        /// The master key fingerprint and derivation path of the key
+        origin: Option<KeySource>,
+        /// The key
+        key: SinglePubKey,
+    },
+    /// An extended public key, from which keys are derived
+    XPub {
+        /// The master key fingerprint and derivation path of the extended key
+        origin: Option<KeySource>,
+        /// The extended key
+        xpub: ExtendedPubKey,
+        /// The unhardened derivation path from the extended key
+        path: DerivationPath,
+        /// Whether the derivation index is appended to the path, making the
+        /// descriptor ranged
+        wildcard: bool,
+    },
+}
+
+impl DescriptorPublicKey {
+    /// Whether the key depends on the derivation index
+    pub fn is_ranged(&self) -> bool {
+        match *self {
+            DescriptorPublicKey::Single { .. } => false,
+            DescriptorPublicKey::XPub { wildcard, .. } => wildcard,
+        }
+    }
+
+    /// Derives the public key at `index`, which is ignored by keys that are
+    /// not ranged. X-only keys are returned with an even Y coordinate.
+    pub fn derive_public_key<C: Verification>(&self, secp: &Secp256k1<C>, index: u32) -> Result<PublicKey, Error> {
+        match *self {
+            DescriptorPublicKey::Single { key: SinglePubKey::FullKey(key), .. } => Ok(key),
+            DescriptorPublicKey::Single { key: SinglePubKey::XOnly(ref key), .. } => {
+                let mut ser = [0x02; 33];
+                ser[1..].copy_from_slice(&key.serialize());
+                Ok(PublicKey::from_slice(&ser).expect("x-only keys have an even point"))
+            }
+            DescriptorPublicKey::XPub { ref xpub, ref path, wildcard, .. } => {
+                let mut xpub = xpub.derive_pub(secp, path)?;
+                if wildcard {
+                    xpub = xpub.ckd_pub(secp, ChildNumber::from_normal_idx(index)?)?;
+                }
+                Ok(xpub.public_key)
+            }
+        }
+    }
+
+    /// Derives the x-only public key at `index`
+    pub fn derive_xonly<C: Verification>(&self, secp: &Secp256k1<C>, index: u32) -> Result<schnorr::PublicKey, Error> {
+        match *self {
+            DescriptorPublicKey::Single { key: SinglePubKey::XOnly(key), .. } => Ok(key),
+            _ => Ok(schnorr::PublicKey::from(self.derive_public_key(secp, index)?.key)),
+        }
+    }
+
+    /// Size of the key in a script
+    fn serialized_len(&self) -> usize {
+        match *self {
+            DescriptorPublicKey::Single { key: SinglePubKey::FullKey(key), .. } if !key.compressed => 65,
+            DescriptorPublicKey::Single { key: SinglePubKey::XOnly(_), .. } => 32,
+            _ => 33,
+        }
+    }
+}
+
+impl fmt::Display for DescriptorPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DescriptorPublicKey::Single { ref origin, ref key } => {
+                fmt_origin(origin, f)?;
+                match *key {
+                    SinglePubKey::FullKey(ref key) => fmt::Display::fmt(key, f),
+                    SinglePubKey::XOnly(ref key) => fmt::Display::fmt(key, f),
+                }
+            }
+            DescriptorPublicKey::XPub { ref origin, ref xpub, ref path, wildcard } => {
+                fmt_origin(origin, f)?;
+                fmt::Display::fmt(xpub, f)?;
+                for child in path {
+                    write!(f, "/{}", child)?;
+                }
+                if wildcard {
+                    f.write_str("/*")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn fmt_origin(origin: &Option<KeySource>, f: &mut fmt::Formatter) -> fmt::Result {
+    if let Some((ref fingerprint, ref path)) = *origin {
+        write!(f, "[{}", fingerprint)?;
+        for child in path {
+            write!(f, "/{:#}", child)?;
+        }
+        f.write_str("]")?;
+    }
+    Ok(())
+}
+
+impl FromStr for DescriptorPublicKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DescriptorPublicKey, Error> {
+        let invalid = || Error::InvalidKey(s.to_owned());
+        let (origin, key) = if s.starts_with('[') {
+            let end = s.find(']').ok_or_else(invalid)?;
+            let mut steps = s[1..end].split('/');
+            let fingerprint = steps.next().expect("split yields at least one item");
+            if fingerprint.len() != 8 {
+                return Err(invalid());
+            }
+            let fingerprint = Fingerprint::from_hex(fingerprint).map_err(|_| invalid())?;
+            let path = steps.map(ChildNumber::from_str).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+            (Some((fingerprint, DerivationPath::from(path))), &s[end + 1..])
+        } else {
+            (None, s)
+        };
+
+        if let Ok(bytes) = Vec::<u8>::from_hex(key) {
+            let key = match bytes.len() {
+                32 => SinglePubKey::XOnly(schnorr::PublicKey::from_slice(&bytes).map_err(|_| invalid())?),
+                33 | 65 => SinglePubKey::FullKey(PublicKey::from_slice(&bytes).map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            };
+            return Ok(DescriptorPublicKey::Single {
+                origin: origin,
+                key: key,
+            });
+        }
+
+        let mut steps = key.split('/');
+        let xpub = ExtendedPubKey::from_str(steps.next().expect("split yields at least one item"))
+            .map_err(|_| invalid())?;
+        let mut path = vec![];
+        let mut wildcard = false;
+        for step in steps {
+            if wildcard {
+                return Err(invalid());
+            }
+            match step {
+                "*" => wildcard = true,
+                "*'" | "*h" => return Err(Error::HardenedDerivation),
+                _ => {
+                    let child = ChildNumber::from_str(step).map_err(|_| invalid())?;
+                    if child.is_hardened() {
+                        return Err(Error::HardenedDerivation);
+                    }
+                    path.push(child);
+                }
+            }
+        }
+        Ok(DescriptorPublicKey::XPub {
+            origin: origin,
+            xpub: xpub,
+            path: DerivationPath::from(path),
+            wildcard: wildcard,
+        })
+    }
+}
+
+/// A `multi()` or `sortedmulti()` descriptor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Multi {
+    /// Number of signatures required
+    pub threshold: usize,
+    /// The keys
+    pub keys: Vec<DescriptorPublicKey>,
+    /// Whether the keys are sorted in the script, as in `sortedmulti()`
+    pub sorted: bool,
+}
+
+/// The script tree of a `tr()` descriptor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TapTree {
+    /// A `pk()` leaf, spendable by a signature of the key
+    Leaf(DescriptorPublicKey),
+    /// A branch with its left and right subtrees
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    /// Whether the keys of the tree depend on the derivation index
+    pub fn is_ranged(&self) -> bool {
+        match *self {
+            TapTree::Leaf(ref key) => key.is_ranged(),
+            TapTree::Branch(ref left, ref right) => left.is_ranged() || right.is_ranged(),
+        }
+    }
+
+    /// Derives the leaf scripts at `index`, in depth-first order with their depths
+    pub fn leaves<C: Verification>(&self, secp: &Secp256k1<C>, index: u32) -> Result<Vec<(usize, Script)>, Error> {
+        let mut leaves = vec![];
+        self.push_leaves(secp, index, 0, &mut leaves)?;
+        Ok(leaves)
+    }
+
+    fn push_leaves<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+        depth: usize,
+        leaves: &mut Vec<(usize, Script)>,
+    ) -> Result<(), Error> {
+        match *self {
+            TapTree::Leaf(ref key) => {
+                let script = Builder::new()
+                    .push_slice(&key.derive_xonly(secp, index)?.serialize())
+                    .push_opcode(opcodes::all::OP_CHECKSIG)
+                    .into_script();
+                leaves.push((depth, script));
+            }
+            TapTree::Branch(ref left, ref right) => {
+                left.push_leaves(secp, index, depth + 1, leaves)?;
+                right.push_leaves(secp, index, depth + 1, leaves)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TapTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TapTree::Leaf(ref key) => write!(f, "pk({})", key),
+            TapTree::Branch(ref left, ref right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+/// An output script descriptor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Descriptor {
+    /// `pk(KEY)`: a pay to public key script
+    Pk(DescriptorPublicKey),
+    /// `pkh(KEY)`: a pay to public key hash script
+    Pkh(DescriptorPublicKey),
+    /// `wpkh(KEY)`: a pay to witness public key hash script
+    Wpkh(DescriptorPublicKey),
+    /// `multi(k,KEY_1,...,KEY_n)` or `sortedmulti(...)`: a multisig script
+    Multi(Multi),
+    /// `sh(SCRIPT)`: a pay to script hash of the inner script
+    Sh(Box<Descriptor>),
+    /// `wsh(SCRIPT)`: a pay to witness script hash of the inner script
+    Wsh(Box<Descriptor>),
+    /// `tr(KEY)` or `tr(KEY,TREE)`: a taproot output with the internal key
+    /// and script tree
+    Tr(DescriptorPublicKey, Option<TapTree>),
+    /// `addr(ADDR)`: the script of an address
+    Addr(Address),
+    /// `raw(HEX)`: a script
+    Raw(Script),
+}
+
+impl Descriptor {
+    /// Whether the described scripts depend on the derivation index
+    pub fn is_ranged(&self) -> bool {
+        match *self {
+            Descriptor::Pk(ref key) | Descriptor::Pkh(ref key) | Descriptor::Wpkh(ref key) => key.is_ranged(),
+            Descriptor::Multi(ref multi) => multi.keys.iter().any(DescriptorPublicKey::is_ranged),
+            Descriptor::Sh(ref inner) | Descriptor::Wsh(ref inner) => inner.is_ranged(),
+            Descriptor::Tr(ref key, None) => key.is_ranged(),
+            Descriptor::Tr(ref key, Some(ref tree)) => key.is_ranged() || tree.is_ranged(),
+            Descriptor::Addr(_) | Descriptor::Raw(_) => false,
+        }
+    }
+
+    /// Derives the script described at `index`, which is ignored by
+    /// descriptors that are not ranged
+    pub fn script_pubkey<C: Verification>(&self, secp: &Secp256k1<C>, index: u32) -> Result<Script, Error> {
+        Ok(match *self {
+            Descriptor::Pk(ref key) => Script::new_p2pk(&key.derive_public_key(secp, index)?),
+            Descriptor::Pkh(ref key) => Script::new_p2pkh(&key.derive_public_key(secp, index)?.pubkey_hash()),
+            Descriptor::Wpkh(ref key) => {
+                let hash = key.derive_public_key(secp, index)?.wpubkey_hash().ok_or(Error::UncompressedKey)?;
+                Script::new_v0_wpkh(&hash)
+            }
+            Descriptor::Multi(ref multi) => {
+                let keys = multi.keys.iter()
+                    .map(|key| key.derive_public_key(secp, index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if multi.sorted {
+                    Script::new_sorted_multisig(multi.threshold, &keys)
+                } else {
+                    Script::new_multisig(multi.threshold, &keys)
+                }.map_err(Error::Script)?
+            }
+            Descriptor::Sh(ref inner) => inner.script_pubkey(secp, index)?.to_p2sh(),
+            Descriptor::Wsh(ref inner) => inner.script_pubkey(secp, index)?.to_v0_p2wsh(),
+            Descriptor::Tr(ref key, ref tree) => {
+                let internal_key = key.derive_xonly(secp, index)?;
+                let merkle_root = match *tree {
+                    Some(ref tree) => {
+                        let mut builder = TaprootBuilder::new();
+                        for (depth, script) in tree.leaves(secp, index)? {
+                            builder = builder.add_leaf(depth, script).map_err(Error::TaprootBuilder)?;
+                        }
+                        Some(builder.into_node_info().map_err(Error::TaprootBuilder)?.merkle_root())
+                    }
+                    None => None,
+                };
+                Script::new_v1_p2tr(secp, internal_key, merkle_root)
+            }
+            Descriptor::Addr(ref address) => address.script_pubkey(),
+            Descriptor::Raw(ref script) => script.clone(),
+        })
+    }
+
+    /// Derives the address of the script described at `index` on `network`.
+    ///
+    /// `addr()` descriptors return their address as is. Scripts without an
+    /// address, like P2PK and bare multisig ones, fail with [Error::NoAddress].
+    pub fn address<C: Verification>(&self, secp: &Secp256k1<C>, index: u32, network: Network) -> Result<Address, Error> {
+        match *self {
+            Descriptor::Addr(ref address) => Ok(address.clone()),
+            _ => Address::from_script(&self.script_pubkey(secp, index)?, network).ok_or(Error::NoAddress),
+        }
+    }
+
+    /// Writes the descriptor without its checksum
+    fn fmt_inner(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        match *self {
+            Descriptor::Pk(ref key) => write!(f, "pk({})", key),
+            Descriptor::Pkh(ref key) => write!(f, "pkh({})", key),
+            Descriptor::Wpkh(ref key) => write!(f, "wpkh({})", key),
+            Descriptor::Multi(ref multi) => {
+                write!(f, "{}({}", if multi.sorted { "sortedmulti" } else { "multi" }, multi.threshold)?;
+                for key in &multi.keys {
+                    write!(f, ",{}", key)?;
+                }
+                f.write_str(")")
+            }
+            Descriptor::Sh(ref inner) => {
+                f.write_str("sh(")?;
+                inner.fmt_inner(f)?;
+                f.write_str(")")
+            }
+            Descriptor::Wsh(ref inner) => {
+                f.write_str("wsh(")?;
+                inner.fmt_inner(f)?;
+                f.write_str(")")
+            }
+            Descriptor::Tr(ref key, None) => write!(f, "tr({})", key),
+            Descriptor::Tr(ref key, Some(ref tree)) => write!(f, "tr({},{})", key, tree),
+            Descriptor::Addr(ref address) => write!(f, "addr({})", address),
+            Descriptor::Raw(ref script) => write!(f, "raw({:x})", script),
+        }
+    }
+}
+
+/// Prints the descriptor with its checksum, or without it in the alternate
+/// form `{:#}`
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut desc = String::new();
+        self.fmt_inner(&mut desc)?;
+        if f.alternate() {
+            f.write_str(&desc)
+        } else {
+            let checksum = checksum(&desc).map_err(|_| fmt::Error)?;
+            write!(f, "{}#{}", desc, checksum)
+        }
+    }
+}
+
+/// Parses a descriptor, checking its checksum if it has one
+impl FromStr for Descriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Descriptor, Error> {
+        let desc = match s.find('#') {
+            Some(pos) => {
+                if s[pos + 1..] != checksum(&s[..pos])? {
+                    return Err(Error::InvalidChecksum);
+                }
+                &s[..pos]
+            }
+            None => {
+                checksum(s)?;
+                s
+            }
+        };
+        parse_script(desc, Context::Top)
+    }
+}
+
+/// Computes the checksum of a descriptor, without the `#` separator
+pub fn checksum(desc: &str) -> Result<String, Error> {
+    let mut chk = 1u64;
+    let mut class = 0;
+    let mut class_count = 0;
+    for c in desc.chars() {
+        let pos = INPUT_CHARSET.find(c).ok_or(Error::InvalidCharacter(c))? as u64;
+        chk = checksum_polymod(chk, pos & 31);
+        // The character classes are compressed in groups of three
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            chk = checksum_polymod(chk, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        chk = checksum_polymod(chk, class);
+    }
+    for _ in 0..8 {
+        chk = checksum_polymod(chk, 0);
+    }
+    chk ^= 1;
+    Ok((0..8).map(|i| CHECKSUM_CHARSET[((chk >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+/// Feeds a 5-bit value to the checksum, as a degree 8 BCH code over GF(32)
+fn checksum_polymod(mut chk: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+    let top = chk >> 35;
+    chk = ((chk & 0x7ffffffff) << 5) ^ value;
+    for (i, generator) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            chk ^= generator;
+        }
+    }
+    chk
+}
+
+/// Where a script function is used, which restricts the functions and keys
+/// allowed
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Context {
+    Top,
+    Sh,
+    Wsh,
+    Tap,
+}
+
+fn parse_script(s: &str, ctx: Context) -> Result<Descriptor, Error> {
+    let (name, args) = split_function(s)?;
+    match (name, ctx) {
+        ("pk", _) => Ok(Descriptor::Pk(parse_key(single_arg(name, &args)?, ctx)?)),
+        ("pkh", _) => Ok(Descriptor::Pkh(parse_key(single_arg(name, &args)?, ctx)?)),
+        ("wpkh", Context::Top) | ("wpkh", Context::Sh) => {
+            Ok(Descriptor::Wpkh(parse_key(single_arg(name, &args)?, Context::Wsh)?))
+        }
+        ("multi", _) | ("sortedmulti", _) => parse_multi(name == "sortedmulti", &args, ctx),
+        ("sh", Context::Top) => Ok(Descriptor::Sh(Box::new(parse_script(single_arg(name, &args)?, Context::Sh)?))),
+        ("wsh", Context::Top) | ("wsh", Context::Sh) => {
+            Ok(Descriptor::Wsh(Box::new(parse_script(single_arg(name, &args)?, Context::Wsh)?)))
+        }
+        ("tr", Context::Top) => {
+            if args.len() > 2 {
+                return Err(Error::Syntax(format!("tr() takes one or two arguments, got {}", args.len())));
+            }
+            let key = parse_key(args[0], Context::Tap)?;
+            let tree = match args.get(1) {
+                Some(tree) => Some(parse_tree(tree, 0)?),
+                None => None,
+            };
+            Ok(Descriptor::Tr(key, tree))
+        }
+        ("addr", Context::Top) => {
+            Ok(Descriptor::Addr(Address::from_str(single_arg(name, &args)?).map_err(Error::Address)?))
+        }
+        ("raw", Context::Top) => {
+            Ok(Descriptor::Raw(Script::from(Vec::from_hex(single_arg(name, &args)?).map_err(Error::Hex)?)))
+        }
+        ("wpkh", _) | ("sh", _) | ("wsh", _) | ("tr", _) | ("addr", _) | ("raw", _) => {
+            Err(Error::InvalidContext(name.to_owned()))
+        }
+        _ => Err(Error::UnknownFunction(name.to_owned())),
+    }
+}
+
+fn parse_multi(sorted: bool, args: &[&str], ctx: Context) -> Result<Descriptor, Error> {
+    let (threshold, keys) = args.split_first().expect("split_args yields at least one item");
+    let threshold = threshold.parse::<usize>()
+        .map_err(|_| Error::Syntax(format!("invalid multisig threshold {}", threshold)))?;
+    if keys.is_empty() {
+        return Err(Error::Syntax(format!("{}() takes at least one key", if sorted { "sortedmulti" } else { "multi" })));
+    }
+    let keys = keys.iter().map(|key| parse_key(key, ctx)).collect::<Result<Vec<_>, _>>()?;
+    if keys.len() > MAX_PUBKEYS_PER_MULTISIG as usize {
+        return Err(Error::TooManyKeys(keys.len()));
+    }
+    if threshold == 0 || threshold > keys.len() {
+        return Err(Error::InvalidThreshold(threshold));
+    }
+    match ctx {
+        // Bare multisig is only standard with up to three keys
+        Context::Top if keys.len() > 3 => return Err(Error::TooManyKeys(keys.len())),
+        // The redeem script must fit in a push
+        Context::Sh if keys.iter().map(|key| key.serialized_len() + 1).sum::<usize>() + 3 > MAX_SCRIPT_ELEMENT_SIZE => {
+            return Err(Error::TooManyKeys(keys.len()));
+        }
+        _ => {}
+    }
+    Ok(Descriptor::Multi(Multi {
+        threshold: threshold,
+        keys: keys,
+        sorted: sorted,
+    }))
+}
+
+fn parse_tree(s: &str, depth: usize) -> Result<TapTree, Error> {
+    if depth > TAPROOT_CONTROL_MAX_NODE_COUNT {
+        return Err(Error::TaprootBuilder(TaprootBuilderError::InvalidMerkleTreeDepth(depth)));
+    }
+    if s.starts_with('{') {
+        if !s.ends_with('}') {
+            return Err(Error::Syntax(format!("unbalanced braces in {}", s)));
+        }
+        let branches = split_args(&s[1..s.len() - 1])?;
+        if branches.len() != 2 {
+            return Err(Error::Syntax(format!("script tree branches must have two children: {}", s)));
+        }
+        return Ok(TapTree::Branch(
+            Box::new(parse_tree(branches[0], depth + 1)?),
+            Box::new(parse_tree(branches[1], depth + 1)?),
+        ));
+    }
+    let (name, args) = split_function(s)?;
+    match name {
+        "pk" => Ok(TapTree::Leaf(parse_key(single_arg(name, &args)?, Context::Tap)?)),
+        "pkh" | "wpkh" | "multi" | "sortedmulti" | "sh" | "wsh" | "tr" | "addr" | "raw" => {
+            Err(Error::InvalidContext(name.to_owned()))
+        }
+        _ => Err(Error::UnknownFunction(name.to_owned())),
+    }
+}
+
+/// Parses a key, checking that its kind is allowed in the context
+fn parse_key(s: &str, ctx: Context) -> Result<DescriptorPublicKey, Error> {
+    let key = DescriptorPublicKey::from_str(s)?;
+    match key {
+        DescriptorPublicKey::Single { key: SinglePubKey::FullKey(ref key), .. }
+            if !key.compressed && (ctx == Context::Wsh || ctx == Context::Tap) => Err(Error::UncompressedKey),
+        DescriptorPublicKey::Single { key: SinglePubKey::XOnly(_), .. } if ctx != Context::Tap => {
+            Err(Error::InvalidKey(s.to_owned()))
+        }
+        _ => Ok(key),
+    }
+}
+
+/// Splits `name(args)` into the function name and its arguments
+fn split_function(s: &str) -> Result<(&str, Vec<&str>), Error> {
+    match s.find('(') {
+        Some(open) if s.ends_with(')') => Ok((&s[..open], split_args(&s[open + 1..s.len() - 1])?)),
+        _ => Err(Error::Syntax(format!("expected a script function: {}", s))),
+    }
+}
+
+/// Splits arguments at the commas which are not nested in brackets
+fn split_args(s: &str) -> Result<Vec<&str>, Error> {
+    let unbalanced = || Error::Syntax(format!("unbalanced brackets in {}", s));
+    let mut args = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (pos, c) in s.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth = depth.checked_sub(1).ok_or_else(unbalanced)?,
+            ',' if depth == 0 => {
+                args.push(&s[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(unbalanced());
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+fn single_arg<'a>(name: &str, args: &[&'a str]) -> Result<&'a str, Error> {
+    match *args {
+        [arg] => Ok(arg),
+        _ => Err(Error::Syntax(format!("{}() takes one argument, got {}", name, args.len()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hashes::hex::ToHex;
+    use secp256k1::Secp256k1;
+
+    const XPUB: &str = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+    const KEY1: &str = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+    const KEY2: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    fn key(s: &str) -> PublicKey {
+        PublicKey::from_str(s).unwrap()
+    }
+
+    fn script(desc: &str) -> Script {
+        let secp = Secp256k1::verification_only();
+        Descriptor::from_str(desc).unwrap().script_pubkey(&secp, 0).unwrap()
+    }
+
+    #[test]
+    fn checksum_test() {
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").unwrap(), "02wpgw69");
+        assert_eq!(checksum("raw(deadbeef)\u{e9}"), Err(Error::InvalidCharacter('\u{e9}')));
+
+        let desc = Descriptor::from_str("raw(deadbeef)#89f8spxm").unwrap();
+        assert_eq!(desc, Descriptor::from_str("raw(deadbeef)").unwrap());
+        assert_eq!(desc.to_string(), "raw(deadbeef)#89f8spxm");
+        assert_eq!(format!("{:#}", desc), "raw(deadbeef)");
+        assert_eq!(Descriptor::from_str("raw(deadbeef)#89f8spxn"), Err(Error::InvalidChecksum));
+        assert_eq!(Descriptor::from_str("raw(deadbeef)#89f8spx"), Err(Error::InvalidChecksum));
+        assert_eq!(Descriptor::from_str("raw(deadbeef)#"), Err(Error::InvalidChecksum));
+    }
+
+    #[test]
+    fn single_key_test() {
+        let secp = Secp256k1::verification_only();
+        let pk = key(KEY1);
+        assert_eq!(script(&format!("pk({})", KEY1)), Script::new_p2pk(&pk));
+        assert_eq!(script(&format!("pkh({})", KEY1)), Script::new_p2pkh(&pk.pubkey_hash()));
+        assert_eq!(
+            script(&format!("wpkh({})", KEY2)),
+            Script::new_v0_wpkh(&key(KEY2).wpubkey_hash().unwrap())
+        );
+        assert_eq!(
+            script(&format!("sh(wpkh({}))", KEY1)),
+            Script::new_v0_wpkh(&pk.wpubkey_hash().unwrap()).to_p2sh()
+        );
+
+        let desc = Descriptor::from_str(&format!("sh(wpkh({}))", KEY1)).unwrap();
+        assert_eq!(
+            desc.address(&secp, 0, Network::Bitcoin).unwrap(),
+            Address::p2shwpkh(&pk, Network::Bitcoin).unwrap()
+        );
+        let desc = Descriptor::from_str(&format!("pk({})", KEY1)).unwrap();
+        assert_eq!(desc.address(&secp, 0, Network::Bitcoin), Err(Error::NoAddress));
+        assert!(!desc.is_ranged());
+
+        let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+        assert!(Descriptor::from_str(&format!("pkh({})", uncompressed)).is_ok());
+        assert_eq!(Descriptor::from_str(&format!("wpkh({})", uncompressed)), Err(Error::UncompressedKey));
+        assert_eq!(Descriptor::from_str(&format!("wsh(pk({}))", uncompressed)), Err(Error::UncompressedKey));
+    }
+
+    #[test]
+    fn context_test() {
+        let err = |desc: &str| Descriptor::from_str(desc).unwrap_err();
+        assert_eq!(err(&format!("wsh(wpkh({}))", KEY1)), Error::InvalidContext("wpkh".to_owned()));
+        assert_eq!(err(&format!("sh(sh(pk({})))", KEY1)), Error::InvalidContext("sh".to_owned()));
+        assert_eq!(err("wsh(raw(deadbeef))"), Error::InvalidContext("raw".to_owned()));
+        assert_eq!(err(&format!("foo({})", KEY1)), Error::UnknownFunction("foo".to_owned()));
+        assert_eq!(err(&format!("pk({},{})", KEY1, KEY2)), Error::Syntax("pk() takes one argument, got 2".to_owned()));
+        assert!(Descriptor::from_str(&format!("pk({}", KEY1)).is_err());
+        assert!(Descriptor::from_str(&format!("pk({}))", KEY1)).is_err());
+        assert!(Descriptor::from_str(&format!("pk({})x", KEY1)).is_err());
+        assert!(Descriptor::from_str(&format!("pk({})", &KEY1[2..])).is_err());
+    }
+
+    #[test]
+    fn multisig_test() {
+        let keys = [key(KEY1), key(KEY2)];
+        assert_eq!(
+            script(&format!("multi(1,{},{})", KEY1, KEY2)),
+            Script::new_multisig(1, &keys).unwrap()
+        );
+        assert_eq!(
+            script(&format!("wsh(sortedmulti(2,{},{}))", KEY1, KEY2)),
+            Script::new_sorted_multisig(2, &keys).unwrap().to_v0_p2wsh()
+        );
+        assert_eq!(
+            script(&format!("sh(wsh(multi(2,{},{})))", KEY1, KEY2)),
+            Script::new_multisig(2, &keys).unwrap().to_v0_p2wsh().to_p2sh()
+        );
+
+        let err = |desc: String| Descriptor::from_str(&desc).unwrap_err();
+        assert_eq!(err(format!("multi(0,{})", KEY1)), Error::InvalidThreshold(0));
+        assert_eq!(err(format!("multi(3,{},{})", KEY1, KEY2)), Error::InvalidThreshold(3));
+        assert_eq!(err("multi(1)".to_owned()), Error::Syntax("multi() takes at least one key".to_owned()));
+        assert_eq!(err("sortedmulti(1)".to_owned()), Error::Syntax("sortedmulti() takes at least one key".to_owned()));
+        let four = vec![KEY1; 4].join(",");
+        assert_eq!(err(format!("multi(1,{})", four)), Error::TooManyKeys(4));
+        assert!(Descriptor::from_str(&format!("sh(multi(1,{}))", four)).is_ok());
+        let sixteen = vec![KEY1; 16].join(",");
+        assert_eq!(err(format!("sh(multi(1,{}))", sixteen)), Error::TooManyKeys(16));
+        assert!(Descriptor::from_str(&format!("wsh(multi(1,{}))", sixteen)).is_ok());
+        let twenty_one = vec![KEY1; 21].join(",");
+        assert_eq!(err(format!("wsh(multi(1,{}))", twenty_one)), Error::TooManyKeys(21));
+    }
+
+    #[test]
+    fn xpub_test() {
+        let secp = Secp256k1::verification_only();
+        let xpub = ExtendedPubKey::from_str(XPUB).unwrap();
+        let desc_str = format!("wpkh([d34db33f/84'/0'/0']{}/1/*)", XPUB);
+        let desc = Descriptor::from_str(&desc_str).unwrap();
+        assert!(desc.is_ranged());
+        assert_eq!(format!("{:#}", desc), desc_str);
+        assert_eq!(Descriptor::from_str(&desc.to_string()).unwrap(), desc);
+
+        let path = DerivationPath::from_str("m/1/7").unwrap();
+        let pk = xpub.derive_pub(&secp, &path).unwrap().public_key;
+        assert_eq!(desc.script_pubkey(&secp, 7).unwrap(), Script::new_v0_wpkh(&pk.wpubkey_hash().unwrap()));
+        assert_eq!(desc.address(&secp, 7, Network::Bitcoin).unwrap(), Address::p2wpkh(&pk, Network::Bitcoin).unwrap());
+        assert_eq!(desc.script_pubkey(&secp, 1 << 31), Err(Error::Bip32(bip32::Error::InvalidChildNumber(1 << 31))));
+
+        // Hardened origins are written with apostrophes
+        let desc = Descriptor::from_str(&format!("pkh([d34db33f/44h/0h]{})", XPUB)).unwrap();
+        assert!(!desc.is_ranged());
+        assert_eq!(format!("{:#}", desc), format!("pkh([d34db33f/44'/0']{})", XPUB));
+        assert_eq!(desc.script_pubkey(&secp, 5).unwrap(), Script::new_p2pkh(&xpub.public_key.pubkey_hash()));
+
+        let err = |desc: String| Descriptor::from_str(&desc).unwrap_err();
+        assert_eq!(err(format!("pkh({}/1'/*)", XPUB)), Error::HardenedDerivation);
+        assert_eq!(err(format!("pkh({}/*')", XPUB)), Error::HardenedDerivation);
+        assert_eq!(err(format!("pkh({}/*h)", XPUB)), Error::HardenedDerivation);
+        assert!(Descriptor::from_str(&format!("pkh({}/*/1)", XPUB)).is_err());
+        assert!(Descriptor::from_str(&format!("pkh([d34db33/0]{})", XPUB)).is_err());
+        assert!(Descriptor::from_str(&format!("pkh([d34db33f/x]{})", XPUB)).is_err());
+    }
+
+    #[test]
+    fn taproot_test() {
+        let secp = Secp256k1::verification_only();
+        let xonly = &KEY1[2..];
+        let internal_key = schnorr::PublicKey::from_str(xonly).unwrap();
+        assert_eq!(
+            script(&format!("tr({})", xonly)).to_hex(),
+            "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11"
+        );
+        // Full keys are used as x-only ones
+        assert_eq!(script(&format!("tr({})", KEY1)), script(&format!("tr({})", xonly)));
+
+        let leaf = |key: &str| {
+            let key = schnorr::PublicKey::from_str(key).unwrap();
+            Builder::new().push_slice(&key.serialize()).push_opcode(opcodes::all::OP_CHECKSIG).into_script()
+        };
+        let root = TaprootBuilder::new()
+            .add_leaf(1, leaf(xonly)).unwrap()
+            .add_leaf(2, leaf(&KEY2[2..])).unwrap()
+            .add_leaf(2, leaf(xonly)).unwrap()
+            .into_node_info().unwrap()
+            .merkle_root();
+        let desc_str = format!("tr({},{{pk({}),{{pk({}),pk({})}}}})", xonly, xonly, KEY2, xonly);
+        let desc = Descriptor::from_str(&desc_str).unwrap();
+        assert_eq!(format!("{:#}", desc), desc_str);
+        assert_eq!(desc.script_pubkey(&secp, 0).unwrap(), Script::new_v1_p2tr(&secp, internal_key, Some(root)));
+        assert_eq!(
+            desc.address(&secp, 0, Network::Bitcoin).unwrap(),
+            Address::p2tr(&secp, internal_key, Some(root), Network::Bitcoin)
+        );
+
+        let root = TaprootBuilder::new().add_leaf(0, leaf(&KEY2[2..])).unwrap().into_node_info().unwrap().merkle_root();
+        assert_eq!(
+            script(&format!("tr({},pk({}))", xonly, KEY2)),
+            Script::new_v1_p2tr(&secp, internal_key, Some(root))
+        );
+
+        let err = |desc: String| Descriptor::from_str(&desc).unwrap_err();
+        assert_eq!(err(format!("pk({})", xonly)), Error::InvalidKey(xonly.to_owned()));
+        assert_eq!(err(format!("sh(tr({}))", xonly)), Error::InvalidContext("tr".to_owned()));
+        assert_eq!(err(format!("tr({},pkh({}))", xonly, KEY2)), Error::InvalidContext("pkh".to_owned()));
+        assert!(Descriptor::from_str(&format!("tr({},{{pk({})}})", xonly, KEY2)).is_err());
+        assert!(Descriptor::from_str(&format!("tr({},pk({}),pk({}))", xonly, KEY2, KEY2)).is_err());
+    }
+
+    #[test]
+    fn addr_raw_test() {
+        let secp = Secp256k1::verification_only();
+        let address = Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        let desc = Descriptor::from_str("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)").unwrap();
+        assert_eq!(desc.script_pubkey(&secp, 0).unwrap(), address.script_pubkey());
+        assert_eq!(desc.address(&secp, 0, Network::Testnet).unwrap(), address);
+        assert!(Descriptor::from_str("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5)").is_err());
+
+        let desc = Descriptor::from_str("raw(a9149a1c78a507689f6f54b847ad1cef1e614ee23f1e87)").unwrap();
+        let address = desc.address(&secp, 0, Network::Bitcoin).unwrap();
+        assert_eq!(address.script_pubkey(), desc.script_pubkey(&secp, 0).unwrap());
+        assert_eq!(address.to_string(), "3Fjt6Jb1SqeYZxRYk8oQgtKpXZiCpwCzD1");
+        assert!(Descriptor::from_str("raw(deadbee)").is_err());
+    }
+}
@@ -18,7 +18,7 @@
 
 use hashes::{sha256d, Hash, HashEngine};
 
-use blockdata::opcodes;
+use blockdata::script::InstructionIndices;
 use consensus::{encode, Encodable};
 
 #[cfg(feature = "secp-recovery")]
@@ -159,6 +159,7 @@ mod message_signing {
                 Some(AddressType::P2sh) => false,
                 Some(AddressType::P2wpkh) => false,
                 Some(AddressType::P2wsh) => false,
+                Some(AddressType::P2tr) => false,
                 None => false,
             })
         }
@@ -198,36 +199,34 @@ mod message_signing {
 
 /// Search for `needle` in the vector `haystack` and remove every
 /// instance of it, returning the number of instances removed.
-/// Loops through the vector opcode by opcode, skipping pushed data.
+/// Loops through the vector opcode by opcode, skipping pushed data, and
+/// only matches at the start of opcodes like Bitcoin Core's `FindAndDelete`.
 pub fn script_find_and_remove(haystack: &mut Vec<u8>, needle: &[u8]) -> usize {
-    if needle.len() > haystack.len() { return 0; }
     if needle.is_empty() { return 0; }
 
-    let mut top = haystack.len() - needle.len();
     let mut n_deleted = 0;
-
-    let mut i = 0;
-    while i <= top {
-        if &haystack[i..(i + needle.len())] == needle {
-            for j in i..top {
-                haystack.swap(j + needle.len(), j);
-            }
+    let mut result = Vec::with_capacity(haystack.len());
+    let mut pos = 0;
+    let mut copied = 0;
+    loop {
+        result.extend_from_slice(&haystack[copied..pos]);
+        while haystack.len() - pos >= needle.len() && &haystack[pos..pos + needle.len()] == needle {
+            pos += needle.len();
             n_deleted += 1;
-            // This is ugly but prevents infinite loop in case of overflow
-            let overflow = top < needle.len();
-            top = top.wrapping_sub(needle.len());
-            if overflow { break; }
-        } else {
-            i += match opcodes::All::from((*haystack)[i]).classify() {
-                opcodes::Class::PushBytes(n) => n as usize + 1,
-                opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA1) => 2,
-                opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA2) => 3,
-                opcodes::Class::Ordinary(opcodes::Ordinary::OP_PUSHDATA4) => 5,
-                _ => 1
-            };
         }
+        copied = pos;
+        // Parsing resumes after the removed instances, even if this is in
+        // the middle of an instruction
+        let mut instructions = InstructionIndices::from_slice(haystack, pos, false);
+        match instructions.next() {
+            Some(Ok(_)) => pos = instructions.pos(),
+            _ => break,
+        }
+    }
+    if n_deleted > 0 {
+        result.extend_from_slice(&haystack[copied..]);
+        *haystack = result;
     }
-    haystack.truncate(top.wrapping_add(needle.len()));
     n_deleted
 }
 
@@ -243,7 +242,7 @@ pub fn signed_msg_hash(msg: &str) -> sha256d::Hash {
 
 #[cfg(test)]
 mod tests {
-    use hashes::hex::ToHex;
+    use hashes::hex::{FromHex, ToHex};
     use super::script_find_and_remove;
     use super::signed_msg_hash;
 
@@ -277,6 +276,18 @@ mod tests {
 
         assert_eq!(script_find_and_remove(&mut v, &[105, 105, 5]), 0);
         assert_eq!(script_find_and_remove(&mut v, &[105]), 0);
+
+        let mut v = Vec::from_hex("02010202010200").unwrap();
+        assert_eq!(script_find_and_remove(&mut v, &[0x02, 0x01, 0x02]), 2);
+        assert_eq!(v, vec![0x00]);
+
+        // Matches only at opcode boundaries, skipping pushed data
+        let mut v = Vec::from_hex("0302ff03").unwrap();
+        assert_eq!(script_find_and_remove(&mut v, &[0x02, 0xff, 0x03]), 0);
+        assert_eq!(v, Vec::from_hex("0302ff03").unwrap());
+        let mut v = Vec::from_hex("4c02ab76ab").unwrap();
+        assert_eq!(script_find_and_remove(&mut v, &[0xab]), 1);
+        assert_eq!(v, Vec::from_hex("4c02ab76").unwrap());
     }
 
     #[test]
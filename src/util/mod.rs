@@ -25,11 +25,14 @@ pub mod base58;
 pub mod bip32;
 pub mod bip143;
 pub mod contracthash;
+pub mod descriptor;
 pub mod hash;
 pub mod merkleblock;
 pub mod misc;
 pub mod psbt;
+pub mod sighash;
 pub mod taproot;
+pub mod txbuilder;
 pub mod uint;
 pub mod bip158;
 
@@ -37,4 +37,4 @@ mod output;
 
 pub use self::global::Global;
 pub use self::input::Input;
-pub use self::output::Output;
+pub use self::output::{Output, TapTree, IncompleteTapTree};
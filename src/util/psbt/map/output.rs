@@ -12,7 +12,7 @@
 // If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
 //
 
-use std::io;
+use std::{error, fmt, io};
 use std::collections::BTreeMap;
 use std::collections::btree_map::Entry;
 
@@ -24,6 +24,8 @@ use util::psbt;
 use util::psbt::map::Map;
 use util::psbt::raw;
 use util::psbt::Error;
+use util::schnorr;
+use util::taproot::{NodeInfo, TapLeafHash, TaprootBuilder, TaprootBuilderError};
 
 /// Type: Redeem Script PSBT_OUT_REDEEM_SCRIPT = 0x00
 const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
@@ -31,6 +33,12 @@ const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
 const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
 /// Type: BIP 32 Derivation Path PSBT_OUT_BIP32_DERIVATION = 0x02
 const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+/// Type: Taproot Internal Key PSBT_OUT_TAP_INTERNAL_KEY = 0x05
+const PSBT_OUT_TAP_INTERNAL_KEY: u8 = 0x05;
+/// Type: Taproot Tree PSBT_OUT_TAP_TREE = 0x06
+const PSBT_OUT_TAP_TREE: u8 = 0x06;
+/// Type: Taproot Key BIP 32 Derivation Path PSBT_OUT_TAP_BIP32_DERIVATION = 0x07
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
 /// Type: Proprietary Use Type PSBT_IN_PROPRIETARY = 0xFC
 const PSBT_OUT_PROPRIETARY: u8 = 0xFC;
 
@@ -47,6 +55,15 @@ pub struct Output {
     /// corresponding master key fingerprints and derivation paths.
     #[cfg_attr(feature = "serde", serde(with = "::serde_utils::btreemap_as_seq"))]
     pub bip32_derivation: BTreeMap<PublicKey, KeySource>,
+    /// The taproot internal key of this output.
+    pub tap_internal_key: Option<schnorr::PublicKey>,
+    /// The taproot script tree of this output.
+    pub tap_tree: Option<TapTree>,
+    /// A map from x-only public keys needed to spend this output to the
+    /// hashes of the leaves they are used in and to their corresponding
+    /// master key fingerprints and derivation paths.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::btreemap_as_seq"))]
+    pub tap_key_origins: BTreeMap<schnorr::PublicKey, (Vec<TapLeafHash>, KeySource)>,
     /// Proprietary key-value pairs for this output.
     #[cfg_attr(feature = "serde", serde(with = "::serde_utils::btreemap_as_seq_byte_values"))]
     pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
@@ -78,6 +95,21 @@ impl Map for Output {
                     self.bip32_derivation <= <raw_key: PublicKey>|<raw_value: KeySource>
                 }
             }
+            PSBT_OUT_TAP_INTERNAL_KEY => {
+                impl_psbt_insert_pair! {
+                    self.tap_internal_key <= <raw_key: _>|<raw_value: schnorr::PublicKey>
+                }
+            }
+            PSBT_OUT_TAP_TREE => {
+                impl_psbt_insert_pair! {
+                    self.tap_tree <= <raw_key: _>|<raw_value: TapTree>
+                }
+            }
+            PSBT_OUT_TAP_BIP32_DERIVATION => {
+                impl_psbt_insert_pair! {
+                    self.tap_key_origins <= <raw_key: schnorr::PublicKey>|<raw_value: (Vec<TapLeafHash>, KeySource)>
+                }
+            }
             PSBT_OUT_PROPRIETARY => match self.proprietary.entry(raw::ProprietaryKey::from_key(raw_key.clone())?) {
                 Entry::Vacant(empty_key) => {empty_key.insert(raw_value);},
                 Entry::Occupied(_) => return Err(Error::DuplicateKey(raw_key.clone()).into()),
@@ -106,6 +138,18 @@ impl Map for Output {
             rv.push(self.bip32_derivation as <PSBT_OUT_BIP32_DERIVATION, PublicKey>|<KeySource>)
         }
 
+        impl_psbt_get_pair! {
+            rv.push(self.tap_internal_key as <PSBT_OUT_TAP_INTERNAL_KEY, _>|<schnorr::PublicKey>)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push(self.tap_tree as <PSBT_OUT_TAP_TREE, _>|<TapTree>)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push(self.tap_key_origins as <PSBT_OUT_TAP_BIP32_DERIVATION, schnorr::PublicKey>|<(Vec<TapLeafHash>, KeySource)>)
+        }
+
         for (key, value) in self.proprietary.iter() {
             rv.push(raw::Pair {
                 key: key.to_key(),
@@ -125,14 +169,81 @@ impl Map for Output {
 
     fn merge(&mut self, other: Self) -> Result<(), psbt::Error> {
         self.bip32_derivation.extend(other.bip32_derivation);
+        self.tap_key_origins.extend(other.tap_key_origins);
         self.proprietary.extend(other.proprietary);
         self.unknown.extend(other.unknown);
 
         merge!(redeem_script, self, other);
         merge!(witness_script, self, other);
+        merge!(tap_internal_key, self, other);
+        merge!(tap_tree, self, other);
 
         Ok(())
     }
 }
 
 impl_psbtmap_consensus_enc_dec_oding!(Output);
+
+/// A taproot script tree all of whose leaves are known, as stored in the
+/// PSBT_OUT_TAP_TREE field of an output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TapTree(NodeInfo);
+
+impl TapTree {
+    /// Finishes the tree being built by `builder`, which must not contain
+    /// hidden nodes.
+    pub fn from_builder(builder: TaprootBuilder) -> Result<TapTree, IncompleteTapTree> {
+        builder.into_node_info()
+            .map_err(IncompleteTapTree::NotFinalized)
+            .and_then(TapTree::from_node_info)
+    }
+
+    /// Wraps the root node of a tree, which must not contain hidden nodes.
+    pub fn from_node_info(node: NodeInfo) -> Result<TapTree, IncompleteTapTree> {
+        if node.has_hidden_nodes() {
+            Err(IncompleteTapTree::HiddenParts(node))
+        } else {
+            Ok(TapTree(node))
+        }
+    }
+
+    /// Returns the root node of the tree, giving access to the merkle root
+    /// and to the leaves in depth-first order.
+    pub fn node_info(&self) -> &NodeInfo {
+        &self.0
+    }
+
+    /// Converts the tree into its root node.
+    pub fn into_node_info(self) -> NodeInfo {
+        self.0
+    }
+}
+
+/// Error returned when trying to make a [`TapTree`] out of a tree which is
+/// not complete or not fully known.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum IncompleteTapTree {
+    /// The tree could not be finished.
+    NotFinalized(TaprootBuilderError),
+    /// The tree contains hidden subtrees, of which the scripts are unknown.
+    HiddenParts(NodeInfo),
+}
+
+impl fmt::Display for IncompleteTapTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IncompleteTapTree::NotFinalized(ref e) => write!(f, "taproot tree not finalized: {}", e),
+            IncompleteTapTree::HiddenParts(_) => f.write_str("taproot tree contains hidden nodes"),
+        }
+    }
+}
+
+impl error::Error for IncompleteTapTree {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            IncompleteTapTree::NotFinalized(ref e) => Some(e),
+            IncompleteTapTree::HiddenParts(_) => None,
+        }
+    }
+}
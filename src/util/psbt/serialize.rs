@@ -21,11 +21,15 @@ use std::io;
 
 use blockdata::script::Script;
 use blockdata::transaction::{SigHashType, Transaction, TxOut};
-use consensus::encode::{self, serialize, Decodable};
+use blockdata::witness::Witness;
+use consensus::encode::{self, serialize, deserialize_partial, Decodable, Encodable, VarInt};
 use util::bip32::{ChildNumber, Fingerprint, KeySource};
 use hashes::{hash160, ripemd160, sha256, sha256d, Hash};
 use util::ecdsa::PublicKey;
 use util::psbt;
+use util::psbt::map::TapTree;
+use util::schnorr;
+use util::taproot::{ControlBlock, LeafVersion, TapBranchHash, TapLeafHash, TaprootBuilder};
 
 /// A trait for serializing a value as raw data for insertion into PSBT
 /// key-value pairs.
@@ -42,11 +46,13 @@ pub trait Deserialize: Sized {
 
 impl_psbt_de_serialize!(Transaction);
 impl_psbt_de_serialize!(TxOut);
-impl_psbt_de_serialize!(Vec<Vec<u8>>); // scriptWitness
+impl_psbt_de_serialize!(Witness);
 impl_psbt_hash_de_serialize!(ripemd160::Hash);
 impl_psbt_hash_de_serialize!(sha256::Hash);
 impl_psbt_hash_de_serialize!(hash160::Hash);
 impl_psbt_hash_de_serialize!(sha256d::Hash);
+impl_psbt_hash_de_serialize!(TapLeafHash);
+impl_psbt_hash_de_serialize!(TapBranchHash);
 
 impl Serialize for Script {
     fn serialize(&self) -> Vec<u8> {
@@ -141,3 +147,160 @@ impl Deserialize for SigHashType {
         }
     }
 }
+
+impl Serialize for schnorr::PublicKey {
+    fn serialize(&self) -> Vec<u8> {
+        schnorr::PublicKey::serialize(self).to_vec()
+    }
+}
+
+impl Deserialize for schnorr::PublicKey {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        schnorr::PublicKey::from_slice(bytes)
+            .map_err(|_| encode::Error::ParseFailed("invalid x-only public key"))
+    }
+}
+
+impl Serialize for schnorr::SchnorrSig {
+    fn serialize(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl Deserialize for schnorr::SchnorrSig {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        schnorr::SchnorrSig::from_slice(bytes).map_err(|e| match e {
+            schnorr::SchnorrSigError::InvalidSigHashType(flag) => {
+                psbt::Error::NonStandardSigHashType(flag as u32).into()
+            }
+            schnorr::SchnorrSigError::InvalidSchnorrSigSize(_) => {
+                encode::Error::ParseFailed("invalid schnorr signature length")
+            }
+            schnorr::SchnorrSigError::Secp256k1(_) => {
+                encode::Error::ParseFailed("invalid schnorr signature")
+            }
+        })
+    }
+}
+
+// tapscript signature keys
+impl Serialize for (schnorr::PublicKey, TapLeafHash) {
+    fn serialize(&self) -> Vec<u8> {
+        let mut rv: Vec<u8> = Vec::with_capacity(64);
+        rv.extend_from_slice(&self.0.serialize());
+        rv.extend_from_slice(&self.1[..]);
+        rv
+    }
+}
+
+impl Deserialize for (schnorr::PublicKey, TapLeafHash) {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        if bytes.len() < 32 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+        }
+        let pk: schnorr::PublicKey = Deserialize::deserialize(&bytes[..32])?;
+        let leaf_hash: TapLeafHash = Deserialize::deserialize(&bytes[32..])?;
+        Ok((pk, leaf_hash))
+    }
+}
+
+impl Serialize for ControlBlock {
+    fn serialize(&self) -> Vec<u8> {
+        ControlBlock::serialize(self)
+    }
+}
+
+impl Deserialize for ControlBlock {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        ControlBlock::from_slice(bytes)
+            .map_err(|_| encode::Error::ParseFailed("invalid control block"))
+    }
+}
+
+// leaf scripts, followed by their leaf version
+impl Serialize for (Script, LeafVersion) {
+    fn serialize(&self) -> Vec<u8> {
+        let mut rv: Vec<u8> = Vec::with_capacity(self.0.len() + 1);
+        rv.extend_from_slice(self.0.as_bytes());
+        rv.push(self.1.as_u8());
+        rv
+    }
+}
+
+impl Deserialize for (Script, LeafVersion) {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        let (ver, script) = match bytes.split_last() {
+            Some((ver, script)) => (*ver, script),
+            None => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        };
+        let ver = LeafVersion::from_u8(ver)
+            .map_err(|_| encode::Error::ParseFailed("invalid leaf version"))?;
+        Ok((Script::from(script.to_vec()), ver))
+    }
+}
+
+// taproot key origins: the leaves the key is used in and its key source
+impl Serialize for (Vec<TapLeafHash>, KeySource) {
+    fn serialize(&self) -> Vec<u8> {
+        let mut rv: Vec<u8> = Vec::with_capacity(9 + 32 * self.0.len() + 4 + 4 * (self.1).1.as_ref().len());
+        VarInt(self.0.len() as u64).consensus_encode(&mut rv).expect("vecs don't error");
+        for leaf_hash in self.0.iter() {
+            rv.extend_from_slice(&leaf_hash[..]);
+        }
+        rv.append(&mut self.1.serialize());
+        rv
+    }
+}
+
+impl Deserialize for (Vec<TapLeafHash>, KeySource) {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        let (VarInt(count), consumed) = deserialize_partial::<VarInt>(bytes)?;
+        let hashes_len = (count as usize).checked_mul(32)
+            .and_then(|len| len.checked_add(consumed))
+            .ok_or(encode::Error::ParseFailed("too many leaf hashes"))?;
+        if bytes.len() < hashes_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+        }
+        let mut leaf_hashes = Vec::with_capacity(count as usize);
+        for chunk in bytes[consumed..hashes_len].chunks(32) {
+            leaf_hashes.push(Deserialize::deserialize(chunk)?);
+        }
+        let key_source: KeySource = Deserialize::deserialize(&bytes[hashes_len..])?;
+        Ok((leaf_hashes, key_source))
+    }
+}
+
+// script trees, as a depth-first list of (depth, leaf version, script)
+impl Serialize for TapTree {
+    fn serialize(&self) -> Vec<u8> {
+        let mut rv: Vec<u8> = Vec::new();
+        for leaf in self.node_info().leaves() {
+            // the depth is at most 128, see TAPROOT_CONTROL_MAX_NODE_COUNT
+            rv.push(leaf.depth() as u8);
+            rv.push(leaf.leaf_version().as_u8());
+            leaf.script().consensus_encode(&mut rv).expect("vecs don't error");
+        }
+        rv
+    }
+}
+
+impl Deserialize for TapTree {
+    fn deserialize(bytes: &[u8]) -> Result<Self, encode::Error> {
+        let mut builder = TaprootBuilder::new();
+        let mut d = bytes;
+        while !d.is_empty() {
+            if d.len() < 2 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+            let depth = d[0] as usize;
+            let ver = LeafVersion::from_u8(d[1])
+                .map_err(|_| encode::Error::ParseFailed("invalid leaf version"))?;
+            let (script, consumed) = deserialize_partial::<Script>(&d[2..])?;
+            d = &d[2 + consumed..];
+            builder = builder.add_leaf_with_ver(depth, script, ver)
+                .map_err(|_| encode::Error::ParseFailed("invalid taproot tree"))?;
+        }
+        TapTree::from_builder(builder)
+            .map_err(|_| encode::Error::ParseFailed("incomplete taproot tree"))
+    }
+}
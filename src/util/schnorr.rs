@@ -13,7 +13,321 @@
 
 //! Schnorr Bitcoin Keys
 //!
-//! Schnorr keys used in Bitcoin, reexporting Secp256k1 Schnorr key types
+//! Schnorr keys used in Bitcoin, reexporting Secp256k1 Schnorr key types,
+//! along with their BIP341 taproot tweaks
 //!
 
-pub use secp256k1::schnorrsig::{PublicKey, KeyPair};
+pub use secp256k1::schnorrsig::{PublicKey, KeyPair, Signature};
+
+use std::{error, fmt};
+
+use hashes::Hash;
+use secp256k1::{self, Secp256k1, Signing, Verification};
+use util::sighash::SchnorrSigHashType;
+use util::taproot::{TapBranchHash, TapTweakHash};
+
+/// An x-only public key before it is tweaked with a taproot commitment,
+/// used as the internal key of a taproot output
+pub type UntweakedPublicKey = PublicKey;
+
+/// An x-only public key tweaked with a taproot commitment, used as the
+/// output key of a taproot output
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct TweakedPublicKey(PublicKey);
+
+impl TweakedPublicKey {
+    /// Tweaks an internal key with the merkle root of a script tree, or with
+    /// no script at all if `merkle_root` is `None`, as described by BIP341.
+    ///
+    /// Returns the output key and whether its Y coordinate is odd, which is
+    /// needed in the control block of script-path spends.
+    pub fn from_internal_key<C: Verification>(
+        secp: &Secp256k1<C>,
+        internal_key: &UntweakedPublicKey,
+        merkle_root: Option<TapBranchHash>,
+    ) -> (TweakedPublicKey, bool) {
+        let tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root);
+        let mut output_key = *internal_key;
+        let parity = output_key.tweak_add_assign(secp, &tweak[..])
+            .expect("tap tweak is a valid scalar except with negligible probability");
+        debug_assert!(internal_key.tweak_add_check(secp, &output_key, parity, tweak.into_inner()));
+        (TweakedPublicKey(output_key), parity)
+    }
+
+    /// Wraps a key which is already known to be tweaked, e.g. the witness
+    /// program of an existing taproot output
+    pub fn dangerous_assume_tweaked(key: PublicKey) -> TweakedPublicKey {
+        TweakedPublicKey(key)
+    }
+
+    /// Returns a reference to the underlying key
+    pub fn as_inner(&self) -> &PublicKey {
+        &self.0
+    }
+
+    /// Returns the underlying key
+    pub fn into_inner(self) -> PublicKey {
+        self.0
+    }
+
+    /// Serializes the key as 32 bytes
+    pub fn serialize(&self) -> [u8; 32] {
+        self.0.serialize()
+    }
+}
+
+impl fmt::Display for TweakedPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// A keypair whose secret key is tweaked with a taproot commitment, able to
+/// sign key-path spends of the corresponding `TweakedPublicKey`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TweakedKeyPair(KeyPair);
+
+impl TweakedKeyPair {
+    /// Tweaks the secret key of a keypair so that its public key is the
+    /// output key of `TweakedPublicKey::from_internal_key` called on the
+    /// keypair's x-only public key with the same `merkle_root`
+    pub fn from_keypair<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        keypair: &KeyPair,
+        merkle_root: Option<TapBranchHash>,
+    ) -> TweakedKeyPair {
+        let internal_key = PublicKey::from_keypair(secp, keypair);
+        let tweak = TapTweakHash::from_key_and_tweak(&internal_key, merkle_root);
+        let mut tweaked = *keypair;
+        tweaked.tweak_add_assign(secp, &tweak[..])
+            .expect("tap tweak is a valid scalar except with negligible probability");
+        TweakedKeyPair(tweaked)
+    }
+
+    /// Wraps a keypair which is already known to be tweaked
+    pub fn dangerous_assume_tweaked(keypair: KeyPair) -> TweakedKeyPair {
+        TweakedKeyPair(keypair)
+    }
+
+    /// Returns a reference to the underlying keypair
+    pub fn as_inner(&self) -> &KeyPair {
+        &self.0
+    }
+
+    /// Returns the underlying keypair
+    pub fn into_inner(self) -> KeyPair {
+        self.0
+    }
+
+    /// Returns the tweaked public key of this keypair
+    pub fn public_key<C: Signing>(&self, secp: &Secp256k1<C>) -> TweakedPublicKey {
+        TweakedPublicKey(PublicKey::from_keypair(secp, &self.0))
+    }
+}
+
+/// A BIP340 signature together with the taproot sighash type it commits to,
+/// as found in taproot witnesses
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SchnorrSig {
+    /// The underlying schnorr signature
+    pub sig: Signature,
+    /// The sighash type the signature commits to
+    pub hash_ty: SchnorrSigHashType,
+}
+
+impl SchnorrSig {
+    /// Deserializes a witness signature: 64 bytes for `SIGHASH_DEFAULT`, or
+    /// 64 bytes followed by any other sighash type byte.
+    ///
+    /// An explicit `SIGHASH_DEFAULT` byte is rejected, as it is by consensus.
+    pub fn from_slice(sl: &[u8]) -> Result<SchnorrSig, SchnorrSigError> {
+        match sl.len() {
+            64 => Ok(SchnorrSig {
+                sig: Signature::from_slice(sl)?,
+                hash_ty: SchnorrSigHashType::Default,
+            }),
+            65 => {
+                let (hash_ty, sig) = sl.split_last().expect("length checked to be 65");
+                let hash_ty = match SchnorrSigHashType::from_u8(*hash_ty) {
+                    Ok(SchnorrSigHashType::Default) | Err(_) => return Err(SchnorrSigError::InvalidSigHashType(*hash_ty)),
+                    Ok(hash_ty) => hash_ty,
+                };
+                Ok(SchnorrSig {
+                    sig: Signature::from_slice(sig)?,
+                    hash_ty: hash_ty,
+                })
+            }
+            len => Err(SchnorrSigError::InvalidSchnorrSigSize(len)),
+        }
+    }
+
+    /// Serializes the signature for a witness, appending the sighash type
+    /// byte unless it is `SIGHASH_DEFAULT`
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut ser_sig = self.sig.as_ref().to_vec();
+        if self.hash_ty != SchnorrSigHashType::Default {
+            ser_sig.push(self.hash_ty.as_u8());
+        }
+        ser_sig
+    }
+}
+
+/// A schnorr signature parsing error
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SchnorrSigError {
+    /// The sighash type byte is invalid, or is an explicit `SIGHASH_DEFAULT`
+    InvalidSigHashType(u8),
+    /// The signature has a valid length but does not parse
+    Secp256k1(secp256k1::Error),
+    /// The signature is neither 64 nor 65 bytes long
+    InvalidSchnorrSigSize(usize),
+}
+
+impl fmt::Display for SchnorrSigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchnorrSigError::InvalidSigHashType(hash_ty) => write!(f, "invalid signature hash type {:#04x}", hash_ty),
+            SchnorrSigError::Secp256k1(ref e) => write!(f, "malformed schnorr signature: {}", e),
+            SchnorrSigError::InvalidSchnorrSigSize(sz) => write!(f, "invalid schnorr signature size: {}", sz),
+        }
+    }
+}
+
+impl error::Error for SchnorrSigError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            SchnorrSigError::Secp256k1(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<secp256k1::Error> for SchnorrSigError {
+    fn from(e: secp256k1::Error) -> SchnorrSigError {
+        SchnorrSigError::Secp256k1(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+    use hashes::hex::{FromHex, ToHex};
+    use secp256k1::{Message, SecretKey};
+
+    fn hex_root(hex: &str) -> Option<TapBranchHash> {
+        Some(TapBranchHash::from_slice(&Vec::<u8>::from_hex(hex).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn bip341_output_keys() {
+        let secp = Secp256k1::verification_only();
+        // internal key, merkle root, output key, output key parity
+        let vectors = [
+            ("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d", None,
+             "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", None),
+            ("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+             Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"),
+             "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", Some(true)),
+            ("93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820",
+             Some("c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"),
+             "e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", Some(false)),
+            ("ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592",
+             Some("6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef"),
+             "712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", Some(false)),
+            ("f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd8",
+             Some("ab179431c28d3b68fb798957faf5497d69c883c6fb1e1cd9f81483d87bac90cc"),
+             "77e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", Some(true)),
+            ("e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f",
+             Some("ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2"),
+             "91b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", Some(false)),
+            ("55adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d",
+             Some("2f6b2c5397b6d68ca18e09a3f05161668ffe93a988582d55c6f07bd5b3329def"),
+             "75169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", Some(true)),
+        ];
+        for &(internal_key, merkle_root, output_key, parity) in vectors.iter() {
+            let internal_key = PublicKey::from_str(internal_key).unwrap();
+            let merkle_root = merkle_root.and_then(hex_root);
+            let (tweaked, tweaked_parity) = TweakedPublicKey::from_internal_key(&secp, &internal_key, merkle_root);
+            assert_eq!(tweaked.serialize().to_hex(), output_key);
+            assert_eq!(tweaked.to_string(), output_key);
+            if let Some(parity) = parity {
+                assert_eq!(tweaked_parity, parity);
+            }
+        }
+    }
+
+    #[test]
+    fn bip341_tweaked_keypairs() {
+        let secp = Secp256k1::new();
+        // internal secret key, merkle root, tweaked secret key
+        let vectors = [
+            ("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa", None,
+             "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9"),
+            ("1e4da49f6aaf4e5cd175fe08a32bb5cb4863d963921255f33d3bc31e1343907f",
+             Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"),
+             "ea260c3b10e60f6de018455cd0278f2f5b7e454be1999572789e6a9565d26080"),
+            ("d3c7af07da2d54f7a7735d3d0fc4f0a73164db638b2f2f7c43f711f6d4aa7e64",
+             Some("c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"),
+             "97323385e57015b75b0339a549c56a948eb961555973f0951f555ae6039ef00d"),
+        ];
+        for &(secret_key, merkle_root, tweaked_secret_key) in vectors.iter() {
+            let merkle_root = merkle_root.and_then(hex_root);
+            let keypair = KeyPair::from_seckey_str(&secp, secret_key).unwrap();
+            let tweaked = TweakedKeyPair::from_keypair(&secp, &keypair, merkle_root);
+
+            let expected = SecretKey::from_str(tweaked_secret_key).unwrap();
+            let expected = KeyPair::from_secret_key(&secp, expected);
+            assert_eq!(tweaked.into_inner(), expected);
+
+            let internal_key = PublicKey::from_keypair(&secp, &keypair);
+            let (output_key, _) = TweakedPublicKey::from_internal_key(&secp, &internal_key, merkle_root);
+            assert_eq!(tweaked.public_key(&secp), output_key);
+        }
+    }
+
+    #[test]
+    fn bip341_key_spend_signatures() {
+        let secp = Secp256k1::new();
+        // witness signature, tweaked secret key, sighash type, sighash
+        let vectors = [
+            ("ed7c1647cb97379e76892be0cacff57ec4a7102aa24296ca39af7541246d8ff14d38958d4cc1e2e478e4d4a764bbfd835b16d4e314b72937b29833060b87276c03",
+             "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9", SchnorrSigHashType::Single,
+             "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+            ("052aedffc554b41f52b521071793a6b88d6dbca9dba94cf34c83696de0c1ec35ca9c5ed4ab28059bd606a4f3a657eec0bb96661d42921b5f50a95ad33675b54f83",
+             "ea260c3b10e60f6de018455cd0278f2f5b7e454be1999572789e6a9565d26080", SchnorrSigHashType::SinglePlusAnyoneCanPay,
+             "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+            ("b4010dd48a617db09926f729e79c33ae0b4e94b79f04a1ae93ede6315eb3669de185a17d2b0ac9ee09fd4c64b678a0b61a0a86fa888a273c8511be83bfd6810f",
+             "a8e7aa924f0d58854185a490e6c41f6efb7b675c0f3331b7f14b549400b4d501", SchnorrSigHashType::Default,
+             "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+        ];
+        for &(witness_sig, secret_key, hash_ty, sighash) in vectors.iter() {
+            let ser_sig = Vec::<u8>::from_hex(witness_sig).unwrap();
+            let sig = SchnorrSig::from_slice(&ser_sig).unwrap();
+            assert_eq!(sig.hash_ty, hash_ty);
+            assert_eq!(sig.to_vec(), ser_sig);
+
+            let keypair = KeyPair::from_seckey_str(&secp, secret_key).unwrap();
+            let msg = Message::from_slice(&Vec::<u8>::from_hex(sighash).unwrap()).unwrap();
+            assert_eq!(secp.schnorrsig_sign_with_aux_rand(&msg, &keypair, &[0u8; 32]), sig.sig);
+            assert!(secp.schnorrsig_verify(&sig.sig, &msg, &PublicKey::from_keypair(&secp, &keypair)).is_ok());
+        }
+    }
+
+    #[test]
+    fn schnorr_sig_errors() {
+        let mut ser_sig = Vec::<u8>::from_hex("b4010dd48a617db09926f729e79c33ae0b4e94b79f04a1ae93ede6315eb3669de185a17d2b0ac9ee09fd4c64b678a0b61a0a86fa888a273c8511be83bfd6810f").unwrap();
+        ser_sig.push(0x00);
+        assert_eq!(SchnorrSig::from_slice(&ser_sig), Err(SchnorrSigError::InvalidSigHashType(0x00)));
+        ser_sig[64] = 0x04;
+        assert_eq!(SchnorrSig::from_slice(&ser_sig), Err(SchnorrSigError::InvalidSigHashType(0x04)));
+        ser_sig[64] = 0x81;
+        assert_eq!(SchnorrSig::from_slice(&ser_sig).unwrap().hash_ty, SchnorrSigHashType::AllPlusAnyoneCanPay);
+        assert_eq!(SchnorrSig::from_slice(&ser_sig[..63]), Err(SchnorrSigError::InvalidSchnorrSigSize(63)));
+        ser_sig.push(0x01);
+        assert_eq!(SchnorrSig::from_slice(&ser_sig), Err(SchnorrSigError::InvalidSchnorrSigSize(66)));
+    }
+}
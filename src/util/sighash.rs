@@ -0,0 +1,934 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//   The rust-bitcoin developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Taproot signature hash
+//!
+//! Implementation of the BIP341 signature message algorithm used by key-path
+//! and script-path taproot spends. The per-transaction hashes are computed
+//! once and shared with BIP143 segwit v0 sighashes, which are a second round
+//! of SHA256 over the same data.
+//!
+
+use std::{error, fmt, io, str};
+use std::borrow::Borrow;
+use std::ops::{Deref, DerefMut};
+
+use hashes::{sha256, sha256d, Hash};
+use hash_types::SigHash;
+use blockdata::script::Script;
+use blockdata::transaction::{Transaction, TxOut, SigHashType};
+use blockdata::witness::Witness;
+use consensus::{encode, Encodable};
+use util::taproot::{TapLeafHash, TapSighashHash, TAPROOT_ANNEX_PREFIX};
+
+/// Key version used in tapscript signature messages, see BIP342
+const KEY_VERSION_0: u8 = 0u8;
+
+/// Code separator position committed to when no `OP_CODESEPARATOR` was executed
+pub const TAPROOT_CODESEP_POS_NONE: u32 = 0xFFFFFFFF;
+
+/// Hashtype of a taproot signature, encoded in the 65th byte of the signature
+/// if present. Fixed values so they can be casted as integer types for encoding
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum SchnorrSigHashType {
+    /// 0x0: Used when not explicitly specified, commits to the same data as `All`
+    Default = 0x00,
+    /// 0x1: Sign all outputs
+    All = 0x01,
+    /// 0x2: Sign no outputs --- anyone can choose the destination
+    None = 0x02,
+    /// 0x3: Sign the output whose index matches this input's index. Unlike
+    /// legacy and segwit v0 signatures, a missing output is an error.
+    Single = 0x03,
+    /// 0x81: Sign all outputs but only this input
+    AllPlusAnyoneCanPay = 0x81,
+    /// 0x82: Sign no outputs and only this input
+    NonePlusAnyoneCanPay = 0x82,
+    /// 0x83: Sign one output and only this input (see `Single` for what "one output" means)
+    SinglePlusAnyoneCanPay = 0x83,
+}
+serde_string_impl!(SchnorrSigHashType, "a SchnorrSigHashType data");
+
+impl fmt::Display for SchnorrSigHashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            SchnorrSigHashType::Default => "SIGHASH_DEFAULT",
+            SchnorrSigHashType::All => "SIGHASH_ALL",
+            SchnorrSigHashType::None => "SIGHASH_NONE",
+            SchnorrSigHashType::Single => "SIGHASH_SINGLE",
+            SchnorrSigHashType::AllPlusAnyoneCanPay => "SIGHASH_ALL|SIGHASH_ANYONECANPAY",
+            SchnorrSigHashType::NonePlusAnyoneCanPay => "SIGHASH_NONE|SIGHASH_ANYONECANPAY",
+            SchnorrSigHashType::SinglePlusAnyoneCanPay => "SIGHASH_SINGLE|SIGHASH_ANYONECANPAY",
+        };
+        f.write_str(s)
+    }
+}
+
+impl str::FromStr for SchnorrSigHashType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SIGHASH_DEFAULT" => Ok(SchnorrSigHashType::Default),
+            "SIGHASH_ALL" => Ok(SchnorrSigHashType::All),
+            "SIGHASH_NONE" => Ok(SchnorrSigHashType::None),
+            "SIGHASH_SINGLE" => Ok(SchnorrSigHashType::Single),
+            "SIGHASH_ALL|SIGHASH_ANYONECANPAY" => Ok(SchnorrSigHashType::AllPlusAnyoneCanPay),
+            "SIGHASH_NONE|SIGHASH_ANYONECANPAY" => Ok(SchnorrSigHashType::NonePlusAnyoneCanPay),
+            "SIGHASH_SINGLE|SIGHASH_ANYONECANPAY" => Ok(SchnorrSigHashType::SinglePlusAnyoneCanPay),
+            _ => Err("can't recognize SIGHASH string".to_string())
+        }
+    }
+}
+
+impl SchnorrSigHashType {
+    /// Break the sighash flag into the "real" sighash flag and the ANYONECANPAY boolean
+    pub(crate) fn split_anyonecanpay_flag(self) -> (SchnorrSigHashType, bool) {
+        match self {
+            SchnorrSigHashType::Default => (SchnorrSigHashType::Default, false),
+            SchnorrSigHashType::All => (SchnorrSigHashType::All, false),
+            SchnorrSigHashType::None => (SchnorrSigHashType::None, false),
+            SchnorrSigHashType::Single => (SchnorrSigHashType::Single, false),
+            SchnorrSigHashType::AllPlusAnyoneCanPay => (SchnorrSigHashType::All, true),
+            SchnorrSigHashType::NonePlusAnyoneCanPay => (SchnorrSigHashType::None, true),
+            SchnorrSigHashType::SinglePlusAnyoneCanPay => (SchnorrSigHashType::Single, true),
+        }
+    }
+
+    /// Reads a taproot sighash type from its byte encoding, rejecting the values
+    /// which are invalid under BIP341
+    pub fn from_u8(n: u8) -> Result<SchnorrSigHashType, Error> {
+        match n {
+            0x00 => Ok(SchnorrSigHashType::Default),
+            0x01 => Ok(SchnorrSigHashType::All),
+            0x02 => Ok(SchnorrSigHashType::None),
+            0x03 => Ok(SchnorrSigHashType::Single),
+            0x81 => Ok(SchnorrSigHashType::AllPlusAnyoneCanPay),
+            0x82 => Ok(SchnorrSigHashType::NonePlusAnyoneCanPay),
+            0x83 => Ok(SchnorrSigHashType::SinglePlusAnyoneCanPay),
+            x => Err(Error::InvalidSigHashType(x)),
+        }
+    }
+
+    /// Converts to a u8
+    pub fn as_u8(self) -> u8 { self as u8 }
+}
+
+impl From<SigHashType> for SchnorrSigHashType {
+    fn from(t: SigHashType) -> SchnorrSigHashType {
+        match t {
+            SigHashType::All => SchnorrSigHashType::All,
+            SigHashType::None => SchnorrSigHashType::None,
+            SigHashType::Single => SchnorrSigHashType::Single,
+            SigHashType::AllPlusAnyoneCanPay => SchnorrSigHashType::AllPlusAnyoneCanPay,
+            SigHashType::NonePlusAnyoneCanPay => SchnorrSigHashType::NonePlusAnyoneCanPay,
+            SigHashType::SinglePlusAnyoneCanPay => SchnorrSigHashType::SinglePlusAnyoneCanPay,
+        }
+    }
+}
+
+/// Errors which can occur while computing a signature hash
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The writer passed to one of the `*_encode_signing_data_to` methods errored
+    Io(io::ErrorKind),
+    /// The requested input index is out of the transaction inputs bounds
+    IndexOutOfInputsBounds {
+        /// Requested index
+        index: usize,
+        /// Number of transaction inputs
+        inputs_size: usize,
+    },
+    /// A taproot SIGHASH_SINGLE signature without an output at the same index as the input
+    SingleWithoutCorrespondingOutput {
+        /// Requested index
+        index: usize,
+        /// Number of transaction outputs
+        outputs_size: usize,
+    },
+    /// The number of prevouts differs from the number of transaction inputs
+    PrevoutsSize,
+    /// No prevout is provided for the requested input
+    PrevoutIndex,
+    /// A single prevout was provided, but all prevouts are needed without ANYONECANPAY
+    PrevoutKind,
+    /// The annex is empty or doesn't start with 0x50
+    WrongAnnex,
+    /// The byte is not a valid taproot sighash type
+    InvalidSigHashType(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "writer errored: {:?}", e),
+            Error::IndexOutOfInputsBounds { index, inputs_size } => write!(f,
+                "input index {} out of bounds, the transaction has {} inputs", index, inputs_size),
+            Error::SingleWithoutCorrespondingOutput { index, outputs_size } => write!(f,
+                "SIGHASH_SINGLE for input {} without a corresponding output, the transaction has {} outputs",
+                index, outputs_size),
+            Error::PrevoutsSize => f.write_str("number of prevouts differs from the number of inputs"),
+            Error::PrevoutIndex => f.write_str("no prevout provided for the requested input"),
+            Error::PrevoutKind => f.write_str("all prevouts are required without SIGHASH_ANYONECANPAY"),
+            Error::WrongAnnex => f.write_str("annex must be non-empty and start with 0x50"),
+            Error::InvalidSigHashType(t) => write!(f, "invalid taproot sighash type {:#04x}", t),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[doc(hidden)]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e.kind())
+    }
+}
+
+/// Outputs spent by the transaction being signed
+///
+/// Without ANYONECANPAY every taproot signature commits to all the spent
+/// outputs, with it only the output spent by the signed input is needed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Prevouts<'u, T> where T: 'u + Borrow<TxOut> {
+    /// The output spent by the input at the given index
+    One(usize, T),
+    /// All the spent outputs, in the same order as the transaction inputs
+    All(&'u [T]),
+}
+
+impl<'u, T> Prevouts<'u, T> where T: Borrow<TxOut> {
+    fn check_all(&self, tx: &Transaction) -> Result<(), Error> {
+        if let Prevouts::All(prevouts) = *self {
+            if prevouts.len() != tx.input.len() {
+                return Err(Error::PrevoutsSize);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_all(&self) -> Result<&[T], Error> {
+        match *self {
+            Prevouts::All(prevouts) => Ok(prevouts),
+            Prevouts::One(..) => Err(Error::PrevoutKind),
+        }
+    }
+
+    pub(crate) fn get(&self, input_index: usize) -> Result<&TxOut, Error> {
+        match *self {
+            Prevouts::One(index, ref prevout) if index == input_index => Ok(prevout.borrow()),
+            Prevouts::One(..) => Err(Error::PrevoutIndex),
+            Prevouts::All(prevouts) => prevouts.get(input_index)
+                .map(|prevout| prevout.borrow())
+                .ok_or(Error::PrevoutIndex),
+        }
+    }
+}
+
+/// The annex of a taproot witness, a byte slice starting with 0x50
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Annex<'a>(&'a [u8]);
+
+impl<'a> Annex<'a> {
+    /// Creates a new annex, checking that the first byte is 0x50
+    pub fn new(annex_bytes: &'a [u8]) -> Result<Annex<'a>, Error> {
+        if annex_bytes.first() == Some(&TAPROOT_ANNEX_PREFIX) {
+            Ok(Annex(annex_bytes))
+        } else {
+            Err(Error::WrongAnnex)
+        }
+    }
+
+    /// Returns the annex bytes, including the 0x50 prefix
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> Encodable for Annex<'a> {
+    fn consensus_encode<W: io::Write>(&self, writer: W) -> Result<usize, io::Error> {
+        encode::consensus_encode_with_size(self.0, writer)
+    }
+}
+
+/// Hashes shared by segwit v0 and taproot signature messages
+#[derive(Debug)]
+struct CommonCache {
+    prevouts: sha256::Hash,
+    sequences: sha256::Hash,
+    outputs: sha256::Hash,
+}
+
+/// Segwit v0 hashes, a second round of SHA256 over `CommonCache`
+#[derive(Debug)]
+pub(crate) struct SegwitCache {
+    pub(crate) prevouts: sha256d::Hash,
+    pub(crate) sequences: sha256d::Hash,
+    pub(crate) outputs: sha256d::Hash,
+}
+
+/// Hashes of the spent outputs, only committed to by taproot signatures
+#[derive(Debug)]
+struct TaprootCache {
+    amounts: sha256::Hash,
+    script_pubkeys: sha256::Hash,
+}
+
+/// Computes signature hashes of segwit v0 and taproot inputs, caching the
+/// per-transaction hashes between inputs and signatures
+#[derive(Debug)]
+pub struct SigHashCache<R: Deref<Target=Transaction>> {
+    /// Access to transaction required for various introspection
+    tx: R,
+    /// Hashes common to segwit v0 and taproot, computed as required
+    common_cache: Option<CommonCache>,
+    /// Segwit v0 hashes, computed as required
+    segwit_cache: Option<SegwitCache>,
+    /// Taproot hashes of the spent outputs, computed as required
+    taproot_cache: Option<TaprootCache>,
+}
+
+impl<R: Deref<Target=Transaction>> SigHashCache<R> {
+    /// Compute the sighash components from an unsigned transaction in a lazy
+    /// manner when required.
+    /// For the generated sighashes to be valid, no fields in the transaction may change except for
+    /// script_sig and witnesses.
+    pub fn new(tx: R) -> Self {
+        SigHashCache {
+            tx: tx,
+            common_cache: None,
+            segwit_cache: None,
+            taproot_cache: None,
+        }
+    }
+
+    /// Encode the BIP341 signing data for any flag type into a given object implementing a
+    /// std::io::Write trait.
+    ///
+    /// `leaf_hash_code_separator` is the leaf hash of the executed tapscript and
+    /// the position of the last executed `OP_CODESEPARATOR` for script-path
+    /// spends, and must be `None` for key-path spends.
+    pub fn taproot_encode_signing_data_to<Write: io::Write, T: Borrow<TxOut>>(
+        &mut self,
+        mut writer: Write,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        annex: Option<Annex>,
+        leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
+        sighash_type: SchnorrSigHashType,
+    ) -> Result<(), Error> {
+        prevouts.check_all(&self.tx)?;
+        if input_index >= self.tx.input.len() {
+            return Err(Error::IndexOutOfInputsBounds {
+                index: input_index,
+                inputs_size: self.tx.input.len(),
+            });
+        }
+
+        let (sighash, anyone_can_pay) = sighash_type.split_anyonecanpay_flag();
+
+        // epoch
+        0u8.consensus_encode(&mut writer)?;
+
+        // Control
+        sighash_type.as_u8().consensus_encode(&mut writer)?;
+
+        // Transaction data
+        self.tx.version.consensus_encode(&mut writer)?;
+        self.tx.lock_time.consensus_encode(&mut writer)?;
+
+        if !anyone_can_pay {
+            self.common_cache().prevouts.consensus_encode(&mut writer)?;
+            {
+                let taproot_cache = self.taproot_cache(prevouts.get_all()?);
+                taproot_cache.amounts.consensus_encode(&mut writer)?;
+                taproot_cache.script_pubkeys.consensus_encode(&mut writer)?;
+            }
+            self.common_cache().sequences.consensus_encode(&mut writer)?;
+        }
+
+        if sighash != SchnorrSigHashType::None && sighash != SchnorrSigHashType::Single {
+            self.common_cache().outputs.consensus_encode(&mut writer)?;
+        }
+
+        // Data about this input
+        let mut spend_type = 0u8;
+        if annex.is_some() {
+            spend_type |= 1u8;
+        }
+        if leaf_hash_code_separator.is_some() {
+            spend_type |= 2u8;
+        }
+        spend_type.consensus_encode(&mut writer)?;
+
+        if anyone_can_pay {
+            let txin = &self.tx.input[input_index];
+            let previous_output = prevouts.get(input_index)?;
+            txin.previous_output.consensus_encode(&mut writer)?;
+            previous_output.value.consensus_encode(&mut writer)?;
+            previous_output.script_pubkey.consensus_encode(&mut writer)?;
+            txin.sequence.consensus_encode(&mut writer)?;
+        } else {
+            (input_index as u32).consensus_encode(&mut writer)?;
+        }
+
+        if let Some(annex) = annex {
+            let mut enc = sha256::Hash::engine();
+            annex.consensus_encode(&mut enc)?;
+            sha256::Hash::from_engine(enc).consensus_encode(&mut writer)?;
+        }
+
+        // Data about this output
+        if sighash == SchnorrSigHashType::Single {
+            let output = self.tx.output.get(input_index).ok_or(Error::SingleWithoutCorrespondingOutput {
+                index: input_index,
+                outputs_size: self.tx.output.len(),
+            })?;
+            let mut enc = sha256::Hash::engine();
+            output.consensus_encode(&mut enc)?;
+            sha256::Hash::from_engine(enc).consensus_encode(&mut writer)?;
+        }
+
+        // BIP342 extension for script-path spends
+        if let Some((leaf_hash, code_separator_pos)) = leaf_hash_code_separator {
+            leaf_hash.into_inner().consensus_encode(&mut writer)?;
+            KEY_VERSION_0.consensus_encode(&mut writer)?;
+            code_separator_pos.consensus_encode(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compute the BIP341 sighash for any flag type, annex and spend path
+    pub fn taproot_signature_hash<T: Borrow<TxOut>>(
+        &mut self,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        annex: Option<Annex>,
+        leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
+        sighash_type: SchnorrSigHashType,
+    ) -> Result<TapSighashHash, Error> {
+        let mut enc = TapSighashHash::engine();
+        self.taproot_encode_signing_data_to(
+            &mut enc,
+            input_index,
+            prevouts,
+            annex,
+            leaf_hash_code_separator,
+            sighash_type,
+        )?;
+        Ok(TapSighashHash::from_engine(enc))
+    }
+
+    /// Compute the BIP341 sighash for a key-path spend without annex
+    pub fn taproot_key_spend_signature_hash<T: Borrow<TxOut>>(
+        &mut self,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        sighash_type: SchnorrSigHashType,
+    ) -> Result<TapSighashHash, Error> {
+        self.taproot_signature_hash(input_index, prevouts, None, None, sighash_type)
+    }
+
+    /// Compute the BIP341 sighash for a script-path spend without annex, where
+    /// no `OP_CODESEPARATOR` was executed
+    pub fn taproot_script_spend_signature_hash<T: Borrow<TxOut>>(
+        &mut self,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        leaf_hash: TapLeafHash,
+        sighash_type: SchnorrSigHashType,
+    ) -> Result<TapSighashHash, Error> {
+        self.taproot_signature_hash(
+            input_index,
+            prevouts,
+            None,
+            Some((leaf_hash, TAPROOT_CODESEP_POS_NONE)),
+            sighash_type,
+        )
+    }
+
+    /// Encode the BIP143 signing data for any flag type into a given object implementing a
+    /// std::io::Write trait.
+    ///
+    /// The sighash type is committed to as given, like legacy signature hashes
+    /// do, so non-standard values behave as their consensus interpretation.
+    pub fn segwit_encode_signing_data_to<Write: io::Write, U: Into<u32>>(
+        &mut self,
+        mut writer: Write,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: U,
+    ) -> Result<(), Error> {
+        let sighash_type: u32 = sighash_type.into();
+        if input_index >= self.tx.input.len() {
+            return Err(Error::IndexOutOfInputsBounds {
+                index: input_index,
+                inputs_size: self.tx.input.len(),
+            });
+        }
+
+        let zero_hash = sha256d::Hash::default();
+
+        let (sighash, anyone_can_pay) = SigHashType::from_u32_consensus(sighash_type).split_anyonecanpay_flag();
+
+        self.tx.version.consensus_encode(&mut writer)?;
+
+        if !anyone_can_pay {
+            self.segwit_cache().prevouts.consensus_encode(&mut writer)?;
+        } else {
+            zero_hash.consensus_encode(&mut writer)?;
+        }
+
+        if !anyone_can_pay && sighash != SigHashType::Single && sighash != SigHashType::None {
+            self.segwit_cache().sequences.consensus_encode(&mut writer)?;
+        } else {
+            zero_hash.consensus_encode(&mut writer)?;
+        }
+
+        {
+            let txin = &self.tx.input[input_index];
+
+            txin.previous_output.consensus_encode(&mut writer)?;
+            script_code.consensus_encode(&mut writer)?;
+            value.consensus_encode(&mut writer)?;
+            txin.sequence.consensus_encode(&mut writer)?;
+        }
+
+        if sighash != SigHashType::Single && sighash != SigHashType::None {
+            self.segwit_cache().outputs.consensus_encode(&mut writer)?;
+        } else if sighash == SigHashType::Single && input_index < self.tx.output.len() {
+            let mut single_enc = SigHash::engine();
+            self.tx.output[input_index].consensus_encode(&mut single_enc)?;
+            SigHash::from_engine(single_enc).consensus_encode(&mut writer)?;
+        } else {
+            zero_hash.consensus_encode(&mut writer)?;
+        }
+
+        self.tx.lock_time.consensus_encode(&mut writer)?;
+        sighash_type.consensus_encode(&mut writer)?;
+        Ok(())
+    }
+
+    /// Compute the BIP143 sighash for any flag type
+    pub fn segwit_signature_hash<U: Into<u32>>(
+        &mut self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: U,
+    ) -> Result<SigHash, Error> {
+        let mut enc = SigHash::engine();
+        self.segwit_encode_signing_data_to(&mut enc, input_index, script_code, value, sighash_type)?;
+        Ok(SigHash::from_engine(enc))
+    }
+
+    fn common_cache(&mut self) -> &CommonCache {
+        Self::common_cache_minimal_borrow(&mut self.common_cache, &self.tx)
+    }
+
+    fn common_cache_minimal_borrow<'a>(
+        common_cache: &'a mut Option<CommonCache>,
+        tx: &R,
+    ) -> &'a CommonCache {
+        common_cache.get_or_insert_with(|| {
+            let mut enc_prevouts = sha256::Hash::engine();
+            let mut enc_sequences = sha256::Hash::engine();
+            for txin in &tx.input {
+                txin.previous_output.consensus_encode(&mut enc_prevouts).unwrap();
+                txin.sequence.consensus_encode(&mut enc_sequences).unwrap();
+            }
+            let mut enc_outputs = sha256::Hash::engine();
+            for txout in &tx.output {
+                txout.consensus_encode(&mut enc_outputs).unwrap();
+            }
+            CommonCache {
+                prevouts: sha256::Hash::from_engine(enc_prevouts),
+                sequences: sha256::Hash::from_engine(enc_sequences),
+                outputs: sha256::Hash::from_engine(enc_outputs),
+            }
+        })
+    }
+
+    pub(crate) fn segwit_cache(&mut self) -> &SegwitCache {
+        let common_cache = &mut self.common_cache;
+        let tx = &self.tx;
+        self.segwit_cache.get_or_insert_with(|| {
+            let common_cache = Self::common_cache_minimal_borrow(common_cache, tx);
+            SegwitCache {
+                prevouts: sha256d::Hash::from_inner(sha256::Hash::hash(&common_cache.prevouts[..]).into_inner()),
+                sequences: sha256d::Hash::from_inner(sha256::Hash::hash(&common_cache.sequences[..]).into_inner()),
+                outputs: sha256d::Hash::from_inner(sha256::Hash::hash(&common_cache.outputs[..]).into_inner()),
+            }
+        })
+    }
+
+    fn taproot_cache<T: Borrow<TxOut>>(&mut self, prevouts: &[T]) -> &TaprootCache {
+        self.taproot_cache.get_or_insert_with(|| {
+            let mut enc_amounts = sha256::Hash::engine();
+            let mut enc_script_pubkeys = sha256::Hash::engine();
+            for prevout in prevouts {
+                prevout.borrow().value.consensus_encode(&mut enc_amounts).unwrap();
+                prevout.borrow().script_pubkey.consensus_encode(&mut enc_script_pubkeys).unwrap();
+            }
+            TaprootCache {
+                amounts: sha256::Hash::from_engine(enc_amounts),
+                script_pubkeys: sha256::Hash::from_engine(enc_script_pubkeys),
+            }
+        })
+    }
+}
+
+impl<R: DerefMut<Target=Transaction>> SigHashCache<R> {
+    /// When the SigHashCache is initialized with a mutable reference to a transaction instead of a
+    /// regular reference, this method is available to allow modification to the witnesses.
+    ///
+    /// This allows in-line signing such as
+    /// ```
+    /// use bitcoin::blockdata::transaction::{Transaction, TxOut};
+    /// use bitcoin::util::sighash::{SigHashCache, Prevouts, SchnorrSigHashType};
+    ///
+    /// let mut tx_to_sign = Transaction { version: 2, lock_time: 0, input: Vec::new(), output: Vec::new() };
+    /// let prevouts: Vec<TxOut> = Vec::new();
+    /// let input_count = tx_to_sign.input.len();
+    ///
+    /// let mut sig_hasher = SigHashCache::new(&mut tx_to_sign);
+    /// for inp in 0..input_count {
+    ///     let _sighash = sig_hasher.taproot_key_spend_signature_hash(inp, &Prevouts::All(&prevouts), SchnorrSigHashType::Default);
+    ///     // ... sign the sighash
+    ///     sig_hasher.access_witness(inp).push(Vec::new());
+    /// }
+    /// ```
+    pub fn access_witness(&mut self, input_index: usize) -> &mut Witness {
+        &mut self.tx.input[input_index].witness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+    use hashes::hex::{FromHex, ToHex};
+    use blockdata::transaction::TxIn;
+    use consensus::encode::deserialize;
+    use util::taproot::LeafVersion;
+
+    fn run_test_taproot_sighash(
+        tx_hex: &str,
+        prevouts_hex: &str,
+        input_index: usize,
+        expected_hash: &str,
+        sighash_type: SchnorrSigHashType,
+        annex_hex: Option<&str>,
+        script_hex: Option<&str>,
+    ) {
+        let tx: Transaction = deserialize(&Vec::<u8>::from_hex(tx_hex).unwrap()).unwrap();
+        let prevouts: Vec<TxOut> = deserialize(&Vec::<u8>::from_hex(prevouts_hex).unwrap()).unwrap();
+        let annex_bytes = annex_hex.map(|hex| Vec::<u8>::from_hex(hex).unwrap());
+        let annex = annex_bytes.as_ref().map(|bytes| Annex::new(bytes).unwrap());
+        let leaf_hash = script_hex.map(|hex| {
+            let script = Script::from(Vec::<u8>::from_hex(hex).unwrap());
+            (TapLeafHash::from_script(&script, LeafVersion::default()), TAPROOT_CODESEP_POS_NONE)
+        });
+
+        let mut cache = SigHashCache::new(&tx);
+        let hash = cache.taproot_signature_hash(input_index, &Prevouts::All(&prevouts), annex.clone(), leaf_hash, sighash_type).unwrap();
+        assert_eq!(hash.into_inner().to_hex(), expected_hash);
+
+        // ANYONECANPAY only needs the spent output of the signed input
+        if sighash_type.split_anyonecanpay_flag().1 {
+            let prevout = Prevouts::One(input_index, &prevouts[input_index]);
+            let hash = cache.taproot_signature_hash(input_index, &prevout, annex, leaf_hash, sighash_type).unwrap();
+            assert_eq!(hash.into_inner().to_hex(), expected_hash);
+        }
+    }
+
+    #[test]
+    fn bip341_key_spend_sighashes() {
+        let tx: Transaction = deserialize(&Vec::<u8>::from_hex(
+            "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c01000000\
+            0000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000\
+            fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000ff\
+            fffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feff\
+            ffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffff\
+            ff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000\
+            e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9\
+            aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778\
+            eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca\
+            9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac\
+            9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d"
+        ).unwrap()).unwrap();
+        let utxos: Vec<TxOut> = [
+            ("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", 420000000),
+            ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462000000),
+            ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294000000),
+            ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504000000),
+            ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630000000),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378000000),
+            ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672000000),
+            ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546000000),
+            ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588000000),
+        ].iter().map(|&(spk, value)| TxOut {
+            value: value,
+            script_pubkey: Script::from(Vec::<u8>::from_hex(spk).unwrap()),
+        }).collect();
+        let prevouts = Prevouts::All(&utxos);
+
+        let mut cache = SigHashCache::new(&tx);
+        assert_eq!(cache.common_cache().prevouts.into_inner().to_hex(),
+            "e3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f");
+        assert_eq!(cache.common_cache().sequences.into_inner().to_hex(),
+            "18959c7221ab5ce9e26c3cd67b22c24f8baa54bac281d8e6b05e400e6c3a957e");
+        assert_eq!(cache.common_cache().outputs.into_inner().to_hex(),
+            "a2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb4bc5");
+        assert_eq!(cache.taproot_cache(&utxos).amounts.into_inner().to_hex(),
+            "58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde6");
+        assert_eq!(cache.taproot_cache(&utxos).script_pubkeys.into_inner().to_hex(),
+            "23ad0f61ad2bca5ba6a7693f50fce988e17c3780bf2b1e720cfbb38fbdd52e21");
+
+        let vectors = [
+            (0, 0x03,
+             "0003020000000065cd1de3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f\
+             58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde623ad0f61ad2bca5ba6a7\
+             693f50fce988e17c3780bf2b1e720cfbb38fbdd52e2118959c7221ab5ce9e26c3cd67b22c24f8baa54ba\
+             c281d8e6b05e400e6c3a957e0000000000d0418f0e9a36245b9a50ec87f8bf5be5bcae434337b87139c3\
+             a5b1f56e33cba0",
+             "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+            (1, 0x83,
+             "0083020000000065cd1d00d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd\
+             9900000000808f891b00000000225120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee775\
+             57b6620f3ea3ffffffffffcef8fb4ca7efc5433f591ecfc57391811ce1e186a3793024def5c884cba51d",
+             "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+            (3, 0x01,
+             "0001020000000065cd1de3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f\
+             58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde623ad0f61ad2bca5ba6a7\
+             693f50fce988e17c3780bf2b1e720cfbb38fbdd52e2118959c7221ab5ce9e26c3cd67b22c24f8baa54ba\
+             c281d8e6b05e400e6c3a957ea2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb\
+             4bc50003000000",
+             "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+            (4, 0x00,
+             "0000020000000065cd1de3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f\
+             58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde623ad0f61ad2bca5ba6a7\
+             693f50fce988e17c3780bf2b1e720cfbb38fbdd52e2118959c7221ab5ce9e26c3cd67b22c24f8baa54ba\
+             c281d8e6b05e400e6c3a957ea2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb\
+             4bc50004000000",
+             "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+            (6, 0x02,
+             "0002020000000065cd1de3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f\
+             58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde623ad0f61ad2bca5ba6a7\
+             693f50fce988e17c3780bf2b1e720cfbb38fbdd52e2118959c7221ab5ce9e26c3cd67b22c24f8baa54ba\
+             c281d8e6b05e400e6c3a957e0006000000",
+             "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+            (7, 0x82,
+             "0082020000000065cd1d00e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4ea\
+             bf00000000804c8b2000000000225120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b\
+             7577e4b17df5ffffffff",
+             "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+            (8, 0x81,
+             "0081020000000065cd1da2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb4bc5\
+             00a778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af101000000002b0c2300\
+             00000022512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220ffffffff",
+             "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2"),
+        ];
+        for &(index, hash_type, sig_msg, sighash) in vectors.iter() {
+            let hash_type = SchnorrSigHashType::from_u8(hash_type).unwrap();
+            let mut msg = Vec::new();
+            cache.taproot_encode_signing_data_to(&mut msg, index, &prevouts, None, None, hash_type).unwrap();
+            assert_eq!(msg.to_hex(), sig_msg);
+            let hash = cache.taproot_key_spend_signature_hash(index, &prevouts, hash_type).unwrap();
+            assert_eq!(hash.into_inner().to_hex(), sighash);
+        }
+    }
+
+    #[test]
+    fn key_spend_sighashes() {
+        // Test cases from Bitcoin Core's functional test framework
+        run_test_taproot_sighash(
+            "020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f1846010000003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c87580200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c908758020000000000001600147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761acfa99418bd6bedc79b9287d652d72000000",
+            "01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc7c093bc500",
+            0,
+            "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703",
+            SchnorrSigHashType::Default, None, None,
+        );
+        run_test_taproot_sighash(
+            "0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f3000000002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee24090100000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+            "02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece",
+            1,
+            "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8",
+            SchnorrSigHashType::All, None, None,
+        );
+        run_test_taproot_sighash(
+            "0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+            "01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c",
+            0,
+            "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c",
+            SchnorrSigHashType::AllPlusAnyoneCanPay, None, None,
+        );
+        run_test_taproot_sighash(
+            "020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f5010000007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+            "0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e807458621",
+            0,
+            "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067",
+            SchnorrSigHashType::None, None, None,
+        );
+        run_test_taproot_sighash(
+            "eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d7300000000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a9143dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9905ede1722b800c0005f246641000000",
+            "013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d9029b265c55",
+            0,
+            "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae",
+            SchnorrSigHashType::NonePlusAnyoneCanPay, None, None,
+        );
+        run_test_taproot_sighash(
+            "02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000",
+            "01efa558000000000022512007071ea3dc7e331b0687d0193d1e6d6ed10e645ef36f10ef8831d5e522ac9e80",
+            0,
+            "30239345177cadd0e3ea413d49803580abb6cb27971b481b7788a78d35117a88",
+            SchnorrSigHashType::Single, None, None,
+        );
+        run_test_taproot_sighash(
+            "0100000001aa6deae89d5e0aaca58714fc76ef6f3c8284224888089232d4e663843ed3ab3eae010000008b6657a60450cb4c0000000000160014a3d42b5413ef0c0701c4702f3cd7d4df222c147058020000000000001976a91430b4ed8723a4ee8992aa2c8814cfe5c3ad0ab9d988ac5802000000000000160014365b1166a6ed0a5e8e9dff17a6d00bbb43454bc758020000000000001976a914bc98c51a84fe7fad5dc380eb8b39586eff47241688ac4f313247",
+            "0107af4e00000000002251202c36d243dfc06cb56a248e62df27ecba7417307511a81ae61aa41c597a929c69",
+            0,
+            "bf9c83f26c6dd16449e4921f813f551c4218e86f2ec906ca8611175b41b566df",
+            SchnorrSigHashType::SinglePlusAnyoneCanPay, None, None,
+        );
+    }
+
+    #[test]
+    fn annex_and_script_spend_sighashes() {
+        run_test_taproot_sighash(
+            "0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000",
+            "01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010",
+            0,
+            "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c",
+            SchnorrSigHashType::SinglePlusAnyoneCanPay,
+            Some("507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e"),
+            None,
+        );
+        run_test_taproot_sighash(
+            "020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000",
+            "011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182",
+            0,
+            "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e",
+            SchnorrSigHashType::All,
+            None,
+            Some("20cc4e1107aea1d170c5ff5b6817e1303010049724fb3caa7941792ea9d29b3e2bacab"),
+        );
+        run_test_taproot_sighash(
+            "020000000132fb72cb8fba496755f027a9743e2d698c831fdb8304e4d1a346ac92cbf51acba50100000026bdc7df044aad34000000000017a9144fa2554ed6174586854fa3bc01de58dcf33567d0875802000000000000160014950367e1e62cdf240b35b883fc2f5e39f0eb9ab95802000000000000160014950367e1e62cdf240b35b883fc2f5e39f0eb9ab958020000000000001600141b31217d48ccc8760dcc0710fade5866d628e733a02d5122",
+            "011458360000000000225120a7baec3fb9f84614e3899fcc010c638f80f13539344120e1f4d8b68a9a011a13",
+            0,
+            "a0042aa434f9a75904b64043f2a283f8b4c143c7f4f7f49a6cbe5b9f745f4c15",
+            SchnorrSigHashType::All,
+            Some("50a6272b470e1460e3332ade7bb14b81671c564fb6245761bd5bd531394b28860e0b3808ab229fb51791fb6ae6fa82d915b2efb8f6df83ae1f5ab3db13e30928875e2a22b749d89358de481f19286cd4caa792ce27f9559082d227a731c5486882cc707f83da361c51b7aadd9a0cf68fe7480c410fa137b454482d9a1ebf0f96d760b4d61426fc109c6e8e99a508372c45caa7b000a41f8251305da3f206c1849985ba03f3d9592832b4053afbd23ab25d0465df0bc25a36c223aacf8e04ec736a418c72dc319e4da3e972e349713ca600965e7c665f2090d5a70e241ac164115a1f5639f28b1773327715ca307ace64a2de7f0e3df70a2ffee3857689f909c0dad46d8a20fa373a4cc6eed6d4c9806bf146f0d76baae1"),
+            Some("7520ab9160dd8299dc1367659be3e8f66781fe440d52940c7f8d314a89b9f2698d406ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6ead6eadac"),
+        );
+
+        // Same script spend through the leaf hash API
+        let tx: Transaction = deserialize(&Vec::<u8>::from_hex("020000000189fc651483f9296b906455dd939813bf086b1bbe7c77635e157c8e14ae29062195010000004445b5c7044561320000000000160014331414dbdada7fb578f700f38fb69995fc9b5ab958020000000000001976a914268db0a8104cc6d8afd91233cc8b3d1ace8ac3ef88ac580200000000000017a914ec00dcb368d6a693e11986d265f659d2f59e8be2875802000000000000160014c715799a49a0bae3956df9c17cb4440a673ac0df6f010000").unwrap()).unwrap();
+        let prevouts: Vec<TxOut> = deserialize(&Vec::<u8>::from_hex("011bec34000000000022512028055142ea437db73382e991861446040b61dd2185c4891d7daf6893d79f7182").unwrap()).unwrap();
+        let leaf_hash = TapLeafHash::from_slice(&Vec::<u8>::from_hex("15a2530514e399f8b5cf0b3d3112cf5b289eaa3e308ba2071b58392fdc6da68a").unwrap()).unwrap();
+        let hash = SigHashCache::new(&tx).taproot_script_spend_signature_hash(0, &Prevouts::All(&prevouts), leaf_hash, SchnorrSigHashType::All).unwrap();
+        assert_eq!(hash.into_inner().to_hex(), "d66de5274a60400c7b08c86ba6b7f198f40660079edf53aca89d2a9501317f2e");
+    }
+
+    #[test]
+    fn segwit_matches_bip143() {
+        let tx: Transaction = deserialize(&Vec::<u8>::from_hex(
+            "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000\
+            0000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a01000000\
+            00ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093\
+            510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000"
+        ).unwrap()).unwrap();
+        let script_code = Script::from(Vec::<u8>::from_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        let mut cache = SigHashCache::new(&tx);
+        let mut bip143_cache = ::util::bip143::SigHashCache::new(&tx);
+        for &t in [SigHashType::All, SigHashType::None, SigHashType::Single, SigHashType::AllPlusAnyoneCanPay].iter() {
+            assert_eq!(
+                cache.segwit_signature_hash(1, &script_code, 600_000_000, t).unwrap(),
+                bip143_cache.signature_hash(1, &script_code, 600_000_000, t)
+            );
+        }
+        assert_eq!(
+            cache.segwit_signature_hash(1, &script_code, 600_000_000, SigHashType::All).unwrap(),
+            hex_hash!(SigHash, "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670")
+        );
+    }
+
+    #[test]
+    fn sighash_errors() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: vec![],
+        };
+        let mut cache = SigHashCache::new(&tx);
+
+        let no_prevouts: Vec<TxOut> = vec![];
+        assert_eq!(
+            cache.taproot_key_spend_signature_hash(0, &Prevouts::All(&no_prevouts), SchnorrSigHashType::All),
+            Err(Error::PrevoutsSize)
+        );
+        let prevout = TxOut::default();
+        assert_eq!(
+            cache.taproot_key_spend_signature_hash(0, &Prevouts::One(1, &prevout), SchnorrSigHashType::All),
+            Err(Error::PrevoutKind)
+        );
+        assert_eq!(
+            cache.taproot_key_spend_signature_hash(0, &Prevouts::One(1, &prevout), SchnorrSigHashType::AllPlusAnyoneCanPay),
+            Err(Error::PrevoutIndex)
+        );
+        assert_eq!(
+            cache.taproot_key_spend_signature_hash(10, &Prevouts::One(10, &prevout), SchnorrSigHashType::AllPlusAnyoneCanPay),
+            Err(Error::IndexOutOfInputsBounds { index: 10, inputs_size: 1 })
+        );
+        assert_eq!(
+            cache.taproot_key_spend_signature_hash(0, &Prevouts::One(0, &prevout), SchnorrSigHashType::SinglePlusAnyoneCanPay),
+            Err(Error::SingleWithoutCorrespondingOutput { index: 0, outputs_size: 0 })
+        );
+        assert_eq!(
+            cache.segwit_signature_hash(1, &Script::new(), 0, SigHashType::All),
+            Err(Error::IndexOutOfInputsBounds { index: 1, inputs_size: 1 })
+        );
+
+        assert_eq!(Annex::new(&[]), Err(Error::WrongAnnex));
+        assert_eq!(Annex::new(&[0x51, 0x50]), Err(Error::WrongAnnex));
+        assert_eq!(Annex::new(&[0x50]).unwrap().as_bytes(), &[0x50]);
+    }
+
+    #[test]
+    fn sighash_type_from_u8_and_str() {
+        for &b in [0x04u8, 0x80, 0x84, 0xff].iter() {
+            assert_eq!(SchnorrSigHashType::from_u8(b), Err(Error::InvalidSigHashType(b)));
+        }
+        let types = [
+            ("SIGHASH_DEFAULT", SchnorrSigHashType::Default),
+            ("SIGHASH_ALL", SchnorrSigHashType::All),
+            ("SIGHASH_NONE", SchnorrSigHashType::None),
+            ("SIGHASH_SINGLE", SchnorrSigHashType::Single),
+            ("SIGHASH_ALL|SIGHASH_ANYONECANPAY", SchnorrSigHashType::AllPlusAnyoneCanPay),
+            ("SIGHASH_NONE|SIGHASH_ANYONECANPAY", SchnorrSigHashType::NonePlusAnyoneCanPay),
+            ("SIGHASH_SINGLE|SIGHASH_ANYONECANPAY", SchnorrSigHashType::SinglePlusAnyoneCanPay),
+        ];
+        for &(s, t) in types.iter() {
+            assert_eq!(t.to_string(), s);
+            assert_eq!(SchnorrSigHashType::from_str(s).unwrap(), t);
+            assert_eq!(SchnorrSigHashType::from_u8(t.as_u8()).unwrap(), t);
+        }
+        assert!(SchnorrSigHashType::from_str("SIGHASH_ALL | SIGHASH_ANYONECANPAY").is_err());
+    }
+}
@@ -13,8 +13,20 @@
 
 //! Taproot
 //!
+//! This module provides support for the taproot tagged hashes, for building
+//! taproot script trees and for the control blocks proving the inclusion of a
+//! script in such a tree, as defined in BIP341.
+//!
+
+use std::{error, fmt, io};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
-use hashes::{sha256, sha256t, Hash};
+use hashes::{sha256, sha256t, Hash, HashEngine};
+use secp256k1::{self, Secp256k1};
+use blockdata::script::Script;
+use consensus::Encodable;
+use util::schnorr;
 
 /// The SHA-256 midstate value for the TapLeaf hash.
 const MIDSTATE_TAPLEAF: [u8; 32] = [
@@ -76,12 +88,600 @@ sha256t_hash_newtype!(TapSighashHash, TapSighashTag, MIDSTATE_TAPSIGHASH, 64,
     doc="Taproot-tagged hash for the taproot signature hash", true
 );
 
+impl TapTweakHash {
+    /// Computes the tweak of an internal key committing to the given script
+    /// tree merkle root, or to no script at all if `merkle_root` is `None`.
+    pub fn from_key_and_tweak(internal_key: &schnorr::PublicKey, merkle_root: Option<TapBranchHash>) -> TapTweakHash {
+        let mut eng = TapTweakHash::engine();
+        eng.input(&internal_key.serialize());
+        if let Some(root) = merkle_root {
+            eng.input(&root[..]);
+        }
+        TapTweakHash::from_engine(eng)
+    }
+}
+
+impl TapLeafHash {
+    /// Computes the leaf hash of a script with the given leaf version.
+    pub fn from_script(script: &Script, ver: LeafVersion) -> TapLeafHash {
+        let mut eng = TapLeafHash::engine();
+        ver.as_u8().consensus_encode(&mut eng).expect("engines don't error");
+        script.consensus_encode(&mut eng).expect("engines don't error");
+        TapLeafHash::from_engine(eng)
+    }
+}
+
+impl TapBranchHash {
+    /// Computes the branch hash of two child nodes. The children are sorted
+    /// lexicographically before hashing, as required by BIP341.
+    pub fn from_node_hashes(a: sha256::Hash, b: sha256::Hash) -> TapBranchHash {
+        let mut eng = TapBranchHash::engine();
+        if a < b {
+            eng.input(&a[..]);
+            eng.input(&b[..]);
+        } else {
+            eng.input(&b[..]);
+            eng.input(&a[..]);
+        }
+        TapBranchHash::from_engine(eng)
+    }
+}
+
+/// Maximum depth of a taproot script tree (and thus of a control block merkle path).
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+/// Size of a single merkle path node in a control block.
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+/// Mask extracting the leaf version from the first byte of a control block.
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+/// Leaf version of BIP342 tapscript.
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+/// Prefix of the taproot annex, which can therefore never be a leaf version.
+pub const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+/// Size of a control block without any merkle path node.
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+/// Maximum size of a control block.
+pub const TAPROOT_CONTROL_MAX_SIZE: usize =
+    TAPROOT_CONTROL_BASE_SIZE + TAPROOT_CONTROL_NODE_SIZE * TAPROOT_CONTROL_MAX_NODE_COUNT;
+
+/// The version of a taproot script leaf.
+///
+/// Only [`TAPROOT_LEAF_TAPSCRIPT`] has defined semantics today, but any even
+/// value other than the annex prefix is a valid (future) leaf version.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct LeafVersion(u8);
+
+impl Default for LeafVersion {
+    fn default() -> Self {
+        LeafVersion(TAPROOT_LEAF_TAPSCRIPT)
+    }
+}
+
+impl LeafVersion {
+    /// Obtains the leaf version from its byte representation. Fails if the
+    /// lowest bit is set or if the value is the annex prefix.
+    pub fn from_u8(ver: u8) -> Result<Self, TaprootError> {
+        if ver & TAPROOT_LEAF_MASK == ver && ver != TAPROOT_ANNEX_PREFIX {
+            Ok(LeafVersion(ver))
+        } else {
+            Err(TaprootError::InvalidTaprootLeafVersion(ver))
+        }
+    }
+
+    /// Returns the byte representation of the leaf version.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+}
+
+impl fmt::Display for LeafVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#04x}", self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for LeafVersion {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u8(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for LeafVersion {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let ver = u8::deserialize(d)?;
+        LeafVersion::from_u8(ver).map_err(|_| {
+            ::serde::de::Error::invalid_value(
+                ::serde::de::Unexpected::Unsigned(ver as u64),
+                &"an even taproot leaf version other than 0x50",
+            )
+        })
+    }
+}
+
+/// The merkle path proving the inclusion of a node in a taproot script tree,
+/// ordered from the node upwards to the root.
+///
+/// The hashes are plain `sha256::Hash`es since a path may contain both leaf
+/// and branch hashes.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TaprootMerkleBranch(Vec<sha256::Hash>);
+
+impl TaprootMerkleBranch {
+    /// Creates a merkle branch from a list of hashes, failing if it is
+    /// deeper than [`TAPROOT_CONTROL_MAX_NODE_COUNT`].
+    pub fn from_inner(inner: Vec<sha256::Hash>) -> Result<Self, TaprootError> {
+        if inner.len() > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            Err(TaprootError::InvalidMerkleTreeDepth(inner.len()))
+        } else {
+            Ok(TaprootMerkleBranch(inner))
+        }
+    }
+
+    /// Parses a merkle branch from the concatenation of its hashes.
+    pub fn from_slice(sl: &[u8]) -> Result<Self, TaprootError> {
+        if sl.len() % TAPROOT_CONTROL_NODE_SIZE != 0 {
+            Err(TaprootError::InvalidMerkleBranchSize(sl.len()))
+        } else if sl.len() > TAPROOT_CONTROL_NODE_SIZE * TAPROOT_CONTROL_MAX_NODE_COUNT {
+            Err(TaprootError::InvalidMerkleTreeDepth(sl.len() / TAPROOT_CONTROL_NODE_SIZE))
+        } else {
+            let inner = sl
+                .chunks(TAPROOT_CONTROL_NODE_SIZE)
+                .map(|chunk| sha256::Hash::from_slice(chunk).expect("chunks are 32 bytes"))
+                .collect();
+            Ok(TaprootMerkleBranch(inner))
+        }
+    }
+
+    /// Returns the hashes of the branch.
+    pub fn as_inner(&self) -> &[sha256::Hash] {
+        &self.0
+    }
+
+    /// Converts the branch into its list of hashes.
+    pub fn into_inner(self) -> Vec<sha256::Hash> {
+        self.0
+    }
+
+    /// Writes the concatenated hashes of the branch, returning the number of
+    /// bytes written.
+    pub fn encode<W: io::Write>(&self, mut writer: W) -> Result<usize, io::Error> {
+        for hash in &self.0 {
+            writer.write_all(&hash[..])?;
+        }
+        Ok(self.0.len() * TAPROOT_CONTROL_NODE_SIZE)
+    }
+
+    /// Serializes the branch as the concatenation of its hashes.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.0.len() * TAPROOT_CONTROL_NODE_SIZE);
+        self.encode(&mut buf).expect("vectors don't error");
+        buf
+    }
+
+    /// Appends the hashing partner of the next level up.
+    fn push(&mut self, hash: sha256::Hash) -> Result<(), TaprootBuilderError> {
+        if self.0.len() >= TAPROOT_CONTROL_MAX_NODE_COUNT {
+            Err(TaprootBuilderError::InvalidMerkleTreeDepth(self.0.len() + 1))
+        } else {
+            self.0.push(hash);
+            Ok(())
+        }
+    }
+}
+
+/// The control block of a taproot script path spend, which is the last witness
+/// element (before the annex, if any). It proves that the spent script is
+/// committed to by the output key.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ControlBlock {
+    /// The leaf version of the spent script
+    pub leaf_version: LeafVersion,
+    /// Whether the output key (not the internal key) has an odd Y coordinate
+    pub output_key_parity: bool,
+    /// The internal key of the output
+    pub internal_key: schnorr::PublicKey,
+    /// The merkle path from the spent leaf to the merkle root
+    pub merkle_branch: TaprootMerkleBranch,
+}
+
+impl ControlBlock {
+    /// Parses a control block from its serialization: a byte carrying the leaf
+    /// version and output key parity, the 32-byte internal key and the merkle
+    /// path, up to 128 32-byte hashes.
+    pub fn from_slice(sl: &[u8]) -> Result<ControlBlock, TaprootError> {
+        if sl.len() < TAPROOT_CONTROL_BASE_SIZE
+            || sl.len() > TAPROOT_CONTROL_MAX_SIZE
+            || (sl.len() - TAPROOT_CONTROL_BASE_SIZE) % TAPROOT_CONTROL_NODE_SIZE != 0
+        {
+            return Err(TaprootError::InvalidControlBlockSize(sl.len()));
+        }
+        let leaf_version = LeafVersion::from_u8(sl[0] & TAPROOT_LEAF_MASK)?;
+        let internal_key = schnorr::PublicKey::from_slice(&sl[1..TAPROOT_CONTROL_BASE_SIZE])
+            .map_err(TaprootError::InvalidInternalKey)?;
+        let merkle_branch = TaprootMerkleBranch::from_slice(&sl[TAPROOT_CONTROL_BASE_SIZE..])?;
+        Ok(ControlBlock {
+            leaf_version: leaf_version,
+            output_key_parity: sl[0] & 1 == 1,
+            internal_key: internal_key,
+            merkle_branch: merkle_branch,
+        })
+    }
+
+    /// The size of the serialized control block.
+    pub fn size(&self) -> usize {
+        TAPROOT_CONTROL_BASE_SIZE + TAPROOT_CONTROL_NODE_SIZE * self.merkle_branch.as_inner().len()
+    }
+
+    /// Writes the serialized control block, returning the number of bytes
+    /// written.
+    pub fn encode<W: io::Write>(&self, mut writer: W) -> Result<usize, io::Error> {
+        let first_byte = self.leaf_version.as_u8() | self.output_key_parity as u8;
+        writer.write_all(&[first_byte])?;
+        writer.write_all(&self.internal_key.serialize())?;
+        self.merkle_branch.encode(&mut writer)?;
+        Ok(self.size())
+    }
+
+    /// Serializes the control block, as it appears in the witness (without
+    /// any length prefix).
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size());
+        self.encode(&mut buf).expect("vectors don't error");
+        buf
+    }
+
+    /// Verifies that the control block proves the commitment of `output_key`
+    /// to `script`. This does not check that the script is satisfied.
+    pub fn verify_taproot_commitment<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        output_key: &schnorr::PublicKey,
+        script: &Script,
+    ) -> bool {
+        let leaf_hash = TapLeafHash::from_script(script, self.leaf_version);
+        let mut hash = sha256::Hash::from_inner(leaf_hash.into_inner());
+        for partner in self.merkle_branch.as_inner() {
+            hash = sha256::Hash::from_inner(TapBranchHash::from_node_hashes(hash, *partner).into_inner());
+        }
+        let merkle_root = TapBranchHash::from_inner(hash.into_inner());
+        let tweak = TapTweakHash::from_key_and_tweak(&self.internal_key, Some(merkle_root));
+        self.internal_key.tweak_add_check(secp, output_key, self.output_key_parity, tweak.into_inner())
+    }
+}
+
+/// A script leaf of a taproot tree together with its merkle path.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScriptLeaf {
+    /// The leaf script
+    script: Script,
+    /// The leaf version
+    ver: LeafVersion,
+    /// The hashing partners from the leaf up to the node it is part of
+    merkle_branch: TaprootMerkleBranch,
+}
+
+impl ScriptLeaf {
+    fn new(script: Script, ver: LeafVersion) -> ScriptLeaf {
+        ScriptLeaf {
+            script: script,
+            ver: ver,
+            merkle_branch: TaprootMerkleBranch::default(),
+        }
+    }
+
+    /// Returns the leaf script.
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    /// Returns the leaf version.
+    pub fn leaf_version(&self) -> LeafVersion {
+        self.ver
+    }
+
+    /// Returns the merkle path of the leaf.
+    pub fn merkle_branch(&self) -> &TaprootMerkleBranch {
+        &self.merkle_branch
+    }
+
+    /// Returns the depth of the leaf in the tree.
+    pub fn depth(&self) -> usize {
+        self.merkle_branch.0.len()
+    }
+
+    /// Computes the leaf hash.
+    pub fn leaf_hash(&self) -> TapLeafHash {
+        TapLeafHash::from_script(&self.script, self.ver)
+    }
+}
+
+/// A (sub)tree of a taproot script tree, storing its hash along with all the
+/// known script leaves below it. Subtrees may be hidden, in which case only
+/// their hash is known.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NodeInfo {
+    /// The merkle hash of this node
+    hash: sha256::Hash,
+    /// The known leaves below this node
+    leaves: Vec<ScriptLeaf>,
+    /// Whether any subtree below this node is hidden
+    has_hidden_nodes: bool,
+}
+
+impl NodeInfo {
+    /// Creates a hidden node, of which only the hash is known.
+    pub fn new_hidden(hash: sha256::Hash) -> NodeInfo {
+        NodeInfo {
+            hash: hash,
+            leaves: vec![],
+            has_hidden_nodes: true,
+        }
+    }
+
+    /// Creates a node consisting of a single script leaf.
+    pub fn new_leaf_with_ver(script: Script, ver: LeafVersion) -> NodeInfo {
+        let leaf = ScriptLeaf::new(script, ver);
+        NodeInfo {
+            hash: sha256::Hash::from_inner(leaf.leaf_hash().into_inner()),
+            leaves: vec![leaf],
+            has_hidden_nodes: false,
+        }
+    }
+
+    /// Combines two nodes into their parent node, extending the merkle paths
+    /// of all the leaves below them.
+    pub fn combine(a: NodeInfo, b: NodeInfo) -> Result<NodeInfo, TaprootBuilderError> {
+        let mut leaves = Vec::with_capacity(a.leaves.len() + b.leaves.len());
+        for mut leaf in a.leaves {
+            leaf.merkle_branch.push(b.hash)?;
+            leaves.push(leaf);
+        }
+        for mut leaf in b.leaves {
+            leaf.merkle_branch.push(a.hash)?;
+            leaves.push(leaf);
+        }
+        let hash = TapBranchHash::from_node_hashes(a.hash, b.hash);
+        Ok(NodeInfo {
+            hash: sha256::Hash::from_inner(hash.into_inner()),
+            leaves: leaves,
+            has_hidden_nodes: a.has_hidden_nodes || b.has_hidden_nodes,
+        })
+    }
+
+    /// Returns the merkle hash of the node. For the root node of a tree this
+    /// is the BIP341 merkle root.
+    pub fn merkle_root(&self) -> TapBranchHash {
+        TapBranchHash::from_inner(self.hash.into_inner())
+    }
+
+    /// Returns the known script leaves below this node, in the order they
+    /// were added, with their merkle paths up to this node.
+    pub fn leaves(&self) -> &[ScriptLeaf] {
+        &self.leaves
+    }
+
+    /// Whether some of the subtrees below this node are hidden, in which case
+    /// [`NodeInfo::leaves`] does not hold all the leaves of the tree.
+    pub fn has_hidden_nodes(&self) -> bool {
+        self.has_hidden_nodes
+    }
+}
+
+/// Builder for taproot script trees.
+///
+/// Leaves (and hidden nodes) are added along with their depth in the tree, in
+/// the order of a depth-first walk of the tree, the root being at depth 0.
+/// Alternatively a tree with optimal expected spending depth can be built
+/// from a set of weighted scripts with [`TaprootBuilder::with_huffman_tree`].
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TaprootBuilder {
+    // For every level of the tree, the completed left-hand node at that level
+    // which is waiting for its right-hand sibling, if any. Index 0 is the
+    // root. The last element is never `None` and the builder is done when
+    // only the root is left.
+    //
+    // For example, building the tree
+    //
+    //            N0
+    //          /    \
+    //        N1      N2
+    //       /  \    /  \
+    //      A    B  C    N3
+    //                  /  \
+    //                 D    E
+    //
+    // goes through {None, None, A}, {None, N1}, {None, N1, C},
+    // {None, N1, C, D} and finally {N0}, as E is combined with D, then with C
+    // and then with N1.
+    branch: Vec<Option<NodeInfo>>,
+}
+
+impl TaprootBuilder {
+    /// Creates a new empty builder.
+    pub fn new() -> TaprootBuilder {
+        TaprootBuilder { branch: vec![] }
+    }
+
+    /// Creates a builder holding the Huffman tree of the given scripts, which
+    /// minimizes the expected depth of the spent leaf when the weights are
+    /// proportional to the probability of each script being used. All the
+    /// scripts get the tapscript leaf version.
+    pub fn with_huffman_tree<I>(script_weights: I) -> Result<TaprootBuilder, TaprootBuilderError>
+        where I: IntoIterator<Item=(u32, Script)>,
+    {
+        let mut node_weights = BinaryHeap::<(Reverse<u64>, NodeInfo)>::new();
+        for (weight, script) in script_weights {
+            node_weights.push((
+                Reverse(weight as u64),
+                NodeInfo::new_leaf_with_ver(script, LeafVersion::default()),
+            ));
+        }
+        if node_weights.is_empty() {
+            return Err(TaprootBuilderError::EmptyTree);
+        }
+        while node_weights.len() > 1 {
+            let (w1, n1) = node_weights.pop().expect("at least two nodes");
+            let (w2, n2) = node_weights.pop().expect("at least two nodes");
+            // Can't overflow: this would take more than 2^32 maximal u32 weights
+            let weight = Reverse(w1.0 + w2.0);
+            node_weights.push((weight, NodeInfo::combine(n1, n2)?));
+        }
+        let (_, root) = node_weights.pop().expect("exactly one node left");
+        Ok(TaprootBuilder { branch: vec![Some(root)] })
+    }
+
+    /// Adds a script leaf with the given leaf version at `depth`.
+    pub fn add_leaf_with_ver(self, depth: usize, script: Script, ver: LeafVersion) -> Result<TaprootBuilder, TaprootBuilderError> {
+        self.insert(NodeInfo::new_leaf_with_ver(script, ver), depth)
+    }
+
+    /// Adds a tapscript leaf at `depth`.
+    pub fn add_leaf(self, depth: usize, script: Script) -> Result<TaprootBuilder, TaprootBuilderError> {
+        self.add_leaf_with_ver(depth, script, LeafVersion::default())
+    }
+
+    /// Adds a hidden subtree, of which only the hash is known, at `depth`.
+    pub fn add_hidden(self, depth: usize, hash: sha256::Hash) -> Result<TaprootBuilder, TaprootBuilderError> {
+        self.insert(NodeInfo::new_hidden(hash), depth)
+    }
+
+    /// Whether the added nodes form a complete tree.
+    pub fn is_complete(&self) -> bool {
+        self.branch.len() == 1 && self.branch[0].is_some()
+    }
+
+    /// Finishes the tree, returning its root node which holds the merkle root
+    /// and the merkle paths of all the leaves.
+    pub fn into_node_info(mut self) -> Result<NodeInfo, TaprootBuilderError> {
+        match self.branch.len() {
+            0 => Err(TaprootBuilderError::EmptyTree),
+            1 => Ok(self.branch.pop().unwrap().expect("last element is never None")),
+            _ => Err(TaprootBuilderError::IncompleteTree),
+        }
+    }
+
+    fn insert(mut self, mut node: NodeInfo, mut depth: usize) -> Result<TaprootBuilder, TaprootBuilderError> {
+        if depth > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(TaprootBuilderError::InvalidMerkleTreeDepth(depth));
+        }
+        // A node shallower than an unfinished branch would not be in DFS order
+        if depth + 1 < self.branch.len() {
+            return Err(TaprootBuilderError::NodeNotInDfsOrder);
+        }
+
+        // Combine with the pending left-hand siblings as far up as possible
+        while self.branch.len() == depth + 1 {
+            let sibling = match self.branch.pop() {
+                Some(Some(sibling)) => sibling,
+                Some(None) => {
+                    self.branch.push(None);
+                    break;
+                }
+                None => unreachable!("branch is not empty"),
+            };
+            if depth == 0 {
+                return Err(TaprootBuilderError::OverCompleteTree);
+            }
+            node = NodeInfo::combine(sibling, node)?;
+            depth -= 1;
+        }
+
+        while self.branch.len() < depth + 1 {
+            self.branch.push(None);
+        }
+        self.branch[depth] = Some(node);
+        Ok(self)
+    }
+}
+
+/// Errors which can happen when building a taproot script tree.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TaprootBuilderError {
+    /// A node was added deeper than 128 levels.
+    InvalidMerkleTreeDepth(usize),
+    /// Nodes were not added in depth-first order.
+    NodeNotInDfsOrder,
+    /// A second node was added at the root level.
+    OverCompleteTree,
+    /// The tree was finalized with unpaired nodes.
+    IncompleteTree,
+    /// The tree was finalized without any node.
+    EmptyTree,
+}
+
+impl fmt::Display for TaprootBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TaprootBuilderError::InvalidMerkleTreeDepth(d) => write!(
+                f, "merkle tree depth {} exceeds the maximum of {}", d, TAPROOT_CONTROL_MAX_NODE_COUNT
+            ),
+            TaprootBuilderError::NodeNotInDfsOrder => f.write_str("leaves must be added in depth-first order"),
+            TaprootBuilderError::OverCompleteTree => f.write_str("the tree already has a root"),
+            TaprootBuilderError::IncompleteTree => f.write_str("the tree is incomplete"),
+            TaprootBuilderError::EmptyTree => f.write_str("the tree is empty"),
+        }
+    }
+}
+
+impl error::Error for TaprootBuilderError {}
+
+/// Errors which can happen when parsing taproot structures.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TaprootError {
+    /// A merkle branch whose size is not a multiple of 32 bytes.
+    InvalidMerkleBranchSize(usize),
+    /// A merkle branch deeper than 128 levels.
+    InvalidMerkleTreeDepth(usize),
+    /// A leaf version which is odd or equal to the annex prefix.
+    InvalidTaprootLeafVersion(u8),
+    /// A control block whose size is not 33 + 32m bytes with m at most 128.
+    InvalidControlBlockSize(usize),
+    /// An invalid x-only internal key.
+    InvalidInternalKey(secp256k1::Error),
+}
+
+impl fmt::Display for TaprootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TaprootError::InvalidMerkleBranchSize(s) => write!(
+                f, "merkle branch size {} is not a multiple of {}", s, TAPROOT_CONTROL_NODE_SIZE
+            ),
+            TaprootError::InvalidMerkleTreeDepth(d) => write!(
+                f, "merkle tree depth {} exceeds the maximum of {}", d, TAPROOT_CONTROL_MAX_NODE_COUNT
+            ),
+            TaprootError::InvalidTaprootLeafVersion(v) => write!(f, "invalid leaf version {:#04x}", v),
+            TaprootError::InvalidControlBlockSize(s) => write!(
+                f, "control block size {} is not of the form 33 + 32m with m <= {}", s, TAPROOT_CONTROL_MAX_NODE_COUNT
+            ),
+            TaprootError::InvalidInternalKey(ref e) => write!(f, "invalid internal key: {}", e),
+        }
+    }
+}
+
+impl error::Error for TaprootError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            TaprootError::InvalidInternalKey(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use hashes::hex::ToHex;
+    use hashes::hex::{FromHex, ToHex};
     use hashes::sha256t::Tag;
     use hashes::{sha256, Hash, HashEngine};
+    use blockdata::opcodes;
+    use blockdata::script::Builder;
+    use std::str::FromStr;
 
     fn tag_engine(tag_name: &str) -> sha256::HashEngine {
         let mut engine = sha256::Hash::engine();
@@ -163,4 +763,250 @@ mod test {
             "cd10c023c300fb9a507dff136370fba1d8a0566667cfafc4099a8803e00dfdc2"
         );
     }
+
+    fn leaf_hash_hex(script_hex: &str, ver: u8) -> String {
+        let script = Script::from_hex(script_hex).unwrap();
+        TapLeafHash::from_script(&script, LeafVersion::from_u8(ver).unwrap())[..].to_hex()
+    }
+
+    #[test]
+    fn leaf_version() {
+        assert_eq!(LeafVersion::default().as_u8(), 0xc0);
+        assert_eq!(LeafVersion::from_u8(0xfa).unwrap().as_u8(), 0xfa);
+        assert_eq!(LeafVersion::from_u8(0xc1), Err(TaprootError::InvalidTaprootLeafVersion(0xc1)));
+        assert_eq!(LeafVersion::from_u8(0x50), Err(TaprootError::InvalidTaprootLeafVersion(0x50)));
+    }
+
+    #[test]
+    fn merkle_branch_parse() {
+        let bytes = Vec::<u8>::from_hex(
+            "8ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7\
+             f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a"
+        ).unwrap();
+        let branch = TaprootMerkleBranch::from_slice(&bytes).unwrap();
+        assert_eq!(branch.as_inner().len(), 2);
+        assert_eq!(branch.serialize(), bytes);
+        assert_eq!(TaprootMerkleBranch::from_slice(&bytes[1..]), Err(TaprootError::InvalidMerkleBranchSize(63)));
+        assert_eq!(
+            TaprootMerkleBranch::from_slice(&[0; 32 * 129]),
+            Err(TaprootError::InvalidMerkleTreeDepth(129))
+        );
+    }
+
+    #[test]
+    fn bip341_script_trees() {
+        // Test vectors from BIP341 wallet-test-vectors.json. Hashes are given in
+        // their natural (non-reversed) byte order there.
+
+        // Single leaf: the merkle root is the leaf hash
+        let root = TaprootBuilder::new()
+            .add_leaf(0, hex_script!("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")).unwrap()
+            .into_node_info().unwrap();
+        assert_eq!(root.merkle_root()[..].to_hex(), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+        assert!(root.leaves()[0].merkle_branch().as_inner().is_empty());
+
+        // Two leaves, one of them with a future leaf version
+        assert_eq!(
+            leaf_hash_hex("20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac", 0xc0),
+            "8ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7"
+        );
+        assert_eq!(
+            leaf_hash_hex("06424950333431", 0xfa),
+            "f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a"
+        );
+        let root = TaprootBuilder::new()
+            .add_leaf(1, hex_script!("20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac")).unwrap()
+            .add_leaf_with_ver(1, hex_script!("06424950333431"), LeafVersion::from_u8(0xfa).unwrap()).unwrap()
+            .into_node_info().unwrap();
+        assert_eq!(root.merkle_root()[..].to_hex(), "6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef");
+        assert_eq!(
+            root.leaves()[0].merkle_branch().serialize().to_hex(),
+            "f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a"
+        );
+        assert_eq!(
+            root.leaves()[1].merkle_branch().serialize().to_hex(),
+            "8ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7"
+        );
+
+        // Three leaves at depths 1, 2 and 2
+        let root = TaprootBuilder::new()
+            .add_leaf(1, hex_script!("2072ea6adcf1d371dea8fba1035a09f3d24ed5a059799bae114084130ee5898e69ac")).unwrap()
+            .add_leaf(2, hex_script!("202352d137f2f3ab38d1eaa976758873377fa5ebb817372c71e2c542313d4abda8ac")).unwrap()
+            .add_leaf(2, hex_script!("207337c0dd4253cb86f2c43a2351aadd82cccb12a172cd120452b9bb8324f2186aac")).unwrap()
+            .into_node_info().unwrap();
+        assert_eq!(root.merkle_root()[..].to_hex(), "ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2");
+        let leaf_hashes: Vec<String> = root.leaves().iter().map(|l| l.leaf_hash()[..].to_hex()).collect();
+        assert_eq!(leaf_hashes, vec![
+            "2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+            "ba982a91d4fc552163cb1c0da03676102d5b7a014304c01f0c77b2b8e888de1c",
+            "9e31407bffa15fefbf5090b149d53959ecdf3f62b1246780238c24501d5ceaf6",
+        ]);
+        let branches: Vec<String> = root.leaves().iter().map(|l| l.merkle_branch().serialize().to_hex()).collect();
+        assert_eq!(branches, vec![
+            "ffe578e9ea769027e4f5a3de40732f75a88a6353a09d767ddeb66accef85e553",
+            "9e31407bffa15fefbf5090b149d53959ecdf3f62b1246780238c24501d5ceaf6\
+             2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+            "ba982a91d4fc552163cb1c0da03676102d5b7a014304c01f0c77b2b8e888de1c\
+             2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+        ]);
+
+        // The same tree with the first leaf hidden
+        let hidden = sha256::Hash::from_slice(&Vec::<u8>::from_hex("2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817").unwrap()).unwrap();
+        let root = TaprootBuilder::new()
+            .add_hidden(1, hidden).unwrap()
+            .add_leaf(2, hex_script!("202352d137f2f3ab38d1eaa976758873377fa5ebb817372c71e2c542313d4abda8ac")).unwrap()
+            .add_leaf(2, hex_script!("207337c0dd4253cb86f2c43a2351aadd82cccb12a172cd120452b9bb8324f2186aac")).unwrap()
+            .into_node_info().unwrap();
+        assert_eq!(root.merkle_root()[..].to_hex(), "ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2");
+        assert_eq!(root.leaves().len(), 2);
+    }
+
+    #[test]
+    fn builder_paths() {
+        //            N0
+        //          /    \
+        //        N1      N2
+        //       /  \    /  \
+        //      A    B  C    N3
+        //                  /  \
+        //                 D    E
+        let scripts: Vec<Script> = (0x51..0x56).map(|op| Script::from(vec![op])).collect();
+        let mut builder = TaprootBuilder::new();
+        for (script, &depth) in scripts.iter().zip([2, 2, 2, 3, 3].iter()) {
+            assert!(!builder.is_complete());
+            builder = builder.add_leaf(depth, script.clone()).unwrap();
+        }
+        assert!(builder.is_complete());
+        let root = builder.into_node_info().unwrap();
+
+        let depths: Vec<usize> = root.leaves().iter().map(|l| l.depth()).collect();
+        assert_eq!(depths, vec![2, 2, 2, 3, 3]);
+        for (leaf, script) in root.leaves().iter().zip(scripts.iter()) {
+            assert_eq!(leaf.script(), script);
+            assert_eq!(leaf.leaf_version(), LeafVersion::default());
+            // Walking up the merkle path must lead to the root
+            let mut hash = sha256::Hash::from_inner(leaf.leaf_hash().into_inner());
+            for partner in leaf.merkle_branch().as_inner() {
+                hash = sha256::Hash::from_inner(TapBranchHash::from_node_hashes(hash, *partner).into_inner());
+            }
+            assert_eq!(hash[..], root.merkle_root()[..]);
+        }
+    }
+
+    #[test]
+    fn builder_errors() {
+        let script = Script::from(vec![0x51]);
+        assert_eq!(TaprootBuilder::new().into_node_info(), Err(TaprootBuilderError::EmptyTree));
+        assert_eq!(
+            TaprootBuilder::new().add_leaf(1, script.clone()).unwrap().into_node_info(),
+            Err(TaprootBuilderError::IncompleteTree)
+        );
+        assert_eq!(
+            TaprootBuilder::new().add_leaf(0, script.clone()).unwrap().add_leaf(0, script.clone()),
+            Err(TaprootBuilderError::OverCompleteTree)
+        );
+        assert_eq!(
+            TaprootBuilder::new().add_leaf(2, script.clone()).unwrap().add_leaf(1, script.clone()),
+            Err(TaprootBuilderError::NodeNotInDfsOrder)
+        );
+        assert_eq!(
+            TaprootBuilder::new().add_leaf(129, script.clone()),
+            Err(TaprootBuilderError::InvalidMerkleTreeDepth(129))
+        );
+        assert_eq!(
+            TaprootBuilder::with_huffman_tree(vec![]),
+            Err(TaprootBuilderError::EmptyTree)
+        );
+    }
+
+    #[test]
+    fn huffman_tree() {
+        let script_weights = vec![
+            (10, Script::from(vec![0x51])),
+            (20, Script::from(vec![0x52])),
+            (20, Script::from(vec![0x53])),
+            (30, Script::from(vec![0x54])),
+            (19, Script::from(vec![0x55])),
+        ];
+        let root = TaprootBuilder::with_huffman_tree(script_weights).unwrap()
+            .into_node_info().unwrap();
+
+        // The lightest two scripts end up at the deepest level:
+        //
+        //        __/\__
+        //       /      \
+        //      /\      /\
+        //    54  52  53  /\
+        //               55 51
+        let mut depths: Vec<(u8, usize)> = root.leaves().iter()
+            .map(|l| (l.script()[0], l.depth()))
+            .collect();
+        depths.sort();
+        assert_eq!(depths, vec![(0x51, 3), (0x52, 2), (0x53, 2), (0x54, 2), (0x55, 3)]);
+    }
+
+    fn verify_tap_commitment(secp: &Secp256k1<secp256k1::VerifyOnly>, spk_hex: &str, script_hex: &str, control_block_hex: &str) {
+        let output_key = schnorr::PublicKey::from_str(&spk_hex[4..]).unwrap();
+        let script = Script::from_hex(script_hex).unwrap();
+        let control_block = ControlBlock::from_slice(&Vec::<u8>::from_hex(control_block_hex).unwrap()).unwrap();
+        assert_eq!(control_block.serialize().to_hex(), control_block_hex);
+        assert_eq!(control_block.size(), control_block_hex.len() / 2);
+        assert!(control_block.verify_taproot_commitment(secp, &output_key, &script));
+        // Any other script is not committed to
+        let other = Builder::from(script.to_bytes()).push_opcode(opcodes::OP_TRUE).into_script();
+        assert!(!control_block.verify_taproot_commitment(secp, &output_key, &other));
+    }
+
+    #[test]
+    fn control_block_verify() {
+        let secp = Secp256k1::verification_only();
+        // Test vectors obtained from printing values in feature_taproot.py from Bitcoin Core
+        verify_tap_commitment(&secp, "51205dc8e62b15e0ebdf44751676be35ba32eed2e84608b290d4061bbff136cd7ba9", "6a", "c1a9d6f66cd4b25004f526bfa873e56942f98e8e492bd79ed6532b966104817c2bda584e7d32612381cf88edc1c02e28a296e807c16ad22f591ee113946e48a71e0641e660d1e5392fb79d64838c2b84faf04b7f5f283c9d8bf83e39e177b64372a0cd22eeab7e093873e851e247714eff762d8a30be699ba4456cfe6491b282e193a071350ae099005a5950d74f73ba13077a57bc478007fb0e4d1099ce9cf3d4");
+        verify_tap_commitment(&secp, "5120e208c869c40d8827101c5ad3238018de0f3f5183d77a0c53d18ac28ddcbcd8ad", "f4", "c0a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f40090ab1f4890d51115998242ebce636efb9ede1b516d9eb8952dc1068e0335306199aaf103cceb41d9bc37ec231aca89b984b5fd3c65977ce764d51033ac65adb4da14e029b1e154a85bfd9139e7aa2720b6070a4ceba8264ca61d5d3ac27aceb9ef4b54cd43c2d1fd5e11b5c2e93cf29b91ea3dc5b832201f02f7473a28c63246");
+        verify_tap_commitment(&secp, "5120567666e7df90e0450bb608e17c01ed3fbcfa5355a5f8273e34e583bfaa70ce09", "203455139bf238a3067bd72ed77e0ab8db590330f55ed58dba7366b53bf4734279ac", "c1a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f400");
+        verify_tap_commitment(&secp, "5120580a19e47269414a55eb86d5d0c6c9b371455d9fd2154412a57dec840df99fe1", "6a", "bca0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f40042ba1bd1c63c03ccff60d4c4d53a653f87909eb3358e7fa45c9d805231fb08c933e1f4e0f9d17f591df1419df7d5b7eb5f744f404c5ef9ecdb1b89b18cafa3a816d8b5dba3205f9a9c05f866d91f40d2793a7586d502cb42f46c7a11f66ad4aa");
+        verify_tap_commitment(&secp, "5120228b94a4806254a38d6efa8a134c28ebc89546209559dfe40b2b0493bafacc5b", "6a50", "c0a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f4009c9aed3dfd11ab0e78bf87ef3bf296269dc4b0f7712140386d6980992bab4b45");
+        verify_tap_commitment(&secp, "5120b0a79103c31fe51eea61d2873bad8a25a310da319d7e7a85f825fa7a00ea3f85", "203455139bf238a3067bd72ed77e0ab8db590330f55ed58dba7366b53bf4734279ad51", "c1a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f400");
+        verify_tap_commitment(&secp, "5120017316303aed02bcdec424c851c9eacbe192b013139bd9634c4e19b3475b06e1", "61", "02a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f40050462265ca552b23cbb4fe021b474313c8cb87d4a18b3f7bdbeb2b418279ba31fc6509d829cd42336f563363cb3538d78758e0876c71e13012eb2b656eb0edb051a2420a840d5c8c6c762abc7410af2c311f606b20ca2ace56a8139f84b1379a");
+        verify_tap_commitment(&secp, "512093c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51", "04ffffffff203455139bf238a3067bd72ed77e0ab8db590330f55ed58dba7366b53bf4734279ba04feffffff87ab", "c1a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f400c9a5cd1f6c8a81f5648e39f9810591df1c9a8f1fe97c92e03ecd7c0c016c951983e05473c6e8238cb4c780ea2ce62552b2a3eee068ceffc00517cd7b97e10dad");
+        verify_tap_commitment(&secp, "5120ee9aecb28f5f35ce1f8b5ec80275ac0f81bca4a21b29b4632fb4bcbef8823e6a", "2021a5981b13be29c9d4ea179ea44a8b773ea8c02d68f6f6eefd98de20d4bd055fac", "c13359c284c196b6e80f0cf1d93b6a397cf7ee722f0427b705bd954b88ada8838bd2622fd0e104fc50aa763b43c6a792d7d117029983abd687223b4344a9402c618bba7f5fc3fa8a57491f6842acde88c1e675ca35caea3b1a69ee2c2d9b10f615");
+    }
+
+    #[test]
+    fn bip341_control_blocks() {
+        // Test vectors from BIP341 wallet-test-vectors.json
+        let secp = Secp256k1::verification_only();
+        verify_tap_commitment(&secp, "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", "20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac", "c0ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a");
+        verify_tap_commitment(&secp, "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", "06424950333431", "faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7");
+        verify_tap_commitment(&secp, "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", "202352d137f2f3ab38d1eaa976758873377fa5ebb817372c71e2c542313d4abda8ac", "c0e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f9e31407bffa15fefbf5090b149d53959ecdf3f62b1246780238c24501d5ceaf62645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817");
+
+        let control_block = ControlBlock::from_slice(&Vec::<u8>::from_hex(
+            "faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7"
+        ).unwrap()).unwrap();
+        assert_eq!(control_block.leaf_version, LeafVersion::from_u8(0xfa).unwrap());
+        assert!(!control_block.output_key_parity);
+        assert_eq!(control_block.internal_key.to_string(), "ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592");
+        assert_eq!(control_block.merkle_branch.as_inner().len(), 1);
+    }
+
+    #[test]
+    fn control_block_errors() {
+        let control_block = Vec::<u8>::from_hex(
+            "c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"
+        ).unwrap();
+        assert!(ControlBlock::from_slice(&control_block).is_ok());
+        assert_eq!(ControlBlock::from_slice(&control_block[..32]), Err(TaprootError::InvalidControlBlockSize(32)));
+        let mut extended = control_block.clone();
+        extended.push(0);
+        assert_eq!(ControlBlock::from_slice(&extended), Err(TaprootError::InvalidControlBlockSize(34)));
+        let mut annex_version = control_block.clone();
+        annex_version[0] = 0x51;
+        assert_eq!(ControlBlock::from_slice(&annex_version), Err(TaprootError::InvalidTaprootLeafVersion(0x50)));
+        let mut bad_key = control_block.clone();
+        bad_key[1..].copy_from_slice(&[0xff; 32]);
+        assert!(ControlBlock::from_slice(&bad_key).is_err());
+        let mut too_deep = control_block.clone();
+        too_deep.extend(vec![0; 32 * 129]);
+        assert_eq!(ControlBlock::from_slice(&too_deep), Err(TaprootError::InvalidControlBlockSize(33 + 32 * 129)));
+    }
 }
@@ -0,0 +1,520 @@
+// Rust Bitcoin Library
+// Written in 2021 by
+//     The rust-bitcoin developers.
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Transaction building
+//!
+//! Builds unsigned transactions paying a set of recipients, selecting the
+//! inputs among candidate UTXOs. Inputs are selected by branch and bound,
+//! which looks for a set of inputs paying the recipients and the fee without
+//! change, as Bitcoin Core's `SelectCoinsBnB` does. If there is no such set,
+//! the largest UTXOs are selected and the excess is sent to a change output,
+//! unless it would be dust, in which case it goes to the fee.
+//!
+
+use std::{cmp, error, fmt};
+
+use blockdata::script::Script;
+use blockdata::fee_rate::FeeRate;
+use blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use blockdata::weight::Weight;
+use blockdata::witness::Witness;
+use consensus::encode::VarInt;
+use util::address::Address;
+use util::amount::Amount;
+use util::psbt::PartiallySignedTransaction;
+
+/// Maximum number of selections branch and bound explores
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+/// Weight of a transaction input with an empty `script_sig` and witness
+const EMPTY_INPUT_WEIGHT: u64 = (32 + 4 + 1 + 4) * 4;
+
+/// Weight of the segwit marker and flag, which is counted for every
+/// transaction as inputs may or may not be segwit spends
+const SEGWIT_MARKER_WEIGHT: u64 = 2;
+
+/// A transaction building error
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// The builder has no recipients
+    NoRecipients,
+    /// The recipient at this index would receive a dust amount
+    DustRecipient(usize),
+    /// The candidate UTXOs can't pay for the recipients and the fee
+    InsufficientFunds {
+        /// Amount needed to pay the recipients and the fee of the transaction
+        /// without inputs
+        needed: Amount,
+        /// Sum of the candidate UTXOs, net of the fees to spend them
+        available: Amount,
+    },
+    /// An amount or a weight overflowed, for example because of an absurd
+    /// fee rate
+    Overflow,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoRecipients => f.write_str("the transaction has no recipients"),
+            Error::DustRecipient(i) => write!(f, "recipient {} would receive a dust amount", i),
+            Error::InsufficientFunds { needed, available } => {
+                write!(f, "insufficient funds: {} available of {} needed", available, needed)
+            }
+            Error::Overflow => f.write_str("amount or weight overflow"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// A UTXO the builder may spend
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Utxo {
+    /// The outpoint of the UTXO
+    pub outpoint: OutPoint,
+    /// The output spent
+    pub txout: TxOut,
+    /// Weight the `script_sig` and witness satisfying the output's script
+    /// add to an input with an empty `script_sig` and no witness, including
+    /// the element count of the witness for segwit spends. For example 108
+    /// weight units for P2WPKH and 428 for P2PKH, with 72 byte signatures.
+    pub satisfaction_weight: Weight,
+}
+
+/// Builds an unsigned transaction paying recipients from a set of UTXOs
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TxBuilder {
+    utxos: Vec<Utxo>,
+    recipients: Vec<TxOut>,
+    fee_rate: FeeRate,
+    change_script: Script,
+}
+
+impl TxBuilder {
+    /// Creates a builder paying fees at `fee_rate` and sending change to
+    /// `change_script`
+    pub fn new(fee_rate: FeeRate, change_script: Script) -> TxBuilder {
+        TxBuilder {
+            utxos: vec![],
+            recipients: vec![],
+            fee_rate: fee_rate,
+            change_script: change_script,
+        }
+    }
+
+    /// Adds a UTXO which may be spent
+    pub fn add_utxo(mut self, utxo: Utxo) -> TxBuilder {
+        self.utxos.push(utxo);
+        self
+    }
+
+    /// Adds UTXOs which may be spent
+    pub fn add_utxos<I: IntoIterator<Item = Utxo>>(mut self, utxos: I) -> TxBuilder {
+        self.utxos.extend(utxos);
+        self
+    }
+
+    /// Adds a recipient, which gets an output of the transaction in the
+    /// order recipients are added
+    pub fn add_recipient(self, address: &Address, amount: Amount) -> TxBuilder {
+        self.add_output(address.script_pubkey(), amount)
+    }
+
+    /// Adds an output paying `amount` to `script_pubkey`
+    pub fn add_output(mut self, script_pubkey: Script, amount: Amount) -> TxBuilder {
+        self.recipients.push(TxOut {
+            value: amount.as_sat(),
+            script_pubkey: script_pubkey,
+        });
+        self
+    }
+
+    /// Selects the inputs and builds the unsigned transaction. The change
+    /// output, if any, comes after the recipients.
+    pub fn finish(self) -> Result<Transaction, Error> {
+        self.build().map(|(tx, _)| tx)
+    }
+
+    /// Like [TxBuilder::finish], but returns a PSBT of the transaction. The
+    /// spent outputs are set as the witness UTXOs of the inputs spending
+    /// witness programs.
+    pub fn finish_psbt(self) -> Result<PartiallySignedTransaction, Error> {
+        let (tx, spent) = self.build()?;
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx)
+            .expect("the transaction is unsigned");
+        for (input, utxo) in psbt.inputs.iter_mut().zip(spent) {
+            if utxo.txout.script_pubkey.is_witness_program() {
+                input.witness_utxo = Some(utxo.txout);
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Fee of `weight` at the fee rate in satoshis, `None` on overflow
+    fn fee(&self, weight: Weight) -> Option<u64> {
+        self.fee_rate.fee_wu(weight).map(Amount::as_sat)
+    }
+
+    fn build(self) -> Result<(Transaction, Vec<Utxo>), Error> {
+        if self.recipients.is_empty() {
+            return Err(Error::NoRecipients);
+        }
+        if let Some(i) = self.recipients.iter().position(|out| out.value < out.script_pubkey.dust_value()) {
+            return Err(Error::DustRecipient(i));
+        }
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: self.recipients.clone(),
+        };
+        let change = TxOut {
+            value: 0,
+            script_pubkey: self.change_script.clone(),
+        };
+        let output_value = checked_sum(self.recipients.iter().map(|out| out.value))
+            .ok_or(Error::Overflow)?;
+        let change_fee = self.fee(txout_weight(&change)).ok_or(Error::Overflow)?;
+
+        // Candidates with their effective values, the value left once the
+        // fee to spend them is paid, dropping those not worth spending
+        let mut candidates = self.utxos.iter()
+            .filter_map(|utxo| {
+                let value = Weight::from_wu(EMPTY_INPUT_WEIGHT).checked_add(utxo.satisfaction_weight)
+                    .and_then(|weight| self.fee(weight))
+                    .and_then(|fee| utxo.txout.value.checked_sub(fee));
+                match value {
+                    Some(value) if value > 0 => Some((utxo, value)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(_, value)| cmp::Reverse(value));
+        let values = candidates.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+        let available = checked_sum(values.iter().cloned()).ok_or(Error::Overflow)?;
+
+        // Branch and bound accepts excesses which wouldn't be worth a change
+        // output, as they would be dust or cost more than them in fees
+        let cost_of_change = change_fee.checked_add(change.script_pubkey.dust_value())
+            .ok_or(Error::Overflow)?;
+
+        // The target is the value of the recipients and the fee of the
+        // transaction without inputs, whose input count takes one byte. From
+        // 253 inputs it takes more, so the target is raised and the inputs
+        // selected again until the count fits.
+        let mut input_count_size = 1;
+        let selection = loop {
+            let target = tx.weight()
+                .checked_add(Weight::from_wu(SEGWIT_MARKER_WEIGHT))
+                .and_then(|weight| weight.checked_add(Weight::from_non_witness_data_size(input_count_size - 1)))
+                .and_then(|weight| self.fee(weight))
+                .and_then(|fee| output_value.checked_add(fee))
+                .ok_or(Error::Overflow)?;
+            let selection = match select_bnb(&values, target, cost_of_change) {
+                Some(selection) => selection,
+                None => select_largest_first(&values, target).ok_or_else(|| Error::InsufficientFunds {
+                    needed: Amount::from_sat(target),
+                    available: Amount::from_sat(available),
+                })?,
+            };
+            let selection_count_size = VarInt(selection.len() as u64).len() as u64;
+            if selection_count_size <= input_count_size {
+                break selection;
+            }
+            input_count_size = selection_count_size;
+        };
+
+        let mut spent = Vec::with_capacity(selection.len());
+        let mut satisfaction_weight = Weight::from_wu(SEGWIT_MARKER_WEIGHT);
+        let mut input_value = 0u64;
+        for i in selection {
+            let utxo = candidates[i].0;
+            tx.input.push(TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: Witness::default(),
+            });
+            satisfaction_weight = satisfaction_weight.checked_add(utxo.satisfaction_weight)
+                .ok_or(Error::Overflow)?;
+            input_value = input_value.checked_add(utxo.txout.value).ok_or(Error::Overflow)?;
+            spent.push(utxo.clone());
+        }
+
+        // The excess goes to change unless the change output would be dust,
+        // the selection guaranteeing the inputs pay for the transaction
+        // without it
+        let fee = tx.weight().checked_add(txout_weight(&change))
+            .and_then(|weight| weight.checked_add(satisfaction_weight))
+            .and_then(|weight| self.fee(weight))
+            .ok_or(Error::Overflow)?;
+        let change_value = (input_value - output_value).saturating_sub(fee);
+        if change_value > 0 && change_value >= change.script_pubkey.dust_value() {
+            tx.output.push(TxOut {
+                value: change_value,
+                script_pubkey: change.script_pubkey,
+            });
+        }
+        Ok((tx, spent))
+    }
+}
+
+/// Weight of an output
+fn txout_weight(txout: &TxOut) -> Weight {
+    let script_len = txout.script_pubkey.len();
+    Weight::from_non_witness_data_size((8 + VarInt(script_len as u64).len() + script_len) as u64)
+}
+
+/// Sum of `values`, `None` on overflow
+fn checked_sum<I: Iterator<Item = u64>>(mut values: I) -> Option<u64> {
+    values.try_fold(0u64, |sum, value| sum.checked_add(value))
+}
+
+/// Selects values summing to between `target` and `target + cost_of_change`,
+/// minimizing the excess over `target`, by a depth first search of the
+/// inclusion branches. `values` must be sorted in decreasing order. Returns
+/// the indices of the selected values.
+fn select_bnb(values: &[u64], target: u64, cost_of_change: u64) -> Option<Vec<usize>> {
+    let mut available = values.iter().sum::<u64>();
+    if available < target {
+        return None;
+    }
+    let mut selected: Vec<usize> = vec![];
+    let mut value = 0;
+    let mut best: Option<Vec<usize>> = None;
+    let mut best_excess = u64::max_value();
+    let mut i = 0;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let mut backtrack = false;
+        if value + available < target || value > target.saturating_add(cost_of_change) {
+            // This branch can't reach the target, or is already past it
+            backtrack = true;
+        } else if value >= target {
+            if value - target <= best_excess {
+                best_excess = value - target;
+                best = Some(selected.clone());
+                if best_excess == 0 {
+                    break;
+                }
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let last = match selected.last() {
+                Some(&last) => last,
+                None => break,
+            };
+            // Values skipped after the last inclusion become available
+            // again for its exclusion branch
+            i -= 1;
+            while i > last {
+                available += values[i];
+                i -= 1;
+            }
+            value -= values[i];
+            selected.pop();
+        } else {
+            available -= values[i];
+            // Excluding a value and including an equal one next gives the
+            // same selections as the branch which included the first one
+            let previous_included = match selected.last() {
+                Some(&last) => last + 1 == i,
+                None => true,
+            };
+            if previous_included || values[i] != values[i - 1] {
+                selected.push(i);
+                value += values[i];
+            }
+        }
+        i += 1;
+    }
+    best
+}
+
+/// Selects the largest values until their sum reaches `target`. `values`
+/// must be sorted in decreasing order.
+fn select_largest_first(values: &[u64], target: u64) -> Option<Vec<usize>> {
+    let mut value = 0;
+    for (i, v) in values.iter().enumerate() {
+        value += v;
+        if value >= target {
+            return Some((0..i + 1).collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    use hashes::Hash;
+    use hash_types::Txid;
+    use util::ecdsa::PublicKey;
+
+    fn p2wpkh_script(i: u8) -> Script {
+        let key = PublicKey::from_str("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        let mut script = Script::new_v0_wpkh(&key.wpubkey_hash().unwrap()).into_bytes();
+        script[2] = i;
+        Script::from(script)
+    }
+
+    fn utxo(i: u8, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::hash(&[i]), 0),
+            txout: TxOut {
+                value: value,
+                script_pubkey: p2wpkh_script(i),
+            },
+            satisfaction_weight: Weight::from_wu(108),
+        }
+    }
+
+    /// Weight of the transaction once its P2WPKH inputs are satisfied
+    fn satisfied_weight(tx: &Transaction) -> Weight {
+        tx.weight() + Weight::from_wu(SEGWIT_MARKER_WEIGHT + 108 * tx.input.len() as u64)
+    }
+
+    #[test]
+    fn select_bnb_test() {
+        assert_eq!(select_bnb(&[5, 4, 3, 2], 9, 0), Some(vec![0, 1]));
+        assert_eq!(select_bnb(&[5, 4, 3, 2], 6, 0), Some(vec![1, 3]));
+        assert_eq!(select_bnb(&[5, 4, 3, 2], 1, 0), None);
+        assert_eq!(select_bnb(&[5, 4, 3, 2], 1, 1), Some(vec![3]));
+        assert_eq!(select_bnb(&[5, 4, 3, 2], 15, 10), None);
+        assert_eq!(select_bnb(&[5, 5, 5, 2], 12, 0), Some(vec![0, 1, 3]));
+        assert_eq!(select_bnb(&[8, 7, 7], 14, 0), Some(vec![1, 2]));
+        assert_eq!(select_bnb(&[], 1, 10), None);
+        // The smallest excess within the window wins
+        assert_eq!(select_bnb(&[10, 7, 4], 11, 5), Some(vec![1, 2]));
+
+        assert_eq!(select_largest_first(&[5, 4, 3, 2], 6), Some(vec![0, 1]));
+        assert_eq!(select_largest_first(&[5, 4, 3, 2], 15), None);
+    }
+
+    #[test]
+    fn exact_match_test() {
+        let recipient = p2wpkh_script(0xff);
+        let builder = TxBuilder::new(FeeRate::MIN_RELAY, p2wpkh_script(0xfe))
+            .add_output(recipient.clone(), Amount::from_sat(50_000));
+        // Find the fee without change, then fund the transaction exactly
+        let mut tx = builder.clone().add_utxo(utxo(1, 1_000_000)).finish().unwrap();
+        tx.output.truncate(1);
+        let fee = builder.fee(satisfied_weight(&tx)).unwrap();
+        let tx = builder
+            .add_utxos(vec![utxo(1, 1_000_000), utxo(2, 50_000 + fee), utxo(3, 20_000)])
+            .finish()
+            .unwrap();
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output, OutPoint::new(Txid::hash(&[2]), 0));
+        assert_eq!(tx.output, vec![TxOut { value: 50_000, script_pubkey: recipient }]);
+    }
+
+    #[test]
+    fn change_test() {
+        let change_script = p2wpkh_script(0xfe);
+        let builder = TxBuilder::new(FeeRate::from_sat_per_vb(2).unwrap(), change_script.clone())
+            .add_recipient(&Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap(), Amount::from_sat(60_000))
+            .add_utxos(vec![utxo(1, 10_000), utxo(2, 40_000), utxo(3, 30_000)]);
+
+        let tx = builder.clone().finish().unwrap();
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.input[0].previous_output, OutPoint::new(Txid::hash(&[2]), 0));
+        assert_eq!(tx.input[1].previous_output, OutPoint::new(Txid::hash(&[3]), 0));
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[1].script_pubkey, change_script);
+        let fee = 70_000 - tx.output[0].value - tx.output[1].value;
+        assert_eq!(fee, builder.fee(satisfied_weight(&tx)).unwrap());
+
+        let psbt = builder.finish_psbt().unwrap();
+        assert_eq!(psbt.global.unsigned_tx, tx);
+        assert_eq!(psbt.inputs[0].witness_utxo, Some(utxo(2, 40_000).txout));
+        assert_eq!(psbt.inputs[1].witness_utxo, Some(utxo(3, 30_000).txout));
+    }
+
+    #[test]
+    fn dust_change_test() {
+        let recipient = p2wpkh_script(0xff);
+        let builder = TxBuilder::new(FeeRate::MIN_RELAY, p2wpkh_script(0xfe))
+            .add_output(recipient.clone(), Amount::from_sat(50_000));
+        // The excess over the amount and fee is too small for a change output
+        let mut tx = builder.clone().add_utxo(utxo(1, 1_000_000)).finish().unwrap();
+        tx.output.truncate(1);
+        let fee = builder.fee(satisfied_weight(&tx)).unwrap();
+        let tx = builder.add_utxo(utxo(1, 50_000 + fee + 300)).finish().unwrap();
+        assert_eq!(tx.output, vec![TxOut { value: 50_000, script_pubkey: recipient }]);
+    }
+
+    #[test]
+    fn error_test() {
+        let builder = TxBuilder::new(FeeRate::MIN_RELAY, p2wpkh_script(0xfe)).add_utxo(utxo(1, 10_000));
+        assert_eq!(builder.clone().finish(), Err(Error::NoRecipients));
+        assert_eq!(
+            builder.clone()
+                .add_output(p2wpkh_script(0), Amount::from_sat(1000))
+                .add_output(p2wpkh_script(0), Amount::from_sat(293))
+                .finish(),
+            Err(Error::DustRecipient(1))
+        );
+        match builder.add_utxo(utxo(2, 50)).add_output(p2wpkh_script(0), Amount::from_sat(20_000)).finish() {
+            Err(Error::InsufficientFunds { needed, available }) => {
+                assert!(needed > Amount::from_sat(20_000));
+                // The second UTXO isn't worth spending
+                assert_eq!(available, Amount::from_sat(10_000 - 68));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn input_count_test() {
+        // From 253 inputs, the input count takes two more bytes the fee must
+        // pay for. Without them, 253 inputs would be an exact match.
+        let builder = TxBuilder::new(FeeRate::MIN_RELAY, p2wpkh_script(0xfe))
+            .add_output(p2wpkh_script(0xff), Amount::from_sat(252_958))
+            .add_utxos((0..255).map(|i| utxo(i, 1_068)));
+        let tx = builder.clone().finish().unwrap();
+        assert_eq!(tx.input.len(), 254);
+        let output_value = tx.output.iter().map(|out| out.value).sum::<u64>();
+        assert!(254 * 1_068 - output_value >= builder.fee(satisfied_weight(&tx)).unwrap());
+    }
+
+    #[test]
+    fn overflow_test() {
+        let recipient = p2wpkh_script(0xff);
+        let builder = TxBuilder::new(FeeRate::from_sat_per_kwu(u64::max_value()), p2wpkh_script(0xfe))
+            .add_output(recipient.clone(), Amount::from_sat(50_000))
+            .add_utxo(utxo(1, u64::max_value()));
+        assert_eq!(builder.finish(), Err(Error::Overflow));
+
+        let builder = TxBuilder::new(FeeRate::MIN_RELAY, p2wpkh_script(0xfe))
+            .add_output(recipient.clone(), Amount::from_sat(50_000));
+        assert_eq!(
+            builder.clone().add_utxo(utxo(1, u64::max_value())).add_utxo(utxo(2, u64::max_value())).finish(),
+            Err(Error::Overflow)
+        );
+        // A UTXO whose satisfaction weight overflows can't be spent
+        let mut absurd = utxo(1, 1_000_000);
+        absurd.satisfaction_weight = Weight::from_wu(u64::max_value());
+        match builder.add_utxo(absurd).finish() {
+            Err(Error::InsufficientFunds { available, .. }) => assert_eq!(available, Amount::from_sat(0)),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}